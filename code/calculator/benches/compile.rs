@@ -0,0 +1,20 @@
+use calculator::parsemath::parser::Parser;
+use criterion::{criterion_group, criterion_main, Criterion};
+use std::hint::black_box;
+
+const FORMULA: &str = "3 * x^2 + sin(x) / (1 + x) - sqrt(abs(x)) * 2";
+
+fn compile_vs_evaluate(c: &mut Criterion) {
+    // `evaluate` has no variable bindings, so the tree walker gets the value substituted into the formula
+    let tree = Parser::new(&FORMULA.replace('x', "1.5")).unwrap().parse().unwrap();
+    let program = Parser::new(FORMULA).unwrap().parse_with_variables(&["x"]).unwrap().compile().unwrap();
+    assert_eq!(tree.evaluate(), program.eval(&[1.5]));
+
+    let mut group = c.benchmark_group("formula");
+    group.bench_function("Expression::evaluate", |b| b.iter(|| black_box(&tree).evaluate().unwrap()));
+    group.bench_function("Program::eval", |b| b.iter(|| program.eval(black_box(&[1.5])).unwrap()));
+    group.finish();
+}
+
+criterion_group!(benches, compile_vs_evaluate);
+criterion_main!(benches);
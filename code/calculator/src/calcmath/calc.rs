@@ -1,51 +1,316 @@
-use crate::parsemath::parser::Parser;
+use crate::calcmath::plot::{self, Chart};
+use crate::parsemath::ast::{EvalError, Expression, Statement};
+use crate::parsemath::functions::FunctionRegistry;
+use crate::parsemath::numeric::{
+    Backend, ComplexBackend, DecimalBackend, FloatBackend, IntegerBackend, NumericMode, OutputBase, RationalBackend,
+};
+use crate::parsemath::parser::{Parser, ParserError};
+use crate::parsemath::solver;
+use crate::parsemath::symbolic::SymbolicError;
+use crate::parsemath::token::Span;
+use crate::parsemath::units::UnitBackend;
 use mockall::automock;
+use serde_json::json;
+use thiserror::Error;
 
 #[automock]
 pub trait Console {
     fn readline(&self) -> String;
     fn println(&self, text: &str);
     fn print(&self, text: &str);
+    fn eprintln(&self, text: &str);
+    // names offered when completing the next line that is read
+    fn set_completions(&self, names: &[String]);
+}
+
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub enum OutputFormat {
+    Plain,
+    Json,
+}
+
+#[derive(Error, Debug, PartialEq, Clone)]
+pub enum CalculatorError {
+    #[error(transparent)]
+    Parser(#[from] ParserError),
+    #[error(transparent)]
+    Eval(#[from] EvalError),
+    #[error(transparent)]
+    Symbolic(#[from] SymbolicError),
 }
 
 pub struct Calculator<'a> {
     console: &'a dyn Console,
+    registry: FunctionRegistry,
+    mode: NumericMode,
 }
 
 impl<'a> Calculator<'a> {
     pub fn new(console: &'a dyn Console) -> Calculator<'a> {
-        Calculator { console }
+        Calculator::with_registry(console, FunctionRegistry::with_builtins())
+    }
+
+    pub fn with_registry(console: &'a dyn Console, registry: FunctionRegistry) -> Calculator<'a> {
+        Calculator { console, registry, mode: NumericMode::Float }
+    }
+
+    pub fn registry_mut(&mut self) -> &mut FunctionRegistry {
+        &mut self.registry
+    }
+
+    pub fn mode(&self) -> NumericMode {
+        self.mode
+    }
+
+    pub fn set_mode(&mut self, mode: NumericMode) {
+        self.mode = mode;
+    }
+
+    // functions complete with their opening parenthesis
+    pub fn completions(&self) -> Vec<String> {
+        let functions = self.registry.function_names().into_iter().map(|name| format!("{}(", name));
+        let values = self.registry.value_names().into_iter().map(str::to_string);
+        functions.chain(values).collect()
     }
 
     pub fn run(&mut self) {
         self.console.println("### Calculator ver. 1.0 ###");
 
         loop {
+            self.console.set_completions(&self.completions());
             self.console.print(">>> ");
-            let input = self.console.readline().to_uppercase();
+            let input = self.console.readline();
 
-            if input == "EXIT" {
+            if input.eq_ignore_ascii_case("EXIT") {
                 break;
             }
+            if input.is_empty() {
+                continue;
+            }
 
-            match Parser::new(&input)  {
-                Ok(mut parser) => {
-                    match parser.parse() {
-                        Ok(ast) => {
-                            let result = ast.evaluate();
-                            self.console.println(&format!("{}", result));
-                        }
-                        Err(error) => {
-                            self.console.println(&format!("{}", error));
-                        }
-                    }
+            if let Some(command) = input.strip_prefix(':') {
+                match self.execute_command(command) {
+                    Ok(lines) => lines.iter().for_each(|line| self.console.println(line)),
+                    Err(error) => self.console.println(&error),
                 }
-                Err(error) => {
+                continue;
+            }
+
+            match self.evaluate(&input) {
+                Ok(result) => self.console.println(&result),
+                Err(CalculatorError::Parser(error)) => {
+                    self.console.println(&input);
+                    self.console.println(&caret_marker(&input, error.span()));
                     self.console.println(&format!("{}", error));
                 }
+                Err(error) => self.console.println(&format!("{}", error)),
+            }
+        }
+    }
+
+    // Evaluates each line of a script, stopping at the first error. Returns whether every line succeeded.
+    pub fn run_script(&mut self, name: &str, source: &str, format: OutputFormat) -> bool {
+        for (index, line) in source.lines().enumerate() {
+            let input = line.trim();
+            if input.is_empty() || input.starts_with('#') {
+                continue;
+            }
+
+            let result = match input.strip_prefix(':') {
+                Some(command) => self.execute_command(command).map_err(|message| (message, None)),
+                None => self.evaluate(input).map(|result| vec![result]).map_err(|error| {
+                    let span = match &error {
+                        CalculatorError::Parser(error) => Some(error.span()),
+                        _ => None,
+                    };
+                    (error.to_string(), span)
+                }),
+            };
+
+            let line_number = index + 1;
+            match (result, format) {
+                (Ok(lines), OutputFormat::Plain) => lines.iter().for_each(|line| self.console.println(line)),
+                (Ok(lines), OutputFormat::Json) => {
+                    let record = json!({ "line": line_number, "input": input, "result": lines.join("\n") });
+                    self.console.println(&record.to_string());
+                }
+                (Err((message, span)), OutputFormat::Plain) => {
+                    self.console.eprintln(&format!("{}:{}: {}", name, line_number, message));
+                    if let Some(span) = span {
+                        self.console.eprintln(&format!("    {}", input));
+                        self.console.eprintln(&format!("    {}", caret_marker(input, span)));
+                    }
+                    return false;
+                }
+                (Err((message, _)), OutputFormat::Json) => {
+                    let record = json!({ "line": line_number, "input": input, "error": message });
+                    self.console.println(&record.to_string());
+                    return false;
+                }
+            }
+        }
+
+        true
+    }
+
+    pub fn evaluate(&mut self, input: &str) -> Result<String, CalculatorError> {
+        let mut parser = match self.mode {
+            NumericMode::Programmer(_) => Parser::with_exact_integers(input, &self.registry)?,
+            _ => Parser::with_registry(input, &self.registry)?,
+        };
+        let ast = match parser.parse_statement()? {
+            Statement::Expression(ast) => ast,
+            Statement::FunctionDefinition { name, params, body } => {
+                let definition = format_definition(&name, &params, &body);
+                self.registry.define(&name, params, body);
+                return Ok(definition);
+            }
+            Statement::Assignment { name, value } => {
+                let (result, literal) = self.evaluate_ast(&value)?;
+                self.registry.assign(&name, literal);
+                return Ok(format!("{} = {}", name, result));
+            }
+        };
+
+        // on its own line a solver call lists every solution, inside an expression it needs a single one
+        if let Expression::Call(name, _) = &ast
+            && solver::arity(name).is_some()
+        {
+            let solutions = ast.solutions(&self.registry)?;
+            let values = solutions
+                .values
+                .iter()
+                .map(|value| Ok(format!("{} = {}", solutions.variable, self.evaluate_ast(&Expression::Number(*value))?.0)))
+                .collect::<Result<Vec<_>, EvalError>>()?;
+            return Ok(values.join(", "));
+        }
+
+        if ast.is_symbolic() {
+            return Ok(ast.expand_symbolic()?.simplify().to_string());
+        }
+
+        Ok(self.evaluate_ast(&ast)?.0)
+    }
+
+    // evaluates in the current mode, returning the formatted result and a literal that reproduces it
+    fn evaluate_ast(&self, ast: &Expression) -> Result<(String, Expression), EvalError> {
+        match self.mode {
+            NumericMode::Float => self.evaluate_in(FloatBackend, ast),
+            NumericMode::Rational => self.evaluate_in(RationalBackend, ast),
+            NumericMode::Decimal(precision) => self.evaluate_in(DecimalBackend::new(precision), ast),
+            NumericMode::Complex => self.evaluate_in(ComplexBackend, ast),
+            NumericMode::Programmer(base) => self.evaluate_in(IntegerBackend::new(base), ast),
+        }
+    }
+
+    fn evaluate_in<B: Backend>(&self, backend: B, ast: &Expression) -> Result<(String, Expression), EvalError> {
+        let value = ast.evaluate_in(&UnitBackend::new(backend), &self.registry)?;
+        Ok((value.to_string(), value.to_expression()))
+    }
+
+    fn execute_command(&mut self, command: &str) -> Result<Vec<String>, String> {
+        let (name, args) = command.split_once(' ').unwrap_or((command, ""));
+
+        match name {
+            "mode" if args.trim().is_empty() => Ok(vec![format!("Mode: {}", self.mode)]),
+            "mode" => {
+                self.mode = args.parse::<NumericMode>()?;
+                Ok(vec![format!("Mode: {}", self.mode)])
+            }
+            "hex" | "bin" | "dec" => {
+                let base = name.parse::<OutputBase>()?;
+                self.mode = NumericMode::Programmer(base);
+                Ok(vec![format!("Mode: {}", self.mode)])
+            }
+            "funcs" => {
+                let functions = self.registry.user_functions();
+                if functions.is_empty() {
+                    return Ok(vec!["No functions defined".to_string()]);
+                }
+                Ok(functions
+                    .into_iter()
+                    .map(|(name, function)| format_definition(name, &function.params, &function.body))
+                    .collect())
+            }
+            "undef" => match self.registry.undefine(args.trim()) {
+                Some(_) => Ok(vec![format!("Removed function '{}'", args.trim())]),
+                None => Err(format!("Unknown function '{}'", args.trim())),
+            },
+            "plot" => self.plot(args),
+            _ => Err(format!("Unknown command ':{}'", name)),
+        }
+    }
+
+    fn plot(&self, args: &str) -> Result<Vec<String>, String> {
+        let usage = || "Usage: :plot f(x)[, g(x)...] from a to b".to_string();
+        let (functions, range) = args.rsplit_once(" from ").ok_or_else(usage)?;
+        let (from, to) = range.split_once(" to ").ok_or_else(usage)?;
+        let functions = split_top_level(functions);
+        if functions.iter().any(|function| function.is_empty()) {
+            return Err(usage());
+        }
+
+        let programs = functions
+            .iter()
+            .map(|function| {
+                let ast = Parser::with_registry(function, &self.registry)
+                    .and_then(|mut parser| parser.parse_with_variables(&[PLOT_VARIABLE]))
+                    .map_err(|error| error.to_string())?;
+                ast.compile_with(&self.registry).map_err(|error| error.to_string())
+            })
+            .collect::<Result<Vec<_>, String>>()?;
+
+        let (from, to) = (self.plot_bound(from)?, self.plot_bound(to)?);
+        if from >= to {
+            return Err(format!("Empty range: {} is not below {}", from, to));
+        }
+
+        let mut lines = vec![plot::legend(&functions)];
+        lines.extend(Chart::default().render(from, to, &programs)?);
+        Ok(lines)
+    }
+
+    fn plot_bound(&self, bound: &str) -> Result<f64, String> {
+        let ast = Parser::with_registry(bound, &self.registry)
+            .and_then(|mut parser| parser.parse())
+            .map_err(|error| error.to_string())?;
+        ast.evaluate_with(&self.registry).map_err(|error| error.to_string())
+    }
+}
+
+const PLOT_VARIABLE: &str = "x";
+
+// splits on commas that are not nested inside brackets, so `max(x, 1), x` gives two functions
+fn split_top_level(text: &str) -> Vec<&str> {
+    let mut parts = Vec::new();
+    let (mut depth, mut start) = (0, 0);
+    for (index, c) in text.char_indices() {
+        match c {
+            '(' => depth += 1,
+            ')' => depth -= 1,
+            ',' if depth == 0 => {
+                parts.push(text[start..index].trim());
+                start = index + 1;
             }
+            _ => {}
         }
     }
+    parts.push(text[start..].trim());
+    parts
+}
+
+fn format_definition(name: &str, params: &[String], body: &Expression) -> String {
+    format!("{}({}) = {}", name, params.join(", "), body)
+}
+
+pub fn caret_marker(input: &str, span: Span) -> String {
+    let start = span.start.min(input.len());
+    let end = span.end.clamp(start, input.len());
+
+    let column = input[..start].chars().count();
+    let width = input[start..end].chars().count().max(1);
+
+    format!("{}^{}", " ".repeat(column), "~".repeat(width - 1))
 }
 
 #[cfg(test)]
@@ -53,11 +318,18 @@ mod tests {
     use mockall::predicate::*;
     use mockall::*;
 
-    use crate::calcmath::calc::{Calculator, MockConsole};
+    use crate::calcmath::calc::{caret_marker, Calculator, CalculatorError, MockConsole, OutputFormat};
+    use crate::parsemath::ast::EvalError;
+    use crate::parsemath::parser::Parser;
+    use crate::parsemath::token::Span;
+    use rstest::rstest;
+    use crate::parsemath::functions::Arity;
+    use crate::parsemath::numeric::{NumericMode, OutputBase};
 
     #[test]
     fn calculator_run_loop_evaluates_expressions() {
         let mut mock_console = MockConsole::new();
+        mock_console.expect_set_completions().returning(|_| ());
 
         let mut seq = Sequence::new();
 
@@ -126,6 +398,7 @@ mod tests {
     #[test]
     fn calculator_run_loop_handles_parser_errors() {
         let mut mock_console = MockConsole::new();
+        mock_console.expect_set_completions().returning(|_| ());
 
         let mut seq = Sequence::new();
 
@@ -148,6 +421,20 @@ mod tests {
             .in_sequence(&mut seq)
             .returning(|| "2#".to_string());
 
+        mock_console
+            .expect_println()
+            .times(1)
+            .in_sequence(&mut seq)
+            .with(eq("2#"))
+            .returning(|_| ());
+
+        mock_console
+            .expect_println()
+            .times(1)
+            .in_sequence(&mut seq)
+            .with(eq(" ^"))
+            .returning(|_| ());
+
         mock_console
             .expect_println()
             .times(1)
@@ -171,4 +458,585 @@ mod tests {
 
         calculator.run();
     }
+
+    #[test]
+    fn calculator_run_loop_evaluates_functions_and_constants() {
+        let mut mock_console = MockConsole::new();
+        mock_console.expect_set_completions().returning(|_| ());
+
+        let mut seq = Sequence::new();
+
+        mock_console
+            .expect_println()
+            .with(eq("### Calculator ver. 1.0 ###"))
+            .times(1)
+            .in_sequence(&mut seq)
+            .returning(|_| ());
+
+        for (input, output) in [("max(1, sqrt(16), 2)", "4"), ("cos(pi)", "-1"), ("sqrt(-1)", "Domain error in 'sqrt(-1)': sqrt of negative number -1"), ("10 / (5 - 5)", "Division by zero in '10 / (5 - 5)'"), ("triple(2)", "6")] {
+            mock_console.expect_print()
+                .with(eq(">>> "))
+                .times(1)
+                .in_sequence(&mut seq)
+                .returning(|_| ());
+
+            mock_console
+                .expect_readline()
+                .times(1)
+                .in_sequence(&mut seq)
+                .returning(move || input.to_string());
+
+            mock_console
+                .expect_println()
+                .times(1)
+                .in_sequence(&mut seq)
+                .with(eq(output))
+                .returning(|_| ());
+        }
+
+        mock_console.expect_print()
+            .with(eq(">>> "))
+            .times(1)
+            .in_sequence(&mut seq)
+            .returning(|_| ());
+
+        mock_console.expect_readline()
+            .times(1)
+            .in_sequence(&mut seq)
+            .returning(|| "exit".to_string());
+
+
+        let mut calculator = Calculator::new(&mock_console);
+        calculator.registry_mut().register("triple", Arity::Exact(1), |args| Ok(args[0] * 3.0));
+
+        calculator.run();
+    }
+
+    #[test]
+    fn calculator_run_loop_switches_numeric_mode() {
+        let mut mock_console = MockConsole::new();
+        mock_console.expect_set_completions().returning(|_| ());
+
+        let mut seq = Sequence::new();
+
+        mock_console
+            .expect_println()
+            .with(eq("### Calculator ver. 1.0 ###"))
+            .times(1)
+            .in_sequence(&mut seq)
+            .returning(|_| ());
+
+        let session = [
+            ("0.1 + 0.2", "0.30000000000000004"),
+            (":mode", "Mode: float"),
+            (":mode rational", "Mode: rational"),
+            ("0.1 + 0.2", "0.3"),
+            ("1 / 3", "1/3"),
+            (":mode decimal 4", "Mode: decimal 4"),
+            ("2 / 3", "0.6667"),
+            (":mode hex", "Unknown mode 'hex'. Available modes: float, rational, decimal [precision], complex, programmer [dec|hex|bin]"),
+            (":foo", "Unknown command ':foo'"),
+        ];
+
+        for (input, output) in session {
+            mock_console.expect_print()
+                .with(eq(">>> "))
+                .times(1)
+                .in_sequence(&mut seq)
+                .returning(|_| ());
+
+            mock_console
+                .expect_readline()
+                .times(1)
+                .in_sequence(&mut seq)
+                .returning(move || input.to_string());
+
+            mock_console
+                .expect_println()
+                .times(1)
+                .in_sequence(&mut seq)
+                .with(eq(output))
+                .returning(|_| ());
+        }
+
+        mock_console.expect_print()
+            .with(eq(">>> "))
+            .times(1)
+            .in_sequence(&mut seq)
+            .returning(|_| ());
+
+        mock_console.expect_readline()
+            .times(1)
+            .in_sequence(&mut seq)
+            .returning(|| "exit".to_string());
+
+
+        let mut calculator = Calculator::new(&mock_console);
+
+        calculator.run();
+
+        assert_eq!(calculator.mode(), NumericMode::Decimal(4));
+    }
+
+    #[rstest]
+    #[case::derivative("diff(x^2 + 3 * x, x)", "2 * x + 3")]
+    #[case::derivative_in_expression("1 + diff(x^2, x)", "2 * x + 1")]
+    #[case::simplify("simplify(a * b + b * a)", "2 * a * b")]
+    #[case::numeric("2^10", "1024")]
+    fn calculator_evaluates_symbolic_expressions(#[case] input: &str, #[case] expected: &str) {
+        let console = MockConsole::new();
+        let mut calculator = Calculator::new(&console);
+
+        assert_eq!(calculator.evaluate(input), Ok(expected.to_string()));
+    }
+
+    #[test]
+    fn calculator_reports_symbolic_errors() {
+        let console = MockConsole::new();
+        let mut calculator = Calculator::new(&console);
+
+        let error = calculator.evaluate("diff(min(x, 2), x)").unwrap_err();
+
+        assert_eq!(format!("{}", error), "Cannot differentiate 'min(x, 2)'");
+    }
+
+    #[test]
+    fn calculator_offers_completions() {
+        let console = MockConsole::new();
+        let mut calculator = Calculator::new(&console);
+
+        calculator.evaluate("sq(x) = x * x").unwrap();
+        calculator.evaluate("radius = 2").unwrap();
+        let completions = calculator.completions();
+
+        assert!(completions.contains(&"sin(".to_string()));
+        assert!(completions.contains(&"sq(".to_string()));
+        assert!(completions.contains(&"radius".to_string()));
+        assert!(completions.contains(&"pi".to_string()));
+    }
+
+    #[rstest]
+    #[case::quadratic("solve(x^2 - 2 * x = 3, x)", "x = -1, x = 3")]
+    #[case::linear("solve(2 * t + 1 = 0, t)", "t = -0.5")]
+    #[case::roots("roots(x^3 - x)", "x = -1, x = 0, x = 1")]
+    #[case::single_solution_as_value("2 * solve(x^3 = 8, x)", "4")]
+    #[case::numeric("solve(ln(x) = 1, x)", "x = 2.718281828459045")]
+    fn calculator_solves_equations(#[case] input: &str, #[case] expected: &str) {
+        let console = MockConsole::new();
+        let mut calculator = Calculator::new(&console);
+
+        assert_eq!(calculator.evaluate(input), Ok(expected.to_string()));
+    }
+
+    #[test]
+    fn calculator_solves_with_user_functions_and_variables() {
+        let console = MockConsole::new();
+        let mut calculator = Calculator::new(&console);
+
+        calculator.evaluate("target = 9").unwrap();
+        calculator.evaluate("root(a) = solve(x^2 = a, x, 0, a)").unwrap();
+
+        assert_eq!(calculator.evaluate("solve(x^2 = target, x)"), Ok("x = -3, x = 3".to_string()));
+        assert_eq!(calculator.evaluate("root(16) + root(target)"), Ok("7".to_string()));
+    }
+
+    #[test]
+    fn calculator_needs_a_single_solution_inside_expressions() {
+        let console = MockConsole::new();
+        let mut calculator = Calculator::new(&console);
+
+        let error = calculator.evaluate("1 + solve(x^2 = 1, x)").unwrap_err();
+
+        assert_eq!(
+            format!("{}", error),
+            "Domain error in 'solve(x^2 = 1, x)': 2 solutions, only a single solution can be used as a value"
+        );
+    }
+
+    #[test]
+    fn calculator_defines_and_calls_user_functions() {
+        let console = MockConsole::new();
+        let mut calculator = Calculator::new(&console);
+
+        assert_eq!(calculator.evaluate("f(x, y) = x^2 + y"), Ok("f(x, y) = x^2 + y".to_string()));
+        assert_eq!(calculator.evaluate("g(x) = 2 * f(x, 1)"), Ok("g(x) = 2 * f(x, 1)".to_string()));
+        assert_eq!(calculator.evaluate("f(2, 3)"), Ok("7".to_string()));
+        assert_eq!(calculator.evaluate("g(3) + f(1, 1)"), Ok("22".to_string()));
+    }
+
+    #[test]
+    fn calculator_user_functions_follow_numeric_mode() {
+        let console = MockConsole::new();
+        let mut calculator = Calculator::new(&console);
+        calculator.set_mode(NumericMode::Rational);
+
+        calculator.evaluate("third(x) = x / 3").unwrap();
+
+        assert_eq!(calculator.evaluate("third(1) + third(1)"), Ok("2/3".to_string()));
+    }
+
+    #[rstest]
+    #[case::undefined_parameter("f(x) = x + y", "Syntax error: Undefined parameter 'y' in definition of 'f'.")]
+    #[case::duplicate_parameter("f(x, x) = x", "Syntax error: Duplicate parameter 'x'.")]
+    #[case::builtin("sin(x) = x", "Syntax error: Cannot redefine built-in function 'sin'.")]
+    #[case::recursive_arity("f(x) = f(x, 1)", "Syntax error: Function 'f' expects 1 argument(s), got 2")]
+    fn calculator_rejects_invalid_definitions(#[case] input: &str, #[case] expected: &str) {
+        let console = MockConsole::new();
+        let mut calculator = Calculator::new(&console);
+
+        let error = calculator.evaluate(input).unwrap_err();
+
+        assert_eq!(format!("{}", error), expected);
+    }
+
+    #[test]
+    fn calculator_limits_recursion_depth() {
+        let console = MockConsole::new();
+        let mut calculator = Calculator::new(&console);
+
+        calculator.evaluate("f(x) = f(x + 1)").unwrap();
+        let error = calculator.evaluate("f(1)").unwrap_err();
+
+        assert!(matches!(error, CalculatorError::Eval(EvalError::RecursionLimit(_))));
+    }
+
+    #[rstest]
+    #[case::sum_of_lengths("3 m + 20 cm", "3.2 m")]
+    #[case::distance("60 km/h * 2 h", "120 km")]
+    #[case::conversion("5 kg -> lb", "11.023113109243878 lb")]
+    #[case::user_function("speed(d, t) = d / t", "speed(d, t) = d / t")]
+    fn calculator_evaluates_units(#[case] input: &str, #[case] expected: &str) {
+        let console = MockConsole::new();
+        let mut calculator = Calculator::new(&console);
+
+        assert_eq!(calculator.evaluate(input), Ok(expected.to_string()));
+    }
+
+    #[test]
+    fn calculator_units_follow_numeric_mode() {
+        let console = MockConsole::new();
+        let mut calculator = Calculator::new(&console);
+        calculator.set_mode(NumericMode::Decimal(2));
+
+        calculator.evaluate("speed(d, t) = d / t -> km/h").unwrap();
+
+        assert_eq!(calculator.evaluate("speed(100 m, 9.58 s)"), Ok("37.58 km/h".to_string()));
+    }
+
+    #[test]
+    fn calculator_reports_incompatible_units() {
+        let console = MockConsole::new();
+        let mut calculator = Calculator::new(&console);
+
+        let error = calculator.evaluate("m + s").unwrap_err();
+
+        assert_eq!(format!("{}", error), "Incompatible units in 'm + s': m and s");
+    }
+
+    #[rstest]
+    #[case::product("(1 + 2i) * (3 - i)", "5+5i")]
+    #[case::square_root("sqrt(-1)", "i")]
+    #[case::square("i^2", "-1")]
+    #[case::modulus("abs(3 + 4i)", "5")]
+    #[case::conjugate("conj(3 + 4i) / 5", "0.6-0.8i")]
+    #[case::parts("re(2 - 3i) + im(2 - 3i)", "-1")]
+    #[case::argument("arg(-1)", "3.141592653589793")]
+    #[case::real_function("sin(0) + 2i", "2i")]
+    fn calculator_evaluates_in_complex_mode(#[case] input: &str, #[case] expected: &str) {
+        let console = MockConsole::new();
+        let mut calculator = Calculator::new(&console);
+        calculator.set_mode(NumericMode::Complex);
+
+        assert_eq!(calculator.evaluate(input), Ok(expected.to_string()));
+    }
+
+    #[test]
+    fn calculator_remembers_complex_values() {
+        let console = MockConsole::new();
+        let mut calculator = Calculator::new(&console);
+        calculator.set_mode(NumericMode::Complex);
+
+        assert_eq!(calculator.evaluate("z = 1 - 2i"), Ok("z = 1-2i".to_string()));
+        assert_eq!(calculator.evaluate("z * conj(z)"), Ok("5".to_string()));
+    }
+
+    #[rstest]
+    #[case::float_mode(NumericMode::Float, "2 + i", "Domain error in 'i': the imaginary unit requires complex mode")]
+    #[case::real_only_function(NumericMode::Complex, "max(i, 1)", "Domain error in 'max(i, 1)': i is not a real number")]
+    fn calculator_reports_complex_errors(#[case] mode: NumericMode, #[case] input: &str, #[case] expected: &str) {
+        let console = MockConsole::new();
+        let mut calculator = Calculator::new(&console);
+        calculator.set_mode(mode);
+
+        let error = calculator.evaluate(input).unwrap_err();
+
+        assert_eq!(format!("{}", error), expected);
+    }
+
+    #[rstest]
+    #[case::masks("0xFF & 0b1010", "10")]
+    #[case::shift_and_or("1 << 4 | 1", "17")]
+    #[case::xor("0o17 xor 0b101", "10")]
+    #[case::not("~0", "-1")]
+    #[case::separators("1_000_000 / 3", "333333")]
+    #[case::arithmetic_before_shift("1 << 2 + 1", "8")]
+    #[case::beyond_f64_precision("9007199254740993", "9007199254740993")]
+    #[case::largest("9223372036854775807", "9223372036854775807")]
+    #[case::smallest("-9223372036854775807 - 1", "-9223372036854775808")]
+    fn calculator_evaluates_in_programmer_mode(#[case] input: &str, #[case] expected: &str) {
+        let console = MockConsole::new();
+        let mut calculator = Calculator::new(&console);
+        calculator.set_mode(NumericMode::Programmer(OutputBase::Dec));
+
+        assert_eq!(calculator.evaluate(input), Ok(expected.to_string()));
+    }
+
+    #[rstest]
+    #[case::overflow("0x7FFFFFFFFFFFFFFF + 1", "Overflow in '9223372036854775807 + 1'")]
+    #[case::decimal_overflow("9223372036854775807 + 1", "Overflow in '9223372036854775807 + 1'")]
+    #[case::fraction("1.5 | 1", "Domain error in '1.5': 1.5 is not an integer")]
+    #[case::shift_range("1 << 64", "Domain error in '1 << 64': shift amount 64 is out of range")]
+    fn calculator_reports_programmer_mode_errors(#[case] input: &str, #[case] expected: &str) {
+        let console = MockConsole::new();
+        let mut calculator = Calculator::new(&console);
+        calculator.set_mode(NumericMode::Programmer(OutputBase::Dec));
+
+        let error = calculator.evaluate(input).unwrap_err();
+
+        assert_eq!(format!("{}", error), expected);
+    }
+
+    #[test]
+    fn calculator_bitwise_operators_require_programmer_mode() {
+        let console = MockConsole::new();
+        let mut calculator = Calculator::new(&console);
+
+        let error = calculator.evaluate("6 & 3").unwrap_err();
+
+        assert_eq!(format!("{}", error), "Domain error in '6 & 3': bitwise operators require programmer mode");
+    }
+
+    #[rstest]
+    #[case::smallest_integer(NumericMode::Programmer(OutputBase::Dec), "-9223372036854775807 - 1", "-9223372036854775808")]
+    #[case::beyond_integer_literals(NumericMode::Float, "2^70", "1180591620717411300000")]
+    fn calculator_remembers_values_beyond_integer_literals(#[case] mode: NumericMode, #[case] input: &str, #[case] expected: &str) {
+        let console = MockConsole::new();
+        let mut calculator = Calculator::new(&console);
+        calculator.set_mode(mode);
+
+        assert_eq!(calculator.evaluate(&format!("x = {}", input)), Ok(format!("x = {}", expected)));
+        assert_eq!(calculator.evaluate("x"), Ok(expected.to_string()));
+    }
+
+    #[test]
+    fn calculator_switches_output_base() {
+        let console = MockConsole::new();
+        let mut calculator = Calculator::new(&console);
+
+        assert_eq!(calculator.execute_command("hex"), Ok(vec!["Mode: programmer hex".to_string()]));
+        assert_eq!(calculator.mode(), NumericMode::Programmer(OutputBase::Hex));
+        assert_eq!(calculator.evaluate("255"), Ok("0xFF".to_string()));
+        assert_eq!(calculator.evaluate("x = 0b1100"), Ok("x = 0xC".to_string()));
+
+        assert_eq!(calculator.execute_command("bin"), Ok(vec!["Mode: programmer bin".to_string()]));
+        assert_eq!(calculator.evaluate("x | 1"), Ok("0b1101".to_string()));
+
+        assert_eq!(calculator.execute_command("dec"), Ok(vec!["Mode: programmer".to_string()]));
+        assert_eq!(calculator.evaluate("x"), Ok("12".to_string()));
+    }
+
+    #[test]
+    fn calculator_plots_functions_on_one_chart() {
+        let console = MockConsole::new();
+        let mut calculator = Calculator::new(&console);
+        calculator.evaluate("k = 2").unwrap();
+
+        let lines = calculator.execute_command("plot sin(x), k * cos(x) from -pi to pi").unwrap();
+
+        assert_eq!(lines.len(), 19);
+        assert_eq!(lines[0], "• sin(x)   + k * cos(x)");
+        assert!(lines[1..17].iter().any(|line| line.contains('•')));
+        assert!(lines[1..17].iter().any(|line| line.contains('+')));
+        assert!(lines[1].starts_with(" 2 ┤"));
+        assert!(lines[16].starts_with("-2 ┤"));
+        assert_eq!(lines[17], format!("   └{}", "─".repeat(60)));
+        assert_eq!(lines[18], format!("   -3.14{}3.14", " ".repeat(52)));
+    }
+
+    #[rstest]
+    #[case::missing_range("plot sin(x)", "Usage: :plot f(x)[, g(x)...] from a to b")]
+    #[case::missing_function("plot sin(x), from 0 to 1", "Usage: :plot f(x)[, g(x)...] from a to b")]
+    #[case::empty_range("plot x from 1 to 1", "Empty range: 1 is not below 1")]
+    #[case::unknown_variable("plot y from 0 to 1", "Syntax error: Unknown constant 'y'")]
+    #[case::undefined("plot ln(x) from -2 to -1", "Nothing to plot: no point in the range could be evaluated")]
+    fn calculator_rejects_invalid_plots(#[case] command: &str, #[case] expected: &str) {
+        let console = MockConsole::new();
+        let mut calculator = Calculator::new(&console);
+
+        assert_eq!(calculator.execute_command(command), Err(expected.to_string()));
+    }
+
+    #[test]
+    fn calculator_run_loop_lists_and_removes_functions() {
+        let mut mock_console = MockConsole::new();
+        mock_console.expect_set_completions().returning(|_| ());
+
+        let mut seq = Sequence::new();
+
+        mock_console
+            .expect_println()
+            .with(eq("### Calculator ver. 1.0 ###"))
+            .times(1)
+            .in_sequence(&mut seq)
+            .returning(|_| ());
+
+        let session = [
+            (":funcs", vec!["No functions defined"]),
+            ("sq(x) = x * x", vec!["sq(x) = x * x"]),
+            ("avg(a, b) = (a + b) / 2", vec!["avg(a, b) = (a + b) / 2"]),
+            (":funcs", vec!["avg(a, b) = (a + b) / 2", "sq(x) = x * x"]),
+            (":undef sq", vec!["Removed function 'sq'"]),
+            (":undef sq", vec!["Unknown function 'sq'"]),
+            ("sq(2)", vec!["sq(2)", "^~", "Syntax error: Unknown function 'sq'"]),
+            ("avg(1, 4)", vec!["2.5"]),
+        ];
+
+        for (input, outputs) in session {
+            mock_console.expect_print()
+                .with(eq(">>> "))
+                .times(1)
+                .in_sequence(&mut seq)
+                .returning(|_| ());
+
+            mock_console
+                .expect_readline()
+                .times(1)
+                .in_sequence(&mut seq)
+                .returning(move || input.to_string());
+
+            for output in outputs {
+                mock_console
+                    .expect_println()
+                    .times(1)
+                    .in_sequence(&mut seq)
+                    .with(eq(output))
+                    .returning(|_| ());
+            }
+        }
+
+        mock_console.expect_print()
+            .with(eq(">>> "))
+            .times(1)
+            .in_sequence(&mut seq)
+            .returning(|_| ());
+
+        mock_console.expect_readline()
+            .times(1)
+            .in_sequence(&mut seq)
+            .returning(|| "exit".to_string());
+
+
+        let mut calculator = Calculator::new(&mock_console);
+
+        calculator.run();
+    }
+
+    #[rstest]
+    #[case::extra_paren("(1 + 2))*3", "       ^")]
+    #[case::unknown_function("1 + foo(2)", "    ^~~")]
+    #[case::arity("log(8) + 1", "^~~~~~")]
+    #[case::end_of_input("2 *", "   ^")]
+    fn caret_marker_points_at_parser_error(#[case] input: &str, #[case] expected: &str) {
+        let error = Parser::new(input).and_then(|mut parser| parser.parse()).unwrap_err();
+
+        assert_eq!(caret_marker(input, error.span()), expected);
+    }
+
+    #[test]
+    fn caret_marker_counts_characters_not_bytes() {
+        assert_eq!(caret_marker("π + #", Span::new(5, 6)), "    ^");
+    }
+
+    #[test]
+    fn calculator_runs_scripts() {
+        let mut mock_console = MockConsole::new();
+        let mut seq = Sequence::new();
+
+        for output in ["x = 3 m", "3.2 m", "Mode: rational", "1/3"] {
+            mock_console
+                .expect_println()
+                .with(eq(output))
+                .times(1)
+                .in_sequence(&mut seq)
+                .returning(|_| ());
+        }
+
+        let mut calculator = Calculator::new(&mock_console);
+        let script = "# lengths\nx = 3 m\n\nx + 20 cm\n:mode rational\n  1 / 3  \n";
+
+        assert!(calculator.run_script("lengths.calc", script, OutputFormat::Plain));
+    }
+
+    #[test]
+    fn calculator_script_stops_at_first_error() {
+        let mut mock_console = MockConsole::new();
+        let mut seq = Sequence::new();
+
+        mock_console
+            .expect_println()
+            .with(eq("3"))
+            .times(1)
+            .in_sequence(&mut seq)
+            .returning(|_| ());
+
+        for output in ["script.calc:2: Syntax error: Unknown function 'foo'", "    foo(1)", "    ^~~"] {
+            mock_console
+                .expect_eprintln()
+                .with(eq(output))
+                .times(1)
+                .in_sequence(&mut seq)
+                .returning(|_| ());
+        }
+
+        let mut calculator = Calculator::new(&mock_console);
+
+        assert!(!calculator.run_script("script.calc", "1 + 2\nfoo(1)\n4", OutputFormat::Plain));
+    }
+
+    #[test]
+    fn calculator_script_reports_command_errors() {
+        let mut mock_console = MockConsole::new();
+
+        mock_console
+            .expect_eprintln()
+            .with(eq("<stdin>:1: Unknown command ':nope'"))
+            .times(1)
+            .returning(|_| ());
+
+        let mut calculator = Calculator::new(&mock_console);
+
+        assert!(!calculator.run_script("<stdin>", ":nope", OutputFormat::Plain));
+    }
+
+    #[test]
+    fn calculator_script_prints_json_records() {
+        let mut mock_console = MockConsole::new();
+        let mut seq = Sequence::new();
+
+        for output in [
+            r#"{"input":"1 + 2","line":1,"result":"3"}"#,
+            r#"{"error":"Division by zero in '1 / 0'","input":"1 / 0","line":2}"#,
+        ] {
+            mock_console
+                .expect_println()
+                .with(eq(output))
+                .times(1)
+                .in_sequence(&mut seq)
+                .returning(|_| ());
+        }
+
+        let mut calculator = Calculator::new(&mock_console);
+
+        assert!(!calculator.run_script("-e", "1 + 2\n1 / 0", OutputFormat::Json));
+    }
 }
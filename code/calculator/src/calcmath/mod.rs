@@ -1 +1,3 @@
-pub mod calc;
\ No newline at end of file
+pub mod calc;
+pub mod plot;
+pub mod terminal;
\ No newline at end of file
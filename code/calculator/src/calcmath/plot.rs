@@ -0,0 +1,241 @@
+use crate::parsemath::compiler::Program;
+
+pub const WIDTH: usize = 60;
+pub const HEIGHT: usize = 16;
+
+const BRAILLE_BLANK: u32 = 0x2800;
+// bit of each dot in a braille cell, indexed by [row][column] of the 2x4 dot grid
+const BRAILLE_DOTS: [[u8; 2]; 4] = [[0x01, 0x08], [0x02, 0x10], [0x04, 0x20], [0x40, 0x80]];
+
+// with several functions on one chart each curve is drawn with its own marker instead of braille dots
+const MARKERS: [char; 8] = ['•', '+', 'x', 'o', '*', '#', '%', '@'];
+
+// A chart of `width` x `height` characters, each holding 2 x 4 braille dots
+pub struct Chart {
+    width: usize,
+    height: usize,
+}
+
+// Dots set on the chart, with (0, 0) in the top left corner, and the markers that cover whole cells
+struct Canvas {
+    columns: usize,
+    dots: Vec<bool>,
+    markers: Vec<Option<char>>,
+}
+
+impl Canvas {
+    fn set(&mut self, column: usize, row: usize) {
+        self.dots[row * self.columns + column] = true;
+    }
+
+    fn mark(&mut self, column: usize, row: usize, marker: char) {
+        self.markers[row / 4 * (self.columns / 2) + column / 2] = Some(marker);
+    }
+
+    fn cell(&self, x: usize, y: usize) -> char {
+        if let Some(marker) = self.markers[y * (self.columns / 2) + x] {
+            return marker;
+        }
+        let mut bits = 0;
+        for (dy, row) in BRAILLE_DOTS.iter().enumerate() {
+            for (dx, bit) in row.iter().enumerate() {
+                if self.dots[(y * 4 + dy) * self.columns + x * 2 + dx] {
+                    bits |= bit;
+                }
+            }
+        }
+        match bits {
+            0 => ' ',
+            bits => char::from_u32(BRAILLE_BLANK + bits as u32).expect("braille patterns are valid characters"),
+        }
+    }
+}
+
+impl Default for Chart {
+    fn default() -> Self {
+        Chart::new(WIDTH, HEIGHT)
+    }
+}
+
+impl Chart {
+    pub fn new(width: usize, height: usize) -> Self {
+        Chart { width: width.max(2), height: height.max(2) }
+    }
+
+    // Samples every program once per dot column over `from..=to` and draws them on shared axes.
+    // Points that cannot be evaluated are left out, so poles and domain errors leave gaps
+    pub fn render(&self, from: f64, to: f64, programs: &[Program]) -> Result<Vec<String>, String> {
+        let (columns, rows) = (self.width * 2, self.height * 4);
+        let mut canvas = Canvas {
+            columns,
+            dots: vec![false; columns * rows],
+            markers: vec![None; self.width * self.height],
+        };
+        let x_at = |column: usize| from + (to - from) * column as f64 / (columns - 1) as f64;
+
+        let samples = programs
+            .iter()
+            .map(|program| {
+                (0..columns)
+                    .map(|column| program.eval(&[x_at(column)]).ok().filter(|y| y.is_finite()))
+                    .collect::<Vec<_>>()
+            })
+            .collect::<Vec<_>>();
+
+        let (low, high) = samples
+            .iter()
+            .flatten()
+            .flatten()
+            .fold((f64::INFINITY, f64::NEG_INFINITY), |(low, high), y| (low.min(*y), high.max(*y)));
+        if low > high {
+            return Err("Nothing to plot: no point in the range could be evaluated".to_string());
+        }
+        let (low, high) = if low == high { (low - 1.0, high + 1.0) } else { (low, high) };
+        let row_of = |y: f64| ((high - y) / (high - low) * (rows - 1) as f64).round() as usize;
+
+        // axes through the origin are dashed so that they stand out from the curves
+        if low <= 0.0 && 0.0 <= high {
+            let row = row_of(0.0);
+            (0..columns).step_by(2).for_each(|column| canvas.set(column, row));
+        }
+        if from <= 0.0 && 0.0 <= to {
+            let column = ((0.0 - from) / (to - from) * (columns - 1) as f64).round() as usize;
+            (0..rows).step_by(2).for_each(|row| canvas.set(column, row));
+        }
+
+        for (index, curve) in samples.iter().enumerate() {
+            let marker = (programs.len() > 1).then(|| marker(index));
+            let mut previous: Option<usize> = None;
+            for (column, y) in curve.iter().enumerate() {
+                let Some(y) = y else {
+                    previous = None;
+                    continue;
+                };
+                let row = row_of(*y);
+                // join steep sections to the previous point so the curve stays connected
+                let (top, bottom) = match previous {
+                    Some(previous) if previous < row => (previous + 1, row),
+                    Some(previous) if previous > row => (row, previous - 1),
+                    _ => (row, row),
+                };
+                (top..=bottom).for_each(|row| match marker {
+                    Some(marker) => canvas.mark(column, row, marker),
+                    None => canvas.set(column, row),
+                });
+                previous = Some(row);
+            }
+        }
+
+        Ok(self.frame(&canvas, (from, to), (low, high)))
+    }
+
+    fn frame(&self, canvas: &Canvas, (from, to): (f64, f64), (low, high): (f64, f64)) -> Vec<String> {
+        let (top, bottom) = (format_label(high), format_label(low));
+        let margin = top.chars().count().max(bottom.chars().count());
+
+        let mut lines = (0..self.height)
+            .map(|y| {
+                let cells = (0..self.width).map(|x| canvas.cell(x, y)).collect::<String>();
+                let (label, tick) = match y {
+                    0 => (top.as_str(), '┤'),
+                    y if y == self.height - 1 => (bottom.as_str(), '┤'),
+                    _ => ("", '│'),
+                };
+                format!("{:>margin$} {}{}", label, tick, cells).trim_end().to_string()
+            })
+            .collect::<Vec<_>>();
+        lines.push(format!("{:margin$} └{}", "", "─".repeat(self.width)));
+
+        let (left, right) = (format_label(from), format_label(to));
+        let gap = (self.width + 1).saturating_sub(left.chars().count() + right.chars().count()).max(1);
+        lines.push(format!("{:margin$} {}{}{}", "", left, " ".repeat(gap), right));
+
+        lines
+    }
+}
+
+fn marker(index: usize) -> char {
+    MARKERS[index % MARKERS.len()]
+}
+
+// Names each function after the marker its curve is drawn with; a lone function needs no legend
+pub fn legend(functions: &[&str]) -> String {
+    match functions {
+        [function] => function.to_string(),
+        functions => functions
+            .iter()
+            .enumerate()
+            .map(|(index, function)| format!("{} {}", marker(index), function))
+            .collect::<Vec<_>>()
+            .join("   "),
+    }
+}
+
+// short axis labels: at most two decimals, without trailing zeros
+fn format_label(value: f64) -> String {
+    if value != 0.0 && (value.abs() >= 1e5 || value.abs() < 1e-2) {
+        return format!("{:.1e}", value);
+    }
+    let label = format!("{:.2}", value);
+    let label = label.trim_end_matches('0').trim_end_matches('.');
+    match label {
+        "-0" => "0".to_string(),
+        label => label.to_string(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parsemath::parser::Parser;
+    use rstest::rstest;
+
+    fn program(expression: &str) -> Program {
+        Parser::new(expression).unwrap().parse_with_variables(&["x"]).unwrap().compile().unwrap()
+    }
+
+    #[test]
+    fn renders_line_with_axes_and_labels() {
+        let chart = Chart::new(4, 2);
+
+        let lines = chart.render(-1.0, 1.0, &[program("x")]).unwrap();
+
+        assert_eq!(lines, vec![" 1 ┤  ⡥⠊", "-1 ┤⡡⠋⠅⠁", "   └────", "   -1  1"]);
+    }
+
+    #[test]
+    fn draws_several_functions_with_their_own_markers() {
+        let chart = Chart::new(8, 3);
+
+        let lines = chart.render(0.0, 1.0, &[program("x"), program("2 * x")]).unwrap();
+
+        assert_eq!(lines, vec!["2 ┤⠅    +++", "  │⠅ ++++••", "0 ┤+++••⡀⡀⡀", "  └────────", "  0       1"]);
+    }
+
+    #[test]
+    fn leaves_gaps_where_undefined() {
+        let chart = Chart::new(4, 2);
+
+        let lines = chart.render(-1.0, 1.0, &[program("sqrt(x)")]).unwrap();
+
+        assert_eq!(lines[..2], ["   1 ┤  ⠅⡜", "0.38 ┤  ⡽"]);
+        assert!(chart.render(-2.0, -1.0, &[program("sqrt(x)")]).is_err());
+    }
+
+    #[rstest]
+    #[case::single(&["sin(x)"], "sin(x)")]
+    #[case::several(&["sin(x)", "max(x, 1)"], "• sin(x)   + max(x, 1)")]
+    fn legend_names_markers(#[case] functions: &[&str], #[case] expected: &str) {
+        assert_eq!(legend(functions), expected);
+    }
+
+    #[rstest]
+    #[case::integer(3.0, "3")]
+    #[case::decimals(-12.3456, "-12.35")]
+    #[case::small(-0.001, "-1.0e-3")]
+    #[case::rounded_to_zero(-0.0, "0")]
+    #[case::large(250000.0, "2.5e5")]
+    fn formats_labels(#[case] value: f64, #[case] expected: &str) {
+        assert_eq!(format_label(value), expected);
+    }
+}
@@ -0,0 +1,149 @@
+use crate::calcmath::calc::Console;
+use rustyline::completion::Completer;
+use rustyline::error::ReadlineError;
+use rustyline::highlight::Highlighter;
+use rustyline::hint::Hinter;
+use rustyline::history::DefaultHistory;
+use rustyline::validate::Validator;
+use rustyline::{Context, Editor, Helper};
+use std::cell::RefCell;
+use std::path::PathBuf;
+
+pub const HISTORY_FILE: &str = ".calculator_history";
+
+// Completes the identifier under the cursor from the names the calculator currently knows
+#[derive(Default)]
+struct NameCompleter {
+    names: Vec<String>,
+}
+
+impl Completer for NameCompleter {
+    type Candidate = String;
+
+    fn complete(&self, line: &str, pos: usize, _ctx: &Context<'_>) -> rustyline::Result<(usize, Vec<String>)> {
+        Ok(complete_name(&self.names, line, pos))
+    }
+}
+
+impl Hinter for NameCompleter {
+    type Hint = String;
+}
+
+impl Highlighter for NameCompleter {}
+
+impl Validator for NameCompleter {}
+
+impl Helper for NameCompleter {}
+
+fn complete_name(names: &[String], line: &str, pos: usize) -> (usize, Vec<String>) {
+    let start = line[..pos]
+        .char_indices()
+        .rev()
+        .take_while(|(_, c)| c.is_ascii_alphanumeric() || *c == '_')
+        .last()
+        .map_or(pos, |(index, _)| index);
+
+    let word = &line[start..pos];
+    if word.is_empty() {
+        return (pos, Vec::new());
+    }
+
+    let candidates = names.iter().filter(|name| name.starts_with(word)).cloned().collect();
+    (start, candidates)
+}
+
+// An interactive console with line editing, reverse search (Ctrl-R), tab completion and a
+// history file that survives between sessions
+pub struct Terminal {
+    editor: RefCell<Editor<NameCompleter, DefaultHistory>>,
+    // text printed without a newline is held back and becomes the prompt of the next readline,
+    // so the editor can redraw it while the line is edited
+    prompt: RefCell<String>,
+    history: Option<PathBuf>,
+}
+
+impl Terminal {
+    pub fn new(history: Option<PathBuf>) -> rustyline::Result<Terminal> {
+        let mut editor = Editor::new()?;
+        editor.set_helper(Some(NameCompleter::default()));
+        if let Some(path) = &history {
+            // a missing history file just means this is the first session
+            let _ = editor.load_history(path);
+        }
+
+        Ok(Terminal { editor: RefCell::new(editor), prompt: RefCell::new(String::new()), history })
+    }
+
+    pub fn default_history() -> Option<PathBuf> {
+        std::env::home_dir().map(|home| home.join(HISTORY_FILE))
+    }
+
+    fn take_prompt(&self) -> String {
+        std::mem::take(&mut *self.prompt.borrow_mut())
+    }
+}
+
+impl Console for Terminal {
+    // end of input (Ctrl-D) reads as `exit`, an interrupted line (Ctrl-C) as an empty one
+    fn readline(&self) -> String {
+        let prompt = self.take_prompt();
+        let mut editor = self.editor.borrow_mut();
+
+        match editor.readline(&prompt) {
+            Ok(line) => {
+                let line = line.trim().to_string();
+                if !line.is_empty()
+                    && editor.add_history_entry(line.as_str()).unwrap_or(false)
+                    && let Some(path) = &self.history
+                {
+                    let _ = editor.save_history(path);
+                }
+                line
+            }
+            Err(ReadlineError::Interrupted) => String::new(),
+            Err(_) => "exit".to_string(),
+        }
+    }
+
+    fn println(&self, text: &str) {
+        println!("{}{}", self.take_prompt(), text);
+    }
+
+    fn print(&self, text: &str) {
+        self.prompt.borrow_mut().push_str(text);
+    }
+
+    fn eprintln(&self, text: &str) {
+        eprintln!("{}", text);
+    }
+
+    fn set_completions(&self, names: &[String]) {
+        if let Some(helper) = self.editor.borrow_mut().helper_mut() {
+            helper.names = names.to_vec();
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rstest::rstest;
+
+    fn names() -> Vec<String> {
+        ["sin(", "sqrt(", "sq(", "pi", "radius"].iter().map(|name| name.to_string()).collect()
+    }
+
+    #[rstest]
+    #[case::prefix("sq", 2, 0, vec!["sqrt(", "sq("])]
+    #[case::inside_expression("2 * ra", 6, 4, vec!["radius"])]
+    #[case::after_parenthesis("max(p", 5, 4, vec!["pi"])]
+    #[case::cursor_in_middle("si + 1", 2, 0, vec!["sin("])]
+    #[case::no_match("cos", 3, 0, vec![])]
+    #[case::no_word("1 + ", 4, 4, vec![])]
+    fn completes_name_under_cursor(#[case] line: &str, #[case] pos: usize, #[case] start: usize, #[case] expected: Vec<&str>) {
+        let (actual_start, candidates) = complete_name(&names(), line, pos);
+
+        assert_eq!(actual_start, start);
+        assert_eq!(candidates, expected);
+    }
+}
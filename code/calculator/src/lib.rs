@@ -0,0 +1,2 @@
+pub mod parsemath;
+pub mod calcmath;
@@ -1,14 +1,26 @@
 use std::io;
-use std::io::Write;
-use crate::calcmath::calc::{Calculator, Console};
+use std::io::{IsTerminal, Write};
+use std::process::ExitCode;
+use calculator::calcmath::calc::{Calculator, Console, OutputFormat};
+use calculator::calcmath::terminal::Terminal;
 
-mod parsemath;
-mod calcmath;
+const USAGE: &str = "\
+Usage: calculator [--json] [-e EXPRESSION]... [FILE]
 
-struct Terminal {
+  -e, --eval EXPRESSION  evaluate EXPRESSION and exit (may be repeated)
+      --json             print one JSON record per evaluated line
+  -h, --help             show this help
+  FILE                   evaluate each line of FILE, '-' reads stdin
+
+Without -e or FILE, piped stdin is evaluated as a script, otherwise the
+interactive prompt starts, keeping its history in ~/.calculator_history.
+Evaluation stops at the first error and the exit status is non-zero.";
+
+// Plain standard streams, used when evaluating scripts
+struct Stdio {
 }
 
-impl Console for Terminal {
+impl Console for Stdio {
     fn readline(&self) -> String {
         io::stdout().flush().unwrap(); // Ensure the prompt is printed
 
@@ -25,12 +37,150 @@ impl Console for Terminal {
     fn print(&self, text: &str) {
         print!("{}", text);
     }
+
+    fn eprintln(&self, text: &str) {
+        eprintln!("{}", text);
+    }
+
+    fn set_completions(&self, _names: &[String]) {}
+}
+
+#[derive(Debug, PartialEq)]
+enum Input {
+    Interactive,
+    Expressions(Vec<String>),
+    File(String),
+    Stdin,
 }
 
-fn main() {
+#[derive(Debug, PartialEq)]
+struct Options {
+    input: Input,
+    format: OutputFormat,
+}
 
-    let mut term = Terminal{};
+fn parse_args(args: impl IntoIterator<Item = String>, stdin_is_terminal: bool) -> Result<Option<Options>, String> {
+    let mut expressions = Vec::new();
+    let mut file = None;
+    let mut format = OutputFormat::Plain;
 
-    let mut calculator = Calculator::new(&mut term);
-    calculator.run();
+    let mut args = args.into_iter();
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "-h" | "--help" => return Ok(None),
+            "--json" => format = OutputFormat::Json,
+            "-e" | "--eval" => match args.next() {
+                Some(expression) => expressions.push(expression),
+                None => return Err(format!("missing expression after '{}'", arg)),
+            },
+            option if option.starts_with('-') && option != "-" => return Err(format!("unknown option '{}'", option)),
+            path if file.is_none() => file = Some(path.to_string()),
+            extra => return Err(format!("unexpected argument '{}'", extra)),
+        }
+    }
+
+    let input = match (expressions.is_empty(), file) {
+        (false, Some(_)) => return Err("cannot combine -e with a script file".to_string()),
+        (false, None) => Input::Expressions(expressions),
+        (true, Some(path)) if path == "-" => Input::Stdin,
+        (true, Some(path)) => Input::File(path),
+        (true, None) if stdin_is_terminal => Input::Interactive,
+        (true, None) => Input::Stdin,
+    };
+
+    Ok(Some(Options { input, format }))
+}
+
+fn main() -> ExitCode {
+    let options = match parse_args(std::env::args().skip(1), io::stdin().is_terminal()) {
+        Ok(Some(options)) => options,
+        Ok(None) => {
+            println!("{}", USAGE);
+            return ExitCode::SUCCESS;
+        }
+        Err(error) => {
+            eprintln!("calculator: {}\n\n{}", error, USAGE);
+            return ExitCode::from(2);
+        }
+    };
+
+    if options.input == Input::Interactive {
+        return match Terminal::new(Terminal::default_history()) {
+            Ok(terminal) => {
+                Calculator::new(&terminal).run();
+                ExitCode::SUCCESS
+            }
+            Err(error) => {
+                eprintln!("calculator: cannot start the terminal: {}", error);
+                ExitCode::FAILURE
+            }
+        };
+    }
+
+    let stdio = Stdio{};
+    let mut calculator = Calculator::new(&stdio);
+
+    let (name, source) = match options.input {
+        Input::Interactive => unreachable!("the interactive prompt is handled above"),
+        Input::Expressions(expressions) => ("-e".to_string(), expressions.join("\n")),
+        Input::Stdin => match io::read_to_string(io::stdin()) {
+            Ok(source) => ("<stdin>".to_string(), source),
+            Err(error) => {
+                eprintln!("calculator: cannot read stdin: {}", error);
+                return ExitCode::FAILURE;
+            }
+        },
+        Input::File(path) => match std::fs::read_to_string(&path) {
+            Ok(source) => (path, source),
+            Err(error) => {
+                eprintln!("calculator: cannot read '{}': {}", path, error);
+                return ExitCode::FAILURE;
+            }
+        },
+    };
+
+    if calculator.run_script(&name, &source, options.format) {
+        ExitCode::SUCCESS
+    } else {
+        ExitCode::FAILURE
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rstest::rstest;
+
+    fn args(args: &[&str]) -> Vec<String> {
+        args.iter().map(|arg| arg.to_string()).collect()
+    }
+
+    #[rstest]
+    #[case::interactive(&[], true, Input::Interactive, OutputFormat::Plain)]
+    #[case::piped(&[], false, Input::Stdin, OutputFormat::Plain)]
+    #[case::dash(&["-", "--json"], true, Input::Stdin, OutputFormat::Json)]
+    #[case::file(&["lengths.calc"], true, Input::File("lengths.calc".to_string()), OutputFormat::Plain)]
+    #[case::expressions(
+        &["-e", "x = 2", "--eval", "x^2"],
+        false,
+        Input::Expressions(vec!["x = 2".to_string(), "x^2".to_string()]),
+        OutputFormat::Plain
+    )]
+    fn parses_arguments(#[case] arguments: &[&str], #[case] terminal: bool, #[case] input: Input, #[case] format: OutputFormat) {
+        assert_eq!(parse_args(args(arguments), terminal), Ok(Some(Options { input, format })));
+    }
+
+    #[rstest]
+    #[case::missing_expression(&["-e"], "missing expression after '-e'")]
+    #[case::unknown_option(&["--yaml"], "unknown option '--yaml'")]
+    #[case::two_files(&["a.calc", "b.calc"], "unexpected argument 'b.calc'")]
+    #[case::expression_and_file(&["-e", "1", "a.calc"], "cannot combine -e with a script file")]
+    fn rejects_invalid_arguments(#[case] arguments: &[&str], #[case] error: &str) {
+        assert_eq!(parse_args(args(arguments), true), Err(error.to_string()));
+    }
+
+    #[test]
+    fn help_is_requested() {
+        assert_eq!(parse_args(args(&["--json", "-h"]), true), Ok(None));
+    }
 }
@@ -1,24 +1,284 @@
+use crate::parsemath::functions::{Arity, FunctionError, FunctionRegistry};
+use crate::parsemath::numeric::{Backend, BitwiseOp, FloatBackend, NumericError, IMAGINARY_UNIT};
+use crate::parsemath::solver;
+use std::collections::HashMap;
+use std::fmt;
+use thiserror::Error;
+
 #[derive(Debug, PartialEq, Clone)]
 pub enum Expression {
     Number(f64),
+    Integer(i64),
+    Variable(String),
     Add(Box<Expression>, Box<Expression>),
     Subtract(Box<Expression>, Box<Expression>),
     Multiply(Box<Expression>, Box<Expression>),
     Divide(Box<Expression>, Box<Expression>),
+    Power(Box<Expression>, Box<Expression>),
     Negate(Box<Expression>),
     Grouping(Box<Expression>),
+    Call(String, Vec<Expression>),
+    Convert(Box<Expression>, Box<Expression>),
+    Bitwise(BitwiseOp, Box<Expression>, Box<Expression>),
+    BitNot(Box<Expression>),
+    // `left = right`, only written as the first argument of `solve`
+    Equation(Box<Expression>, Box<Expression>),
+}
+
+#[derive(Error, Debug, PartialEq, Clone)]
+pub enum EvalError {
+    #[error("Division by zero in '{0}'")]
+    DivisionByZero(String),
+    #[error("Domain error in '{expression}': {message}")]
+    DomainError { expression: String, message: String },
+    #[error("Overflow in '{0}'")]
+    Overflow(String),
+    #[error("Unknown variable '{0}'")]
+    UnknownVariable(String),
+    #[error("Invalid call '{expression}': {source}")]
+    InvalidCall { expression: String, source: FunctionError },
+    #[error("Recursion limit exceeded in '{0}'")]
+    RecursionLimit(String),
+    #[error("Incompatible units in '{expression}': {left} and {right}")]
+    IncompatibleUnits { expression: String, left: String, right: String },
+}
+
+impl EvalError {
+    pub(crate) fn from_numeric(error: NumericError, expression: String) -> EvalError {
+        match error {
+            NumericError::DivisionByZero => EvalError::DivisionByZero(expression),
+            NumericError::Overflow => EvalError::Overflow(expression),
+            NumericError::DomainError(message) => EvalError::DomainError { expression, message },
+            NumericError::IncompatibleUnits(left, right) => EvalError::IncompatibleUnits { expression, left, right },
+        }
+    }
+
+    pub(crate) fn from_function(error: FunctionError, expression: String) -> EvalError {
+        match error {
+            FunctionError::DomainError(message) => EvalError::DomainError { expression, message },
+            source => EvalError::InvalidCall { expression, source },
+        }
+    }
+}
+
+pub const MAX_CALL_DEPTH: usize = 100;
+
+// Parameter values visible while evaluating the body of a user-defined function
+struct Scope<V> {
+    bindings: HashMap<String, V>,
+    depth: usize,
+}
+
+impl<V> Default for Scope<V> {
+    fn default() -> Self {
+        Scope { bindings: HashMap::new(), depth: 0 }
+    }
+}
+
+#[derive(Debug, PartialEq, Clone)]
+pub enum Statement {
+    Expression(Expression),
+    FunctionDefinition {
+        name: String,
+        params: Vec<String>,
+        body: Expression,
+    },
+    Assignment {
+        name: String,
+        value: Expression,
+    },
 }
 
 impl Expression {
-    pub fn evaluate(&self) -> f64 {
+    pub fn evaluate(&self) -> Result<f64, EvalError> {
+        self.evaluate_with(FunctionRegistry::builtins())
+    }
+
+    pub fn evaluate_with(&self, registry: &FunctionRegistry) -> Result<f64, EvalError> {
+        self.evaluate_in(&FloatBackend, registry)
+    }
+
+    pub fn evaluate_in<B: Backend>(&self, backend: &B, registry: &FunctionRegistry) -> Result<B::Value, EvalError> {
+        self.evaluate_in_scope(backend, registry, &Scope::default())
+    }
+
+    // evaluates as f64 with the given names bound, as if they were parameters of a user function
+    pub(crate) fn evaluate_with_bindings(&self, registry: &FunctionRegistry, bindings: HashMap<String, f64>) -> Result<f64, EvalError> {
+        self.evaluate_in_scope(&FloatBackend, registry, &Scope { bindings, depth: 0 })
+    }
+
+    fn evaluate_in_scope<B: Backend>(&self, backend: &B, registry: &FunctionRegistry, scope: &Scope<B::Value>) -> Result<B::Value, EvalError> {
+        let evaluate = |expr: &Expression| expr.evaluate_in_scope(backend, registry, scope);
+
+        let result = match self {
+            Expression::Number(n) => backend.number(*n),
+            Expression::Integer(n) => backend.integer(*n),
+            Expression::Variable(name) => {
+                if let Some(value) = scope.bindings.get(name) {
+                    return Ok(value.clone());
+                }
+                if let Some(value) = registry.variable(name) {
+                    return value.evaluate_in_scope(backend, registry, &Scope::default());
+                }
+                match (registry.constant(name), backend.unit(name)) {
+                    (Some(value), _) => backend.number(value),
+                    (None, Some(unit)) => unit,
+                    (None, None) if name == IMAGINARY_UNIT => backend.imaginary_unit(),
+                    (None, None) => return Err(EvalError::UnknownVariable(name.clone())),
+                }
+            }
+            Expression::Add(a, b) => backend.add(&evaluate(a)?, &evaluate(b)?),
+            Expression::Subtract(a, b) => backend.subtract(&evaluate(a)?, &evaluate(b)?),
+            Expression::Multiply(a, b) => backend.multiply(&evaluate(a)?, &evaluate(b)?),
+            Expression::Divide(a, b) => backend.divide(&evaluate(a)?, &evaluate(b)?),
+            Expression::Power(a, b) => backend.power(&evaluate(a)?, &evaluate(b)?),
+            Expression::Negate(expr) => backend.negate(&evaluate(expr)?),
+            Expression::Grouping(expr) => return evaluate(expr),
+            Expression::Convert(value, target) => backend.convert(&evaluate(value)?, &evaluate(target)?),
+            Expression::Bitwise(op, a, b) => backend.bitwise(*op, &evaluate(a)?, &evaluate(b)?),
+            Expression::BitNot(expr) => backend.bit_not(&evaluate(expr)?),
+            Expression::Equation(_, _) => Err(NumericError::DomainError("an equation has no value, use solve(equation, variable)".to_string())),
+            Expression::Call(name, _) if solver::arity(name).is_some() => {
+                let bindings = scope
+                    .bindings
+                    .iter()
+                    .map(|(name, value)| backend.to_f64(value).map(|value| (name.clone(), value)))
+                    .collect::<Result<HashMap<String, f64>, NumericError>>()
+                    .map_err(|error| EvalError::from_numeric(error, self.to_string()))?;
+
+                match self.solutions_with(registry, &bindings)?.values.as_slice() {
+                    [value] => backend.number(*value),
+                    values => Err(NumericError::DomainError(format!(
+                        "{} solutions, only a single solution can be used as a value",
+                        values.len()
+                    ))),
+                }
+            }
+            Expression::Call(name, args) => {
+                let values = args.iter().map(evaluate).collect::<Result<Vec<B::Value>, EvalError>>()?;
+
+                if let Some(function) = registry.user_function(name) {
+                    if values.len() != function.params.len() {
+                        return Err(EvalError::InvalidCall {
+                            expression: self.to_string(),
+                            source: FunctionError::ArityMismatch {
+                                name: name.clone(),
+                                expected: Arity::Exact(function.params.len()),
+                                actual: values.len(),
+                            },
+                        });
+                    }
+                    if scope.depth >= MAX_CALL_DEPTH {
+                        return Err(EvalError::RecursionLimit(self.to_string()));
+                    }
+
+                    let scope = Scope {
+                        bindings: function.params.iter().cloned().zip(values).collect(),
+                        depth: scope.depth + 1,
+                    };
+                    return function.body.evaluate_in_scope(backend, registry, &scope);
+                }
+
+                registry
+                    .check_arity(name, values.len())
+                    .map_err(|error| EvalError::from_function(error, self.to_string()))?;
+                if let Some(result) = backend.call(name, &values) {
+                    return result.map_err(|error| EvalError::from_numeric(error, self.to_string()));
+                }
+
+                let values = values
+                    .iter()
+                    .map(|value| backend.to_f64(value))
+                    .collect::<Result<Vec<f64>, NumericError>>()
+                    .map_err(|error| EvalError::from_numeric(error, self.to_string()))?;
+                let result = registry
+                    .call(name, &values)
+                    .map_err(|error| EvalError::from_function(error, self.to_string()))?;
+                backend.number(result)
+            }
+        };
+
+        result.map_err(|error| EvalError::from_numeric(error, self.to_string()))
+    }
+}
+
+impl Expression {
+    // binding strength used to print with as few parentheses as possible
+    fn precedence(&self) -> u8 {
+        match self {
+            Expression::Equation(_, _) | Expression::Convert(_, _) => 0,
+            Expression::Bitwise(BitwiseOp::Or, _, _) => 1,
+            Expression::Bitwise(BitwiseOp::Xor, _, _) => 2,
+            Expression::Bitwise(BitwiseOp::And, _, _) => 3,
+            Expression::Bitwise(BitwiseOp::ShiftLeft | BitwiseOp::ShiftRight, _, _) => 4,
+            Expression::Add(_, _) | Expression::Subtract(_, _) => 5,
+            Expression::Multiply(_, _) | Expression::Divide(_, _) => 6,
+            Expression::Negate(_) | Expression::BitNot(_) => 7,
+            Expression::Number(n) if n.is_sign_negative() => 7,
+            Expression::Integer(n) if *n < 0 => 7,
+            Expression::Power(_, _) => 8,
+            Expression::Grouping(expr) => expr.precedence(),
+            Expression::Number(_) | Expression::Integer(_) | Expression::Variable(_) | Expression::Call(_, _) => 9,
+        }
+    }
+
+    fn fmt_operand(&self, f: &mut fmt::Formatter<'_>, min_precedence: u8) -> fmt::Result {
+        if self.precedence() < min_precedence {
+            write!(f, "({})", self)
+        } else {
+            write!(f, "{}", self)
+        }
+    }
+}
+
+impl fmt::Display for Expression {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let binary = |f: &mut fmt::Formatter<'_>, a: &Expression, op: &str, b: &Expression, left: u8, right: u8| {
+            a.fmt_operand(f, left)?;
+            write!(f, "{}", op)?;
+            b.fmt_operand(f, right)
+        };
+
         match self {
-            Expression::Number(n) => *n,
-            Expression::Add(a, b) => a.evaluate() + b.evaluate(),
-            Expression::Subtract(a, b) => a.evaluate() - b.evaluate(),
-            Expression::Multiply(a, b) => a.evaluate() * b.evaluate(),
-            Expression::Divide(a, b) => a.evaluate() / b.evaluate(),
-            Expression::Negate(expr) => -expr.evaluate(),
-            Expression::Grouping(expr) => expr.evaluate(),
+            Expression::Number(n) => write!(f, "{}", n),
+            Expression::Integer(n) => write!(f, "{}", n),
+            Expression::Variable(name) => write!(f, "{}", name),
+            Expression::Add(a, b) => binary(f, a, " + ", b, 5, 5),
+            Expression::Subtract(a, b) => binary(f, a, " - ", b, 5, 6),
+            Expression::Multiply(a, b) => binary(f, a, " * ", b, 6, 6),
+            Expression::Divide(a, b) => binary(f, a, " / ", b, 6, 7),
+            Expression::Power(a, b) => binary(f, a, "^", b, 9, 7),
+            Expression::Negate(expr) => {
+                write!(f, "-")?;
+                expr.fmt_operand(f, 6)
+            }
+            Expression::BitNot(expr) => {
+                write!(f, "~")?;
+                expr.fmt_operand(f, 7)
+            }
+            Expression::Grouping(expr) => write!(f, "{}", expr),
+            Expression::Convert(value, target) => binary(f, value, " -> ", target, 1, 1),
+            Expression::Equation(left, right) => binary(f, left, " = ", right, 0, 0),
+            Expression::Bitwise(op, a, b) => {
+                let (symbol, level) = match op {
+                    BitwiseOp::Or => (" | ", 1),
+                    BitwiseOp::Xor => (" xor ", 2),
+                    BitwiseOp::And => (" & ", 3),
+                    BitwiseOp::ShiftLeft => (" << ", 4),
+                    BitwiseOp::ShiftRight => (" >> ", 4),
+                };
+                binary(f, a, symbol, b, level, level + 1)
+            }
+            Expression::Call(name, args) => {
+                write!(f, "{}(", name)?;
+                for (i, arg) in args.iter().enumerate() {
+                    if i > 0 {
+                        write!(f, ", ")?;
+                    }
+                    write!(f, "{}", arg)?;
+                }
+                write!(f, ")")
+            }
         }
     }
 }
@@ -27,6 +287,10 @@ impl Expression {
 mod tests {
     use rstest::rstest;
     use crate::parsemath::ast::Expression;
+    use crate::parsemath::ast::EvalError;
+    use crate::parsemath::functions::FunctionRegistry;
+    use crate::parsemath::numeric::{DecimalBackend, RationalBackend};
+    use crate::parsemath::parser::Parser;
 
     #[rstest]
     #[case::ast_add_1_2(
@@ -56,8 +320,116 @@ mod tests {
         ), -3.0)]
     fn eval_simple_expression(#[case] ast: Expression, #[case] expected: f64)
     {
-        let result = ast.evaluate();
+        let result = ast.evaluate().unwrap();
+
+        assert_eq!(result, expected);
+    }
+
+    #[rstest]
+    #[case::ast_pi(Expression::Variable("pi".to_string()), std::f64::consts::PI)]
+    #[case::ast_max_1_2_3(
+        Expression::Call(
+            "max".to_string(),
+            vec![Expression::Number(1.0), Expression::Number(3.0), Expression::Number(2.0)]
+        ), 3.0)]
+    #[case::ast_sqrt_add_7_2(
+        Expression::Call(
+            "sqrt".to_string(),
+            vec![Expression::Add(
+                Box::new(Expression::Number(7.0)),
+                Box::new(Expression::Number(2.0))
+            )]
+        ), 3.0)]
+    fn eval_function_calls_and_constants(#[case] ast: Expression, #[case] expected: f64)
+    {
+        let result = ast.evaluate().unwrap();
 
         assert_eq!(result, expected);
     }
-}
\ No newline at end of file
+
+    #[rstest]
+    #[case::division_by_zero("1 + 4 / (2 - 2)", EvalError::DivisionByZero("4 / (2 - 2)".to_string()))]
+    #[case::zero_by_zero("0 / 0", EvalError::DivisionByZero("0 / 0".to_string()))]
+    #[case::domain_error(
+        "2 * sqrt(-4)",
+        EvalError::DomainError {
+            expression: "sqrt(-4)".to_string(),
+            message: "sqrt of negative number -4".to_string()
+        }
+    )]
+    fn eval_reports_math_errors(#[case] expression: &str, #[case] expected: EvalError) {
+        let ast = Parser::new(expression).unwrap().parse().unwrap();
+
+        let error = ast.evaluate().unwrap_err();
+
+        assert_eq!(error, expected);
+    }
+
+    #[test]
+    fn eval_reports_overflow() {
+        let ast = Expression::Add(
+            Box::new(Expression::Number(1.0)),
+            Box::new(Expression::Multiply(
+                Box::new(Expression::Number(1e200)),
+                Box::new(Expression::Number(1e200))
+            ))
+        );
+
+        let error = ast.evaluate().unwrap_err();
+
+        assert!(matches!(error, EvalError::Overflow(expression) if expression.contains(" * ")));
+    }
+
+    #[test]
+    fn eval_reports_unknown_variables() {
+        let ast = Expression::Add(Box::new(Expression::Number(1.0)), Box::new(Expression::Variable("x".to_string())));
+
+        let error = ast.evaluate().unwrap_err();
+
+        assert_eq!(error, EvalError::UnknownVariable("x".to_string()));
+        assert_eq!(format!("{}", error), "Unknown variable 'x'");
+    }
+
+    #[test]
+    fn eval_error_message_shows_subexpression() {
+        let ast = Parser::new("3 * (1 / 0)").unwrap().parse().unwrap();
+
+        let error = ast.evaluate().unwrap_err();
+
+        assert_eq!(format!("{}", error), "Division by zero in '1 / 0'");
+    }
+
+    #[rstest]
+    #[case::tenths("0.1 + 0.2", "0.3")]
+    #[case::third("1 / 3", "1/3")]
+    #[case::thirds_sum("1 / 3 + 2 / 3", "1")]
+    #[case::negative("-(1.5 - 2) * 4", "2")]
+    fn eval_rational(#[case] expression: &str, #[case] expected: &str) {
+        let ast = Parser::new(expression).unwrap().parse().unwrap();
+
+        let result = ast.evaluate_in(&RationalBackend, FunctionRegistry::builtins()).unwrap();
+
+        assert_eq!(result.to_string(), expected);
+    }
+
+    #[rstest]
+    #[case::tenths(2, "0.1 + 0.2", "0.3")]
+    #[case::third(4, "1 / 3", "0.3333")]
+    #[case::sqrt(3, "sqrt(2)", "1.414")]
+    fn eval_decimal(#[case] precision: u32, #[case] expression: &str, #[case] expected: &str) {
+        let ast = Parser::new(expression).unwrap().parse().unwrap();
+
+        let result = ast.evaluate_in(&DecimalBackend::new(precision), FunctionRegistry::builtins()).unwrap();
+
+        assert_eq!(result.to_string(), expected);
+    }
+
+    #[test]
+    fn eval_rational_reports_division_by_zero() {
+        let ast = Parser::new("1 / (0.5 - 1 / 2)").unwrap().parse().unwrap();
+
+        let error = ast.evaluate_in(&RationalBackend, FunctionRegistry::builtins()).unwrap_err();
+
+        assert_eq!(error, EvalError::DivisionByZero("1 / (0.5 - 1 / 2)".to_string()));
+    }
+}
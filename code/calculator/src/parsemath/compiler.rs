@@ -0,0 +1,331 @@
+use crate::parsemath::ast::{EvalError, Expression, MAX_CALL_DEPTH};
+use crate::parsemath::functions::{Arity, FunctionBody, FunctionError, FunctionRegistry};
+use crate::parsemath::numeric::{Backend, FloatBackend, NumericError, IMAGINARY_UNIT};
+use std::collections::HashMap;
+
+// Every fallible instruction keeps the index of the subexpression it was compiled from,
+// so that errors read the same as those of `Expression::evaluate`
+#[derive(Debug, Clone, Copy)]
+enum Instruction {
+    Push(f64),
+    Load(usize),
+    LoadLocal(usize),
+    StoreLocal(usize),
+    Add(usize),
+    Subtract(usize),
+    Multiply(usize),
+    Divide(usize),
+    Power(usize),
+    Negate,
+    Call(FunctionBody, usize, usize),
+}
+
+// A flat stack program evaluated in a single loop, for formulas evaluated over many bindings
+#[derive(Debug, Clone)]
+pub struct Program {
+    instructions: Vec<Instruction>,
+    sources: Vec<String>,
+    variables: Vec<String>,
+    locals: usize,
+    stack_size: usize,
+}
+
+impl Expression {
+    pub fn compile(&self) -> Result<Program, EvalError> {
+        self.compile_with(FunctionRegistry::builtins())
+    }
+
+    pub fn compile_with(&self, registry: &FunctionRegistry) -> Result<Program, EvalError> {
+        let mut compiler = Compiler {
+            registry,
+            program: Program {
+                instructions: Vec::new(),
+                sources: Vec::new(),
+                variables: Vec::new(),
+                locals: 0,
+                stack_size: 0,
+            },
+            depth: 0,
+        };
+        compiler.compile(self, &HashMap::new(), 0)?;
+        Ok(compiler.program)
+    }
+}
+
+impl Program {
+    // Names of the free variables, in the order their values are expected by `eval`
+    pub fn variables(&self) -> &[String] {
+        &self.variables
+    }
+
+    pub fn eval(&self, bindings: &[f64]) -> Result<f64, EvalError> {
+        if let Some(missing) = self.variables.get(bindings.len()) {
+            return Err(EvalError::UnknownVariable(missing.clone()));
+        }
+
+        let mut stack: Vec<f64> = Vec::with_capacity(self.stack_size);
+        let mut locals = vec![0.0; self.locals];
+        let numeric = |result: Result<f64, NumericError>, source: usize| {
+            result.map_err(|error| EvalError::from_numeric(error, self.sources[source].clone()))
+        };
+
+        for instruction in &self.instructions {
+            match *instruction {
+                Instruction::Push(value) => stack.push(value),
+                Instruction::Load(slot) => stack.push(bindings[slot]),
+                Instruction::LoadLocal(slot) => stack.push(locals[slot]),
+                Instruction::StoreLocal(slot) => locals[slot] = pop(&mut stack),
+                Instruction::Negate => {
+                    let a = pop(&mut stack);
+                    stack.push(-a);
+                }
+                Instruction::Call(body, args, source) => {
+                    let start = stack.len() - args;
+                    let result = body(&stack[start..])
+                        .map_err(|error| EvalError::from_function(error, self.sources[source].clone()))?;
+                    stack.truncate(start);
+                    stack.push(numeric(FloatBackend.number(result), source)?);
+                }
+                binary => {
+                    let b = pop(&mut stack);
+                    let a = pop(&mut stack);
+                    let result = match binary {
+                        Instruction::Add(source) => numeric(FloatBackend.add(&a, &b), source)?,
+                        Instruction::Subtract(source) => numeric(FloatBackend.subtract(&a, &b), source)?,
+                        Instruction::Multiply(source) => numeric(FloatBackend.multiply(&a, &b), source)?,
+                        Instruction::Divide(source) => numeric(FloatBackend.divide(&a, &b), source)?,
+                        Instruction::Power(source) => numeric(FloatBackend.power(&a, &b), source)?,
+                        _ => unreachable!("all other instructions are handled above"),
+                    };
+                    stack.push(result);
+                }
+            }
+        }
+
+        Ok(pop(&mut stack))
+    }
+}
+
+fn pop(stack: &mut Vec<f64>) -> f64 {
+    stack.pop().expect("compiled programs keep the stack balanced")
+}
+
+struct Compiler<'a> {
+    registry: &'a FunctionRegistry,
+    program: Program,
+    depth: usize,
+}
+
+impl Compiler<'_> {
+    fn emit(&mut self, instruction: Instruction, stack_effect: isize) {
+        self.program.instructions.push(instruction);
+        self.depth = self.depth.checked_add_signed(stack_effect).expect("stack effects never underflow");
+        self.program.stack_size = self.program.stack_size.max(self.depth);
+    }
+
+    fn source(&mut self, expression: &Expression) -> usize {
+        self.program.sources.push(expression.to_string());
+        self.program.sources.len() - 1
+    }
+
+    fn binary(&mut self, expression: &Expression, a: &Expression, b: &Expression, locals: &HashMap<String, usize>, calls: usize, op: fn(usize) -> Instruction) -> Result<(), EvalError> {
+        self.compile(a, locals, calls)?;
+        self.compile(b, locals, calls)?;
+        let source = self.source(expression);
+        self.emit(op(source), -1);
+        Ok(())
+    }
+
+    // `locals` maps the parameters of the user function being inlined to their slots
+    fn compile(&mut self, expression: &Expression, locals: &HashMap<String, usize>, calls: usize) -> Result<(), EvalError> {
+        match expression {
+            Expression::Number(n) => self.emit(Instruction::Push(*n), 1),
+            Expression::Integer(n) => self.emit(Instruction::Push(*n as f64), 1),
+            Expression::Variable(name) => {
+                if let Some(slot) = locals.get(name) {
+                    self.emit(Instruction::LoadLocal(*slot), 1);
+                } else if let Some(value) = self.registry.variable(name) {
+                    self.compile(value, &HashMap::new(), calls)?;
+                } else if let Some(value) = self.registry.constant(name) {
+                    self.emit(Instruction::Push(value), 1);
+                } else if name == IMAGINARY_UNIT {
+                    return Err(EvalError::DomainError {
+                        expression: expression.to_string(),
+                        message: "complex numbers cannot be compiled".to_string(),
+                    });
+                } else {
+                    let slot = match self.program.variables.iter().position(|variable| variable == name) {
+                        Some(slot) => slot,
+                        None => {
+                            self.program.variables.push(name.clone());
+                            self.program.variables.len() - 1
+                        }
+                    };
+                    self.emit(Instruction::Load(slot), 1);
+                }
+            }
+            Expression::Add(a, b) => self.binary(expression, a, b, locals, calls, Instruction::Add)?,
+            Expression::Subtract(a, b) => self.binary(expression, a, b, locals, calls, Instruction::Subtract)?,
+            Expression::Multiply(a, b) => self.binary(expression, a, b, locals, calls, Instruction::Multiply)?,
+            Expression::Divide(a, b) => self.binary(expression, a, b, locals, calls, Instruction::Divide)?,
+            Expression::Power(a, b) => self.binary(expression, a, b, locals, calls, Instruction::Power)?,
+            Expression::Negate(expr) => {
+                self.compile(expr, locals, calls)?;
+                self.emit(Instruction::Negate, 0);
+            }
+            Expression::Grouping(expr) => self.compile(expr, locals, calls)?,
+            Expression::Call(name, args) => {
+                if let Some(function) = self.registry.user_function(name) {
+                    Arity::Exact(function.params.len())
+                        .check(name, args.len())
+                        .map_err(|error| EvalError::from_function(error, expression.to_string()))?;
+                    if calls >= MAX_CALL_DEPTH {
+                        return Err(EvalError::RecursionLimit(expression.to_string()));
+                    }
+
+                    let mut params = HashMap::new();
+                    for (param, arg) in function.params.iter().zip(args) {
+                        self.compile(arg, locals, calls)?;
+                        self.emit(Instruction::StoreLocal(self.program.locals), -1);
+                        params.insert(param.clone(), self.program.locals);
+                        self.program.locals += 1;
+                    }
+                    return self.compile(&function.body, &params, calls + 1);
+                }
+
+                let function = self
+                    .registry
+                    .function(name)
+                    .ok_or_else(|| FunctionError::UnknownFunction(name.clone()))
+                    .and_then(|function| function.arity.check(name, args.len()).map(|_| function))
+                    .map_err(|error| EvalError::from_function(error, expression.to_string()))?;
+                for arg in args {
+                    self.compile(arg, locals, calls)?;
+                }
+                let source = self.source(expression);
+                self.emit(Instruction::Call(function.body, args.len(), source), 1 - args.len() as isize);
+            }
+            Expression::Convert(_, _) => {
+                return Err(EvalError::DomainError {
+                    expression: expression.to_string(),
+                    message: "unit conversion cannot be compiled".to_string(),
+                });
+            }
+            Expression::Bitwise(_, _, _) | Expression::BitNot(_) => {
+                return Err(EvalError::DomainError {
+                    expression: expression.to_string(),
+                    message: "bitwise operators cannot be compiled".to_string(),
+                });
+            }
+            Expression::Equation(_, _) => {
+                return Err(EvalError::DomainError {
+                    expression: expression.to_string(),
+                    message: "an equation has no value".to_string(),
+                });
+            }
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::parsemath::ast::{EvalError, Expression, Statement};
+    use crate::parsemath::functions::{FunctionError, FunctionRegistry};
+    use crate::parsemath::parser::Parser;
+    use rstest::rstest;
+
+    fn define(registry: &mut FunctionRegistry, definition: &str) {
+        match Parser::with_registry(definition, registry).unwrap().parse_statement().unwrap() {
+            Statement::FunctionDefinition { name, params, body } => registry.define(&name, params, body),
+            statement => panic!("not a definition: {:?}", statement),
+        }
+    }
+
+    #[rstest]
+    #[case::arithmetic("1 + 2 * 3 - 4 / 2", &[], 5.0)]
+    #[case::power_and_negate("-2^2 + 2^-1", &[], -3.5)]
+    #[case::builtins("max(1, sqrt(16), min(7, 3)) + log(2, 8)", &[], 7.0)]
+    #[case::constants("cos(pi)", &[], -1.0)]
+    #[case::variables("x^2 + y", &[3.0, 1.0], 10.0)]
+    #[case::repeated_variable("x * x - x", &[4.0], 12.0)]
+    fn compiled_programs_evaluate_expressions(#[case] expression: &str, #[case] bindings: &[f64], #[case] expected: f64) {
+        let ast = Parser::new(expression).unwrap().parse_with_variables(&["x", "y"]).unwrap();
+
+        let program = ast.compile().unwrap();
+
+        assert_eq!(program.eval(bindings), Ok(expected));
+    }
+
+    #[test]
+    fn variables_are_numbered_by_first_use() {
+        let mut registry = FunctionRegistry::with_builtins();
+        registry.assign("k", Expression::Number(2.0));
+        let ast = Parser::with_registry("b + k * a", &registry).unwrap().parse_with_variables(&["a", "b"]).unwrap();
+
+        let program = ast.compile_with(&registry).unwrap();
+
+        assert_eq!(program.variables(), ["b", "a"]);
+        assert_eq!(program.eval(&[1.0, 10.0]), Ok(21.0));
+        assert_eq!(program.eval(&[1.0]), Err(EvalError::UnknownVariable("a".to_string())));
+    }
+
+    #[test]
+    fn user_functions_are_inlined() {
+        let mut registry = FunctionRegistry::with_builtins();
+        define(&mut registry, "sq(x) = x * x");
+        define(&mut registry, "hyp(a, b) = sqrt(sq(a) + sq(b))");
+        let ast = Parser::with_registry("hyp(3, 4) + sq(x)", &registry).unwrap().parse_with_variables(&["x"]).unwrap();
+
+        let program = ast.compile_with(&registry).unwrap();
+
+        assert_eq!(program.eval(&[2.0]), Ok(9.0));
+    }
+
+    #[rstest]
+    #[case::division_by_zero("1 + 4 / (2 - 2)", EvalError::DivisionByZero("4 / (2 - 2)".to_string()))]
+    #[case::domain_error(
+        "2 * sqrt(-4)",
+        EvalError::DomainError { expression: "sqrt(-4)".to_string(), message: "sqrt of negative number -4".to_string() }
+    )]
+    #[case::overflow("10^400", EvalError::Overflow("10^400".to_string()))]
+    fn compiled_programs_report_the_same_errors(#[case] expression: &str, #[case] expected: EvalError) {
+        let ast = Parser::new(expression).unwrap().parse().unwrap();
+
+        let program = ast.compile().unwrap();
+
+        assert_eq!(program.eval(&[]), Err(expected.clone()));
+        assert_eq!(ast.evaluate(), Err(expected));
+    }
+
+    #[rstest]
+    #[case::unit_conversion("1 m -> cm")]
+    #[case::symbolic("diff(x^2, x)")]
+    #[case::bitwise("0xF0 | 0x0F")]
+    #[case::complex("2 + 3i")]
+    fn unsupported_expressions_do_not_compile(#[case] expression: &str) {
+        let ast = Parser::new(expression).unwrap().parse().unwrap();
+
+        assert!(ast.compile().is_err());
+    }
+
+    #[test]
+    fn recursive_functions_do_not_compile() {
+        let mut registry = FunctionRegistry::with_builtins();
+        define(&mut registry, "f(x) = f(x + 1)");
+        let ast = Parser::with_registry("f(2)", &registry).unwrap().parse().unwrap();
+
+        assert!(matches!(ast.compile_with(&registry), Err(EvalError::RecursionLimit(_))));
+    }
+
+    #[test]
+    fn unknown_functions_do_not_compile() {
+        let ast = Expression::Call("nope".to_string(), vec![]);
+
+        assert_eq!(
+            ast.compile().unwrap_err(),
+            EvalError::InvalidCall { expression: "nope()".to_string(), source: FunctionError::UnknownFunction("nope".to_string()) }
+        );
+    }
+}
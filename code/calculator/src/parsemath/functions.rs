@@ -0,0 +1,281 @@
+use crate::parsemath::ast::Expression;
+use std::collections::HashMap;
+use std::fmt;
+use std::sync::LazyLock;
+use thiserror::Error;
+
+pub type FunctionBody = fn(&[f64]) -> Result<f64, FunctionError>;
+
+#[derive(Error, Debug, PartialEq, Clone)]
+pub enum FunctionError {
+    #[error("Unknown function '{0}'")]
+    UnknownFunction(String),
+    #[error("Unknown constant '{0}'")]
+    UnknownConstant(String),
+    #[error("Function '{name}' expects {expected} argument(s), got {actual}")]
+    ArityMismatch {
+        name: String,
+        expected: Arity,
+        actual: usize,
+    },
+    #[error("Domain error: {0}")]
+    DomainError(String),
+}
+
+#[derive(Debug, PartialEq, Copy, Clone)]
+pub enum Arity {
+    Exact(usize),
+    AtLeast(usize),
+}
+
+impl Arity {
+    pub fn accepts(&self, count: usize) -> bool {
+        match self {
+            Arity::Exact(n) => count == *n,
+            Arity::AtLeast(n) => count >= *n,
+        }
+    }
+
+    pub fn check(&self, name: &str, count: usize) -> Result<(), FunctionError> {
+        if !self.accepts(count) {
+            return Err(FunctionError::ArityMismatch {
+                name: name.to_string(),
+                expected: *self,
+                actual: count,
+            });
+        }
+        Ok(())
+    }
+}
+
+impl fmt::Display for Arity {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Arity::Exact(n) => write!(f, "{}", n),
+            Arity::AtLeast(n) => write!(f, "at least {}", n),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+pub struct Function {
+    pub arity: Arity,
+    pub body: FunctionBody,
+}
+
+#[derive(Debug, PartialEq, Clone)]
+pub struct UserFunction {
+    pub params: Vec<String>,
+    pub body: Expression,
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct FunctionRegistry {
+    functions: HashMap<String, Function>,
+    user_functions: HashMap<String, UserFunction>,
+    variables: HashMap<String, Expression>,
+    constants: HashMap<String, f64>,
+}
+
+static BUILTINS: LazyLock<FunctionRegistry> = LazyLock::new(FunctionRegistry::with_builtins);
+
+impl FunctionRegistry {
+    pub fn new() -> Self {
+        FunctionRegistry::default()
+    }
+
+    pub fn builtins() -> &'static FunctionRegistry {
+        &BUILTINS
+    }
+
+    pub fn with_builtins() -> Self {
+        let mut registry = FunctionRegistry::new();
+
+        registry.register("sin", Arity::Exact(1), |args| Ok(args[0].sin()));
+        registry.register("cos", Arity::Exact(1), |args| Ok(args[0].cos()));
+        registry.register("tan", Arity::Exact(1), |args| Ok(args[0].tan()));
+        registry.register("abs", Arity::Exact(1), |args| Ok(args[0].abs()));
+        registry.register("sqrt", Arity::Exact(1), |args| {
+            if args[0] < 0.0 {
+                return Err(FunctionError::DomainError(format!("sqrt of negative number {}", args[0])));
+            }
+            Ok(args[0].sqrt())
+        });
+        registry.register("ln", Arity::Exact(1), |args| {
+            if args[0] <= 0.0 {
+                return Err(FunctionError::DomainError(format!("ln of non-positive number {}", args[0])));
+            }
+            Ok(args[0].ln())
+        });
+        registry.register("log", Arity::Exact(2), |args| {
+            let (base, x) = (args[0], args[1]);
+            if base <= 0.0 || base == 1.0 {
+                return Err(FunctionError::DomainError(format!("invalid logarithm base {}", base)));
+            }
+            if x <= 0.0 {
+                return Err(FunctionError::DomainError(format!("log of non-positive number {}", x)));
+            }
+            Ok(x.log(base))
+        });
+        // the real-valued versions of the complex helpers, so that every mode accepts them
+        registry.register("re", Arity::Exact(1), |args| Ok(args[0]));
+        registry.register("im", Arity::Exact(1), |_| Ok(0.0));
+        registry.register("conj", Arity::Exact(1), |args| Ok(args[0]));
+        registry.register("arg", Arity::Exact(1), |args| Ok(if args[0] < 0.0 { std::f64::consts::PI } else { 0.0 }));
+        registry.register("min", Arity::AtLeast(1), |args| Ok(args.iter().copied().fold(f64::INFINITY, f64::min)));
+        registry.register("max", Arity::AtLeast(1), |args| Ok(args.iter().copied().fold(f64::NEG_INFINITY, f64::max)));
+
+        registry.define_constant("pi", std::f64::consts::PI);
+        registry.define_constant("e", std::f64::consts::E);
+
+        registry
+    }
+
+    pub fn register(&mut self, name: &str, arity: Arity, body: FunctionBody) {
+        self.functions.insert(name.to_string(), Function { arity, body });
+    }
+
+    pub fn define_constant(&mut self, name: &str, value: f64) {
+        self.constants.insert(name.to_string(), value);
+    }
+
+    pub fn define(&mut self, name: &str, params: Vec<String>, body: Expression) {
+        self.user_functions.insert(name.to_string(), UserFunction { params, body });
+    }
+
+    pub fn undefine(&mut self, name: &str) -> Option<UserFunction> {
+        self.user_functions.remove(name)
+    }
+
+    pub fn assign(&mut self, name: &str, value: Expression) {
+        self.variables.insert(name.to_string(), value);
+    }
+
+    pub fn variable(&self, name: &str) -> Option<&Expression> {
+        self.variables.get(name)
+    }
+
+    pub fn function(&self, name: &str) -> Option<&Function> {
+        self.functions.get(name)
+    }
+
+    pub fn user_function(&self, name: &str) -> Option<&UserFunction> {
+        self.user_functions.get(name)
+    }
+
+    pub fn user_functions(&self) -> Vec<(&str, &UserFunction)> {
+        let mut functions = self
+            .user_functions
+            .iter()
+            .map(|(name, function)| (name.as_str(), function))
+            .collect::<Vec<_>>();
+        functions.sort_by_key(|(name, _)| *name);
+        functions
+    }
+
+    pub fn function_names(&self) -> Vec<&str> {
+        let mut names = self.functions.keys().chain(self.user_functions.keys()).map(String::as_str).collect::<Vec<_>>();
+        names.sort();
+        names
+    }
+
+    pub fn value_names(&self) -> Vec<&str> {
+        let mut names = self.variables.keys().chain(self.constants.keys()).map(String::as_str).collect::<Vec<_>>();
+        names.sort();
+        names
+    }
+
+    pub fn constant(&self, name: &str) -> Option<f64> {
+        self.constants.get(name).copied()
+    }
+
+    pub fn check_arity(&self, name: &str, count: usize) -> Result<(), FunctionError> {
+        let arity = match (self.function(name), self.user_function(name)) {
+            (Some(function), _) => function.arity,
+            (None, Some(function)) => Arity::Exact(function.params.len()),
+            (None, None) => return Err(FunctionError::UnknownFunction(name.to_string())),
+        };
+
+        arity.check(name, count)
+    }
+
+    pub fn call(&self, name: &str, args: &[f64]) -> Result<f64, FunctionError> {
+        let function = self
+            .function(name)
+            .ok_or_else(|| FunctionError::UnknownFunction(name.to_string()))?;
+        self.check_arity(name, args.len())?;
+        (function.body)(args)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rstest::rstest;
+
+    #[rstest]
+    #[case::sqrt("sqrt", vec![16.0], 4.0)]
+    #[case::abs("abs", vec![-2.5], 2.5)]
+    #[case::log("log", vec![2.0, 8.0], 3.0)]
+    #[case::min("min", vec![3.0, -1.0, 2.0], -1.0)]
+    #[case::max("max", vec![3.0], 3.0)]
+    #[case::real_part("re", vec![-2.0], -2.0)]
+    #[case::imaginary_part("im", vec![-2.0], 0.0)]
+    #[case::argument_of_negative("arg", vec![-2.0], std::f64::consts::PI)]
+    fn builtin_functions(#[case] name: &str, #[case] args: Vec<f64>, #[case] expected: f64) {
+        let result = FunctionRegistry::builtins().call(name, &args).unwrap();
+
+        assert!((result - expected).abs() < 1e-12);
+    }
+
+    #[rstest]
+    #[case::sqrt_negative("sqrt", vec![-1.0])]
+    #[case::ln_zero("ln", vec![0.0])]
+    #[case::log_base_one("log", vec![1.0, 10.0])]
+    fn builtin_functions_report_domain_errors(#[case] name: &str, #[case] args: Vec<f64>) {
+        let error = FunctionRegistry::builtins().call(name, &args).unwrap_err();
+
+        assert!(matches!(error, FunctionError::DomainError(_)));
+    }
+
+    #[test]
+    fn arity_mismatch_is_reported() {
+        let error = FunctionRegistry::builtins().call("log", &[10.0]).unwrap_err();
+
+        assert_eq!(error, FunctionError::ArityMismatch { name: "log".to_string(), expected: Arity::Exact(2), actual: 1 });
+        assert_eq!(format!("{}", error), "Function 'log' expects 2 argument(s), got 1");
+    }
+
+    #[test]
+    fn registry_can_be_extended() {
+        let mut registry = FunctionRegistry::with_builtins();
+        registry.register("double", Arity::Exact(1), |args| Ok(args[0] * 2.0));
+        registry.define_constant("answer", 42.0);
+
+        assert_eq!(registry.call("double", &[21.0]), Ok(42.0));
+        assert_eq!(registry.constant("answer"), Some(42.0));
+    }
+
+    #[test]
+    fn registry_lists_names() {
+        let mut registry = FunctionRegistry::new();
+        registry.register("sin", Arity::Exact(1), |args| Ok(args[0].sin()));
+        registry.define("sq", vec!["x".to_string()], Expression::Variable("x".to_string()));
+        registry.define("abs2", vec!["x".to_string()], Expression::Variable("x".to_string()));
+        registry.define_constant("pi", std::f64::consts::PI);
+        registry.assign("x", Expression::Number(2.0));
+
+        assert_eq!(registry.function_names(), vec!["abs2", "sin", "sq"]);
+        assert_eq!(registry.value_names(), vec!["pi", "x"]);
+    }
+
+    #[test]
+    fn registry_stores_variables() {
+        let mut registry = FunctionRegistry::with_builtins();
+        registry.assign("x", Expression::Number(2.0));
+        registry.assign("x", Expression::Number(3.0));
+
+        assert_eq!(registry.variable("x"), Some(&Expression::Number(3.0)));
+        assert_eq!(registry.variable("y"), None);
+    }
+}
@@ -1,4 +1,10 @@
 pub mod token;
 pub mod tokenizer;
 pub mod ast;
-pub mod parser;
\ No newline at end of file
+pub mod compiler;
+pub mod functions;
+pub mod numeric;
+pub mod parser;
+pub mod solver;
+pub mod symbolic;
+pub mod units;
\ No newline at end of file
@@ -0,0 +1,871 @@
+use num_bigint::BigInt;
+use num_complex::Complex64;
+use num_rational::BigRational;
+use num_traits::{One, Signed, ToPrimitive, Zero};
+use std::fmt;
+use std::str::FromStr;
+use thiserror::Error;
+
+#[derive(Error, Debug, PartialEq, Clone)]
+pub enum NumericError {
+    #[error("division by zero")]
+    DivisionByZero,
+    #[error("overflow")]
+    Overflow,
+    #[error("{0}")]
+    DomainError(String),
+    #[error("incompatible units {0} and {1}")]
+    IncompatibleUnits(String, String),
+}
+
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub enum BitwiseOp {
+    And,
+    Or,
+    Xor,
+    ShiftLeft,
+    ShiftRight,
+}
+
+pub trait Backend {
+    type Value: Clone + fmt::Display;
+
+    fn number(&self, value: f64) -> Result<Self::Value, NumericError>;
+
+    fn integer(&self, value: i64) -> Result<Self::Value, NumericError> {
+        self.number(value as f64)
+    }
+
+    fn to_f64(&self, value: &Self::Value) -> Result<f64, NumericError>;
+    fn add(&self, a: &Self::Value, b: &Self::Value) -> Result<Self::Value, NumericError>;
+    fn subtract(&self, a: &Self::Value, b: &Self::Value) -> Result<Self::Value, NumericError>;
+    fn multiply(&self, a: &Self::Value, b: &Self::Value) -> Result<Self::Value, NumericError>;
+    fn divide(&self, a: &Self::Value, b: &Self::Value) -> Result<Self::Value, NumericError>;
+    fn power(&self, a: &Self::Value, b: &Self::Value) -> Result<Self::Value, NumericError>;
+    fn negate(&self, a: &Self::Value) -> Result<Self::Value, NumericError>;
+
+    fn unit(&self, _name: &str) -> Option<Result<Self::Value, NumericError>> {
+        None
+    }
+
+    fn convert(&self, _value: &Self::Value, _target: &Self::Value) -> Result<Self::Value, NumericError> {
+        Err(NumericError::DomainError("unit conversion is not supported".to_string()))
+    }
+
+    fn bitwise(&self, _op: BitwiseOp, _a: &Self::Value, _b: &Self::Value) -> Result<Self::Value, NumericError> {
+        Err(NumericError::DomainError("bitwise operators require programmer mode".to_string()))
+    }
+
+    fn bit_not(&self, _a: &Self::Value) -> Result<Self::Value, NumericError> {
+        Err(NumericError::DomainError("bitwise operators require programmer mode".to_string()))
+    }
+
+    fn imaginary_unit(&self) -> Result<Self::Value, NumericError> {
+        Err(NumericError::DomainError("the imaginary unit requires complex mode".to_string()))
+    }
+
+    // lets a backend evaluate a built-in function on its own values instead of going through f64
+    fn call(&self, _name: &str, _args: &[Self::Value]) -> Option<Result<Self::Value, NumericError>> {
+        None
+    }
+}
+
+pub const IMAGINARY_UNIT: &str = "i";
+
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub enum NumericMode {
+    Float,
+    Rational,
+    Decimal(u32),
+    Complex,
+    Programmer(OutputBase),
+}
+
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub enum OutputBase {
+    Dec,
+    Hex,
+    Bin,
+}
+
+pub const DEFAULT_DECIMAL_PRECISION: u32 = 10;
+pub const MAX_DECIMAL_PRECISION: u32 = 1000;
+
+impl fmt::Display for OutputBase {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            OutputBase::Dec => write!(f, "dec"),
+            OutputBase::Hex => write!(f, "hex"),
+            OutputBase::Bin => write!(f, "bin"),
+        }
+    }
+}
+
+impl FromStr for OutputBase {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "dec" => Ok(OutputBase::Dec),
+            "hex" => Ok(OutputBase::Hex),
+            "bin" => Ok(OutputBase::Bin),
+            _ => Err(format!("Unknown base '{}'. Available bases: dec, hex, bin", s)),
+        }
+    }
+}
+
+impl fmt::Display for NumericMode {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            NumericMode::Float => write!(f, "float"),
+            NumericMode::Rational => write!(f, "rational"),
+            NumericMode::Decimal(precision) => write!(f, "decimal {}", precision),
+            NumericMode::Complex => write!(f, "complex"),
+            NumericMode::Programmer(OutputBase::Dec) => write!(f, "programmer"),
+            NumericMode::Programmer(base) => write!(f, "programmer {}", base),
+        }
+    }
+}
+
+const AVAILABLE_MODES: &str = "Available modes: float, rational, decimal [precision], complex, programmer [dec|hex|bin]";
+
+impl FromStr for NumericMode {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut words = s.split_whitespace();
+        let mode = match (words.next(), words.next()) {
+            (Some("float"), None) => NumericMode::Float,
+            (Some("rational"), None) => NumericMode::Rational,
+            (Some("decimal"), None) => NumericMode::Decimal(DEFAULT_DECIMAL_PRECISION),
+            (Some("decimal"), Some(precision)) => match precision.parse() {
+                Ok(digits) if digits <= MAX_DECIMAL_PRECISION => NumericMode::Decimal(digits),
+                Ok(_) => return Err(format!("Precision {} is above the limit of {}", precision, MAX_DECIMAL_PRECISION)),
+                Err(_) => return Err(format!("Invalid precision '{}'", precision)),
+            },
+            (Some("complex"), None) => NumericMode::Complex,
+            (Some("programmer"), None) => NumericMode::Programmer(OutputBase::Dec),
+            (Some("programmer"), Some(base)) => NumericMode::Programmer(base.parse()?),
+            _ => return Err(format!("Unknown mode '{}'. {}", s, AVAILABLE_MODES)),
+        };
+
+        if words.next().is_some() {
+            return Err(format!("Unknown mode '{}'. {}", s, AVAILABLE_MODES));
+        }
+
+        Ok(mode)
+    }
+}
+
+pub struct FloatBackend;
+
+impl FloatBackend {
+    fn checked(value: f64) -> Result<f64, NumericError> {
+        if value.is_infinite() {
+            return Err(NumericError::Overflow);
+        }
+        if value.is_nan() {
+            return Err(NumericError::DomainError("result is not a number".to_string()));
+        }
+        Ok(value)
+    }
+}
+
+impl Backend for FloatBackend {
+    type Value = f64;
+
+    fn number(&self, value: f64) -> Result<f64, NumericError> {
+        FloatBackend::checked(value)
+    }
+
+    fn to_f64(&self, value: &f64) -> Result<f64, NumericError> {
+        Ok(*value)
+    }
+
+    fn add(&self, a: &f64, b: &f64) -> Result<f64, NumericError> {
+        FloatBackend::checked(a + b)
+    }
+
+    fn subtract(&self, a: &f64, b: &f64) -> Result<f64, NumericError> {
+        FloatBackend::checked(a - b)
+    }
+
+    fn multiply(&self, a: &f64, b: &f64) -> Result<f64, NumericError> {
+        FloatBackend::checked(a * b)
+    }
+
+    fn divide(&self, a: &f64, b: &f64) -> Result<f64, NumericError> {
+        if *b == 0.0 {
+            return Err(NumericError::DivisionByZero);
+        }
+        FloatBackend::checked(a / b)
+    }
+
+    fn power(&self, a: &f64, b: &f64) -> Result<f64, NumericError> {
+        if *a == 0.0 && *b < 0.0 {
+            return Err(NumericError::DivisionByZero);
+        }
+        FloatBackend::checked(a.powf(*b))
+    }
+
+    fn negate(&self, a: &f64) -> Result<f64, NumericError> {
+        Ok(-a)
+    }
+}
+
+#[derive(Debug, PartialEq, Clone)]
+pub struct Rational(pub BigRational);
+
+impl fmt::Display for Rational {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match decimal_digits(self.0.denom()) {
+            Some(scale) => {
+                let scaled = self.0.numer() * pow10(scale) / self.0.denom();
+                write!(f, "{}", format_scaled(&scaled, scale))
+            }
+            None => write!(f, "{}/{}", self.0.numer(), self.0.denom()),
+        }
+    }
+}
+
+impl Rational {
+    pub fn is_terminating_decimal(&self) -> bool {
+        decimal_digits(self.0.denom()).is_some()
+    }
+}
+
+pub struct RationalBackend;
+
+impl Backend for RationalBackend {
+    type Value = Rational;
+
+    fn number(&self, value: f64) -> Result<Rational, NumericError> {
+        exact_rational(value).map(Rational)
+    }
+
+    fn to_f64(&self, value: &Rational) -> Result<f64, NumericError> {
+        Ok(value.0.to_f64().unwrap_or(f64::NAN))
+    }
+
+    fn add(&self, a: &Rational, b: &Rational) -> Result<Rational, NumericError> {
+        Ok(Rational(&a.0 + &b.0))
+    }
+
+    fn subtract(&self, a: &Rational, b: &Rational) -> Result<Rational, NumericError> {
+        Ok(Rational(&a.0 - &b.0))
+    }
+
+    fn multiply(&self, a: &Rational, b: &Rational) -> Result<Rational, NumericError> {
+        Ok(Rational(&a.0 * &b.0))
+    }
+
+    fn divide(&self, a: &Rational, b: &Rational) -> Result<Rational, NumericError> {
+        if b.0.is_zero() {
+            return Err(NumericError::DivisionByZero);
+        }
+        Ok(Rational(&a.0 / &b.0))
+    }
+
+    fn power(&self, a: &Rational, b: &Rational) -> Result<Rational, NumericError> {
+        match integer_exponent(&b.0) {
+            Some(exponent) => exact_power(&a.0, exponent).map(Rational),
+            None => self.number(FloatBackend.power(&self.to_f64(a)?, &self.to_f64(b)?)?),
+        }
+    }
+
+    fn negate(&self, a: &Rational) -> Result<Rational, NumericError> {
+        Ok(Rational(-&a.0))
+    }
+
+    fn integer(&self, value: i64) -> Result<Rational, NumericError> {
+        Ok(Rational(BigRational::from_integer(value.into())))
+    }
+}
+
+#[derive(Debug, PartialEq, Clone)]
+pub struct Decimal {
+    scaled: BigInt,
+    precision: u32,
+}
+
+impl fmt::Display for Decimal {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", format_scaled(&self.scaled, self.precision))
+    }
+}
+
+pub struct DecimalBackend {
+    precision: u32,
+}
+
+impl DecimalBackend {
+    pub fn new(precision: u32) -> Self {
+        DecimalBackend { precision }
+    }
+
+    fn decimal(&self, scaled: BigInt) -> Decimal {
+        Decimal { scaled, precision: self.precision }
+    }
+}
+
+impl Backend for DecimalBackend {
+    type Value = Decimal;
+
+    fn number(&self, value: f64) -> Result<Decimal, NumericError> {
+        let rational = exact_rational(value)?;
+        let scaled = round_half_even(rational.numer() * pow10(self.precision), rational.denom());
+        Ok(self.decimal(scaled))
+    }
+
+    fn to_f64(&self, value: &Decimal) -> Result<f64, NumericError> {
+        Ok(BigRational::new(value.scaled.clone(), pow10(value.precision))
+            .to_f64()
+            .unwrap_or(f64::NAN))
+    }
+
+    fn add(&self, a: &Decimal, b: &Decimal) -> Result<Decimal, NumericError> {
+        Ok(self.decimal(&a.scaled + &b.scaled))
+    }
+
+    fn subtract(&self, a: &Decimal, b: &Decimal) -> Result<Decimal, NumericError> {
+        Ok(self.decimal(&a.scaled - &b.scaled))
+    }
+
+    fn multiply(&self, a: &Decimal, b: &Decimal) -> Result<Decimal, NumericError> {
+        Ok(self.decimal(round_half_even(&a.scaled * &b.scaled, &pow10(self.precision))))
+    }
+
+    fn divide(&self, a: &Decimal, b: &Decimal) -> Result<Decimal, NumericError> {
+        if b.scaled.is_zero() {
+            return Err(NumericError::DivisionByZero);
+        }
+        Ok(self.decimal(round_half_even(&a.scaled * pow10(self.precision), &b.scaled)))
+    }
+
+    fn power(&self, a: &Decimal, b: &Decimal) -> Result<Decimal, NumericError> {
+        let exponent = BigRational::new(b.scaled.clone(), pow10(b.precision));
+        match integer_exponent(&exponent) {
+            Some(exponent) => {
+                let result = exact_power(&BigRational::new(a.scaled.clone(), pow10(a.precision)), exponent)?;
+                Ok(self.decimal(round_half_even(result.numer() * pow10(self.precision), result.denom())))
+            }
+            None => self.number(FloatBackend.power(&self.to_f64(a)?, &self.to_f64(b)?)?),
+        }
+    }
+
+    fn negate(&self, a: &Decimal) -> Result<Decimal, NumericError> {
+        Ok(self.decimal(-&a.scaled))
+    }
+
+    fn integer(&self, value: i64) -> Result<Decimal, NumericError> {
+        Ok(self.decimal(BigInt::from(value) * pow10(self.precision)))
+    }
+}
+
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub struct Integer {
+    pub value: i64,
+    base: OutputBase,
+}
+
+// negative numbers print as their 64-bit two's complement pattern in hex and binary
+impl fmt::Display for Integer {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.base {
+            OutputBase::Dec => write!(f, "{}", self.value),
+            OutputBase::Hex => write!(f, "{:#X}", self.value),
+            OutputBase::Bin => write!(f, "{:#b}", self.value),
+        }
+    }
+}
+
+pub struct IntegerBackend {
+    base: OutputBase,
+}
+
+impl IntegerBackend {
+    pub fn new(base: OutputBase) -> Self {
+        IntegerBackend { base }
+    }
+
+    fn checked(&self, value: Option<i64>) -> Result<Integer, NumericError> {
+        value.map(|value| Integer { value, base: self.base }).ok_or(NumericError::Overflow)
+    }
+
+    fn shift_amount(b: &Integer) -> Result<u32, NumericError> {
+        u32::try_from(b.value)
+            .ok()
+            .filter(|amount| *amount < i64::BITS)
+            .ok_or_else(|| NumericError::DomainError(format!("shift amount {} is out of range", b.value)))
+    }
+}
+
+impl Backend for IntegerBackend {
+    type Value = Integer;
+
+    fn number(&self, value: f64) -> Result<Integer, NumericError> {
+        // 2^53 is the largest range in which every integer has an exact f64
+        if value.fract() != 0.0 || !value.is_finite() {
+            return Err(NumericError::DomainError(format!("{} is not an integer", value)));
+        }
+        if value.abs() > 9_007_199_254_740_992.0 {
+            return Err(NumericError::Overflow);
+        }
+        self.checked(Some(value as i64))
+    }
+
+    fn integer(&self, value: i64) -> Result<Integer, NumericError> {
+        self.checked(Some(value))
+    }
+
+    fn to_f64(&self, value: &Integer) -> Result<f64, NumericError> {
+        Ok(value.value as f64)
+    }
+
+    fn add(&self, a: &Integer, b: &Integer) -> Result<Integer, NumericError> {
+        self.checked(a.value.checked_add(b.value))
+    }
+
+    fn subtract(&self, a: &Integer, b: &Integer) -> Result<Integer, NumericError> {
+        self.checked(a.value.checked_sub(b.value))
+    }
+
+    fn multiply(&self, a: &Integer, b: &Integer) -> Result<Integer, NumericError> {
+        self.checked(a.value.checked_mul(b.value))
+    }
+
+    // truncates toward zero like integer division in most languages
+    fn divide(&self, a: &Integer, b: &Integer) -> Result<Integer, NumericError> {
+        if b.value == 0 {
+            return Err(NumericError::DivisionByZero);
+        }
+        self.checked(a.value.checked_div(b.value))
+    }
+
+    fn power(&self, a: &Integer, b: &Integer) -> Result<Integer, NumericError> {
+        if b.value < 0 {
+            return Err(NumericError::DomainError("negative exponents are not integers".to_string()));
+        }
+        let exponent = u32::try_from(b.value).map_err(|_| NumericError::Overflow)?;
+        self.checked(a.value.checked_pow(exponent))
+    }
+
+    fn negate(&self, a: &Integer) -> Result<Integer, NumericError> {
+        self.checked(a.value.checked_neg())
+    }
+
+    fn bitwise(&self, op: BitwiseOp, a: &Integer, b: &Integer) -> Result<Integer, NumericError> {
+        let value = match op {
+            BitwiseOp::And => a.value & b.value,
+            BitwiseOp::Or => a.value | b.value,
+            BitwiseOp::Xor => a.value ^ b.value,
+            BitwiseOp::ShiftLeft => {
+                let shifted = a.value << IntegerBackend::shift_amount(b)?;
+                if shifted >> b.value != a.value {
+                    return Err(NumericError::Overflow);
+                }
+                shifted
+            }
+            BitwiseOp::ShiftRight => a.value >> IntegerBackend::shift_amount(b)?,
+        };
+        self.checked(Some(value))
+    }
+
+    fn bit_not(&self, a: &Integer) -> Result<Integer, NumericError> {
+        self.checked(Some(!a.value))
+    }
+}
+
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub struct Complex(pub Complex64);
+
+// prints as `a+bi`, leaving out a zero real or imaginary part and a unit coefficient
+impl fmt::Display for Complex {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        // adding zero turns -0 into 0
+        let (re, im) = (self.0.re + 0.0, self.0.im + 0.0);
+        if im == 0.0 {
+            return write!(f, "{}", re);
+        }
+
+        let coefficient = if im.abs() == 1.0 { String::new() } else { im.abs().to_string() };
+        match (re == 0.0, im < 0.0) {
+            (true, false) => write!(f, "{}i", coefficient),
+            (true, true) => write!(f, "-{}i", coefficient),
+            (false, false) => write!(f, "{}+{}i", re, coefficient),
+            (false, true) => write!(f, "{}-{}i", re, coefficient),
+        }
+    }
+}
+
+pub struct ComplexBackend;
+
+impl ComplexBackend {
+    fn checked(value: Complex64) -> Result<Complex, NumericError> {
+        if value.is_infinite() {
+            return Err(NumericError::Overflow);
+        }
+        if value.is_nan() {
+            return Err(NumericError::DomainError("result is not a number".to_string()));
+        }
+        Ok(Complex(value))
+    }
+}
+
+impl Backend for ComplexBackend {
+    type Value = Complex;
+
+    fn number(&self, value: f64) -> Result<Complex, NumericError> {
+        ComplexBackend::checked(Complex64::from(value))
+    }
+
+    fn to_f64(&self, value: &Complex) -> Result<f64, NumericError> {
+        if value.0.im != 0.0 {
+            return Err(NumericError::DomainError(format!("{} is not a real number", value)));
+        }
+        Ok(value.0.re)
+    }
+
+    fn add(&self, a: &Complex, b: &Complex) -> Result<Complex, NumericError> {
+        ComplexBackend::checked(a.0 + b.0)
+    }
+
+    fn subtract(&self, a: &Complex, b: &Complex) -> Result<Complex, NumericError> {
+        ComplexBackend::checked(a.0 - b.0)
+    }
+
+    fn multiply(&self, a: &Complex, b: &Complex) -> Result<Complex, NumericError> {
+        ComplexBackend::checked(a.0 * b.0)
+    }
+
+    fn divide(&self, a: &Complex, b: &Complex) -> Result<Complex, NumericError> {
+        if b.0.is_zero() {
+            return Err(NumericError::DivisionByZero);
+        }
+        ComplexBackend::checked(a.0 / b.0)
+    }
+
+    // small integer exponents multiply out exactly, so that i^2 is -1 rather than -1+1.2e-16i
+    fn power(&self, a: &Complex, b: &Complex) -> Result<Complex, NumericError> {
+        if a.0.is_zero() {
+            return match (b.0.is_zero(), b.0.re > 0.0) {
+                (true, _) => self.number(1.0),
+                (false, true) => self.number(0.0),
+                (false, false) => Err(NumericError::DivisionByZero),
+            };
+        }
+        if b.0.im == 0.0 && b.0.re.fract() == 0.0 && b.0.re.abs() <= i32::MAX as f64 {
+            return ComplexBackend::checked(a.0.powi(b.0.re as i32));
+        }
+        ComplexBackend::checked(a.0.powc(b.0))
+    }
+
+    // subtracting from zero keeps the imaginary part of -1 at +0, so sqrt(-1) stays on the right side of the branch cut
+    fn negate(&self, a: &Complex) -> Result<Complex, NumericError> {
+        Ok(Complex(Complex64::default() - a.0))
+    }
+
+    fn imaginary_unit(&self) -> Result<Complex, NumericError> {
+        Ok(Complex(Complex64::i()))
+    }
+
+    fn call(&self, name: &str, args: &[Complex]) -> Option<Result<Complex, NumericError>> {
+        let [Complex(z)] = args else {
+            return None;
+        };
+        let value = match name {
+            "sqrt" => z.sqrt(),
+            "abs" => Complex64::from(z.norm()),
+            "arg" => Complex64::from(z.arg()),
+            "conj" => z.conj(),
+            "re" => Complex64::from(z.re),
+            "im" => Complex64::from(z.im),
+            "ln" if z.is_zero() => return Some(Err(NumericError::DomainError("ln of zero".to_string()))),
+            "ln" => z.ln(),
+            "sin" => z.sin(),
+            "cos" => z.cos(),
+            "tan" => z.tan(),
+            _ => return None,
+        };
+        Some(ComplexBackend::checked(value))
+    }
+}
+
+// f64's Display is the shortest representation that round-trips, so `0.1` becomes exactly 1/10
+fn exact_rational(value: f64) -> Result<BigRational, NumericError> {
+    if value.is_infinite() {
+        return Err(NumericError::Overflow);
+    }
+    if value.is_nan() {
+        return Err(NumericError::DomainError("result is not a number".to_string()));
+    }
+
+    let text = value.to_string();
+    let (sign, digits) = match text.strip_prefix('-') {
+        Some(digits) => (-BigInt::one(), digits),
+        None => (BigInt::one(), text.as_str()),
+    };
+    let (integer, fraction) = digits.split_once('.').unwrap_or((digits, ""));
+
+    let numer = BigInt::from_str(&format!("{}{}", integer, fraction)).expect("f64 formats as decimal digits");
+    Ok(BigRational::new(sign * numer, pow10(fraction.len() as u32)))
+}
+
+// Exponents beyond this would produce numbers too large to be of any use
+const MAX_EXACT_EXPONENT: i32 = 10_000;
+
+fn integer_exponent(exponent: &BigRational) -> Option<i32> {
+    if exponent.is_integer() {
+        exponent.numer().to_i32().filter(|e| e.abs() <= MAX_EXACT_EXPONENT)
+    } else {
+        None
+    }
+}
+
+fn exact_power(base: &BigRational, exponent: i32) -> Result<BigRational, NumericError> {
+    if base.is_zero() && exponent < 0 {
+        return Err(NumericError::DivisionByZero);
+    }
+    Ok(num_traits::Pow::pow(base, exponent))
+}
+
+fn pow10(exponent: u32) -> BigInt {
+    num_traits::pow(BigInt::from(10), exponent as usize)
+}
+
+// Number of decimal places needed to print a fraction with this denominator exactly, if finite
+fn decimal_digits(denom: &BigInt) -> Option<u32> {
+    let mut rest = denom.clone();
+    let (mut twos, mut fives) = (0, 0);
+    while (&rest % 2u32).is_zero() {
+        rest /= 2u32;
+        twos += 1;
+    }
+    while (&rest % 5u32).is_zero() {
+        rest /= 5u32;
+        fives += 1;
+    }
+    rest.is_one().then(|| u32::max(twos, fives))
+}
+
+fn round_half_even(numer: BigInt, denom: &BigInt) -> BigInt {
+    let (denom, numer) = if denom.is_negative() { (-denom, -numer) } else { (denom.clone(), numer) };
+
+    let mut quotient = &numer / &denom;
+    let mut remainder = &numer - &quotient * &denom;
+    if remainder.is_negative() {
+        quotient -= 1u32;
+        remainder += &denom;
+    }
+    let twice = &remainder * 2u32;
+
+    if twice > denom || (twice == denom && !(&quotient % 2u32).is_zero()) {
+        quotient + 1u32
+    } else {
+        quotient
+    }
+}
+
+fn format_scaled(scaled: &BigInt, scale: u32) -> String {
+    let digits = scaled.abs().to_string();
+    let scale = scale as usize;
+    let digits = format!("{:0>width$}", digits, width = scale + 1);
+    let (integer, fraction) = digits.split_at(digits.len() - scale);
+    let fraction = fraction.trim_end_matches('0');
+
+    let sign = if scaled.is_negative() { "-" } else { "" };
+    if fraction.is_empty() {
+        format!("{}{}", sign, integer)
+    } else {
+        format!("{}{}.{}", sign, integer, fraction)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rstest::rstest;
+
+    #[rstest]
+    #[case::float("float", NumericMode::Float)]
+    #[case::rational("rational", NumericMode::Rational)]
+    #[case::decimal_default("decimal", NumericMode::Decimal(DEFAULT_DECIMAL_PRECISION))]
+    #[case::decimal_precision("decimal 2", NumericMode::Decimal(2))]
+    #[case::decimal_precision_at_limit("decimal 1000", NumericMode::Decimal(MAX_DECIMAL_PRECISION))]
+    #[case::complex("complex", NumericMode::Complex)]
+    #[case::programmer("programmer", NumericMode::Programmer(OutputBase::Dec))]
+    #[case::programmer_hex("programmer hex", NumericMode::Programmer(OutputBase::Hex))]
+    fn parse_numeric_mode(#[case] text: &str, #[case] expected: NumericMode) {
+        assert_eq!(text.parse::<NumericMode>(), Ok(expected));
+    }
+
+    #[rstest]
+    #[case::unknown("interval")]
+    #[case::bad_precision("decimal two")]
+    #[case::precision_above_limit("decimal 1001")]
+    #[case::trailing("float 2")]
+    #[case::bad_base("programmer oct")]
+    fn parse_invalid_numeric_mode(#[case] text: &str) {
+        assert!(text.parse::<NumericMode>().is_err());
+    }
+
+    #[rstest]
+    #[case::tenth(0.1, "0.1")]
+    #[case::negative(-2.5, "-2.5")]
+    #[case::integer(42.0, "42")]
+    fn rational_from_f64_is_exact_decimal(#[case] value: f64, #[case] expected: &str) {
+        let rational = RationalBackend.number(value).unwrap();
+
+        assert_eq!(rational.to_string(), expected);
+    }
+
+    #[test]
+    fn rational_arithmetic_is_exact() {
+        let backend = RationalBackend;
+        let sum = backend.add(&backend.number(0.1).unwrap(), &backend.number(0.2).unwrap()).unwrap();
+        let third = backend.divide(&backend.number(1.0).unwrap(), &backend.number(3.0).unwrap()).unwrap();
+
+        assert_eq!(sum.to_string(), "0.3");
+        assert_eq!(third.to_string(), "1/3");
+        assert_eq!(backend.divide(&sum, &backend.number(0.0).unwrap()), Err(NumericError::DivisionByZero));
+    }
+
+    #[rstest]
+    #[case::two_thirds(2, 2.0, 3.0, "0.67")]
+    #[case::half_even_down(0, 5.0, 2.0, "2")]
+    #[case::half_even_up(0, 7.0, 2.0, "4")]
+    #[case::negative(3, -1.0, 8.0, "-0.125")]
+    fn decimal_division_rounds_half_even(#[case] precision: u32, #[case] a: f64, #[case] b: f64, #[case] expected: &str) {
+        let backend = DecimalBackend::new(precision);
+
+        let result = backend.divide(&backend.number(a).unwrap(), &backend.number(b).unwrap()).unwrap();
+
+        assert_eq!(result.to_string(), expected);
+    }
+
+    #[rstest]
+    #[case::square(0.1, 2.0, "0.01")]
+    #[case::negative_exponent(2.0, -3.0, "0.125")]
+    #[case::fractional_exponent(4.0, 0.5, "2")]
+    fn rational_power(#[case] base: f64, #[case] exponent: f64, #[case] expected: &str) {
+        let backend = RationalBackend;
+
+        let result = backend.power(&backend.number(base).unwrap(), &backend.number(exponent).unwrap()).unwrap();
+
+        assert_eq!(result.to_string(), expected);
+    }
+
+    #[test]
+    fn power_of_zero_to_negative_exponent_is_division_by_zero() {
+        assert_eq!(FloatBackend.power(&0.0, &-1.0), Err(NumericError::DivisionByZero));
+        assert_eq!(RationalBackend.power(&RationalBackend.number(0.0).unwrap(), &RationalBackend.number(-2.0).unwrap()), Err(NumericError::DivisionByZero));
+    }
+
+    #[test]
+    fn float_backend_detects_overflow() {
+        assert_eq!(FloatBackend.multiply(&1e200, &1e200), Err(NumericError::Overflow));
+        assert_eq!(FloatBackend.divide(&1.0, &0.0), Err(NumericError::DivisionByZero));
+    }
+
+    #[rstest]
+    #[case::dec(OutputBase::Dec, 255, "255")]
+    #[case::hex(OutputBase::Hex, 255, "0xFF")]
+    #[case::bin(OutputBase::Bin, 10, "0b1010")]
+    #[case::negative_dec(OutputBase::Dec, -1, "-1")]
+    #[case::negative_hex(OutputBase::Hex, -1, "0xFFFFFFFFFFFFFFFF")]
+    fn integers_print_in_output_base(#[case] base: OutputBase, #[case] value: i64, #[case] expected: &str) {
+        let integer = IntegerBackend::new(base).integer(value).unwrap();
+
+        assert_eq!(integer.to_string(), expected);
+    }
+
+    #[rstest]
+    #[case::and(BitwiseOp::And, 0b1100, 0b1010, 0b1000)]
+    #[case::or(BitwiseOp::Or, 0b1100, 0b1010, 0b1110)]
+    #[case::xor(BitwiseOp::Xor, 0b1100, 0b1010, 0b0110)]
+    #[case::shift_left(BitwiseOp::ShiftLeft, 1, 62, 1 << 62)]
+    #[case::shift_right_is_arithmetic(BitwiseOp::ShiftRight, -8, 1, -4)]
+    fn integer_bitwise_operators(#[case] op: BitwiseOp, #[case] a: i64, #[case] b: i64, #[case] expected: i64) {
+        let backend = IntegerBackend::new(OutputBase::Dec);
+
+        let result = backend.bitwise(op, &backend.integer(a).unwrap(), &backend.integer(b).unwrap());
+
+        assert_eq!(result.map(|integer| integer.value), Ok(expected));
+    }
+
+    #[rstest]
+    #[case::real(3.0, 0.0, "3")]
+    #[case::negative_zero_imaginary(3.0, -0.0, "3")]
+    #[case::imaginary(0.0, 2.0, "2i")]
+    #[case::unit(0.0, 1.0, "i")]
+    #[case::negative_unit(-0.0, -1.0, "-i")]
+    #[case::both(3.0, 4.0, "3+4i")]
+    #[case::negative_imaginary(1.5, -0.5, "1.5-0.5i")]
+    #[case::unit_coefficient(-1.0, 1.0, "-1+i")]
+    fn complex_display(#[case] re: f64, #[case] im: f64, #[case] expected: &str) {
+        assert_eq!(Complex(Complex64::new(re, im)).to_string(), expected);
+    }
+
+    #[rstest]
+    #[case::square_of_i("sqrt", (-1.0, 0.0), (0.0, 1.0))]
+    #[case::abs("abs", (3.0, 4.0), (5.0, 0.0))]
+    #[case::arg("arg", (0.0, 2.0), (std::f64::consts::FRAC_PI_2, 0.0))]
+    #[case::conj("conj", (3.0, 4.0), (3.0, -4.0))]
+    #[case::re("re", (3.0, 4.0), (3.0, 0.0))]
+    #[case::im("im", (3.0, 4.0), (4.0, 0.0))]
+    #[case::ln("ln", (-1.0, 0.0), (0.0, std::f64::consts::PI))]
+    fn complex_functions(#[case] name: &str, #[case] z: (f64, f64), #[case] expected: (f64, f64)) {
+        let z = Complex(Complex64::new(z.0, z.1));
+
+        let result = ComplexBackend.call(name, &[z]).unwrap().unwrap();
+
+        assert_eq!(result, Complex(Complex64::new(expected.0, expected.1)));
+    }
+
+    #[test]
+    fn complex_arithmetic() {
+        let backend = ComplexBackend;
+        let z = |re, im| Complex(Complex64::new(re, im));
+        let i = backend.imaginary_unit().unwrap();
+
+        assert_eq!(backend.power(&i, &z(2.0, 0.0)), Ok(z(-1.0, 0.0)));
+        assert_eq!(backend.multiply(&z(1.0, 2.0), &z(3.0, -1.0)), Ok(z(5.0, 5.0)));
+        assert_eq!(backend.divide(&z(5.0, 5.0), &z(1.0, 2.0)), Ok(z(3.0, -1.0)));
+        assert_eq!(backend.divide(&i, &z(0.0, 0.0)), Err(NumericError::DivisionByZero));
+        assert_eq!(backend.power(&z(0.0, 0.0), &i), Err(NumericError::DivisionByZero));
+        assert_eq!(backend.to_f64(&i), Err(NumericError::DomainError("i is not a real number".to_string())));
+        assert_eq!(ComplexBackend.call("min", &[i, i]), None);
+    }
+
+    #[test]
+    fn integer_overflow_is_detected() {
+        let backend = IntegerBackend::new(OutputBase::Dec);
+        let int = |value| backend.integer(value).unwrap();
+
+        assert_eq!(backend.add(&int(i64::MAX), &int(1)), Err(NumericError::Overflow));
+        assert_eq!(backend.multiply(&int(1 << 32), &int(1 << 32)), Err(NumericError::Overflow));
+        assert_eq!(backend.power(&int(2), &int(64)), Err(NumericError::Overflow));
+        assert_eq!(backend.negate(&int(i64::MIN)), Err(NumericError::Overflow));
+        assert_eq!(backend.bitwise(BitwiseOp::ShiftLeft, &int(1), &int(63)), Err(NumericError::Overflow));
+        assert_eq!(
+            backend.bitwise(BitwiseOp::ShiftLeft, &int(1), &int(64)),
+            Err(NumericError::DomainError("shift amount 64 is out of range".to_string()))
+        );
+    }
+
+    #[rstest]
+    #[case::fraction(0.5, NumericError::DomainError("0.5 is not an integer".to_string()))]
+    #[case::inexact(1e17, NumericError::Overflow)]
+    fn integer_backend_rejects_inexact_numbers(#[case] value: f64, #[case] expected: NumericError) {
+        assert_eq!(IntegerBackend::new(OutputBase::Dec).number(value), Err(expected));
+    }
+
+    #[test]
+    fn integer_division_truncates() {
+        let backend = IntegerBackend::new(OutputBase::Dec);
+
+        let result = backend.divide(&backend.integer(-7).unwrap(), &backend.integer(2).unwrap());
+
+        assert_eq!(result.map(|integer| integer.value), Ok(-3));
+    }
+}
@@ -1,44 +1,252 @@
-use crate::parsemath::ast::Expression;
-use crate::parsemath::token::Token;
+use crate::parsemath::ast::{Expression, Statement};
+use crate::parsemath::functions::{Arity, FunctionError, FunctionRegistry};
+use crate::parsemath::numeric::{BitwiseOp, IMAGINARY_UNIT};
+use crate::parsemath::solver;
+use crate::parsemath::symbolic;
+use crate::parsemath::token::{Span, Token};
 use crate::parsemath::tokenizer::{Tokenizer, TokenizingError};
+use crate::parsemath::units;
 use thiserror::Error;
 
 #[derive(Error, Debug, PartialEq, Clone)]
 pub enum ParserError {
     #[error("Syntax error: {0}")]
-    UnexpectedToken(#[from] TokenizingError),
+    UnexpectedToken(TokenizingError, Span),
     #[error("Syntax error: {0}")]
-    SyntaxError(String)
+    SyntaxError(String, Span),
+    #[error("Syntax error: {0}")]
+    FunctionError(FunctionError, Span),
+}
+
+impl ParserError {
+    pub fn span(&self) -> Span {
+        match self {
+            ParserError::UnexpectedToken(_, span) => *span,
+            ParserError::SyntaxError(_, span) => *span,
+            ParserError::FunctionError(_, span) => *span,
+        }
+    }
 }
 
 #[derive(Debug)]
-pub struct Parser {
-    tokens: Vec<Token>,
+pub struct Parser<'a> {
+    tokens: Vec<(Token, Span)>,
+    end_of_input: usize,
     current_token_index: usize,
     bracket_count: usize,
+    registry: &'a FunctionRegistry,
+    free_variables: Vec<(String, Span)>,
+    defining: Option<(String, usize)>,
 }
 
-impl Parser {
+impl Parser<'static> {
     pub fn new(expression: &str) -> Result<Self, ParserError> {
-        let tokenizer = Tokenizer::new(expression);
-        let tokens = tokenizer.collect::<Result<Vec<Token>, TokenizingError>>()?;
+        Parser::with_registry(expression, FunctionRegistry::builtins())
+    }
+}
+
+impl<'a> Parser<'a> {
+    pub fn with_registry(expression: &str, registry: &'a FunctionRegistry) -> Result<Self, ParserError> {
+        Parser::from_tokenizer(Tokenizer::new(expression), expression, registry)
+    }
+
+    // like `with_registry`, but literals without a fraction stay exact 64-bit integers, for integer arithmetic
+    pub fn with_exact_integers(expression: &str, registry: &'a FunctionRegistry) -> Result<Self, ParserError> {
+        Parser::from_tokenizer(Tokenizer::new(expression).with_exact_integers(), expression, registry)
+    }
+
+    fn from_tokenizer(
+        mut tokenizer: Tokenizer,
+        expression: &str,
+        registry: &'a FunctionRegistry,
+    ) -> Result<Self, ParserError> {
+        let mut tokens = Vec::new();
+        while let Some(token) = tokenizer.next() {
+            let token = token.map_err(|error| ParserError::UnexpectedToken(error, tokenizer.span()))?;
+            tokens.push((token, tokenizer.span()));
+        }
 
         Ok(Parser {
             tokens,
+            end_of_input: expression.len(),
             current_token_index: 0,
             bracket_count: 0,
+            registry,
+            free_variables: Vec::new(),
+            defining: None,
         })
     }
 
     pub fn parse(&mut self) -> Result<Expression, ParserError> {
-        let expression = self.expression();
-        expression
+        self.parse_with_variables(&[])
+    }
+
+    // like `parse`, but `variables` may appear free, e.g. in formulas that are compiled and evaluated many times
+    pub fn parse_with_variables(&mut self, variables: &[&str]) -> Result<Expression, ParserError> {
+        let expression = self.expression()?;
+        self.expect_end()?;
+
+        if let Some((name, span)) = self.free_variables.iter().find(|(name, _)| !variables.contains(&name.as_str())) {
+            return Err(ParserError::FunctionError(FunctionError::UnknownConstant(name.clone()), *span));
+        }
+
+        Ok(expression)
+    }
+
+    pub fn parse_statement(&mut self) -> Result<Statement, ParserError> {
+        if let (Some(Token::Identifier(_)), Some(Token::Equals)) = (self.peek(), self.peek_at(1)) {
+            return self.assignment();
+        }
+        if self.has_top_level_equals() {
+            return self.function_definition();
+        }
+
+        Ok(Statement::Expression(self.parse()?))
+    }
+
+    // an `=` inside parentheses belongs to an equation like `solve(x^2 = 2, x)`
+    fn has_top_level_equals(&self) -> bool {
+        let mut depth = 0usize;
+        self.tokens.iter().any(|(token, _)| {
+            match token {
+                Token::LeftParen => depth += 1,
+                Token::RightParen => depth = depth.saturating_sub(1),
+                _ => {}
+            }
+            *token == Token::Equals && depth == 0
+        })
+    }
+
+    fn assignment(&mut self) -> Result<Statement, ParserError> {
+        let name = match self.next() {
+            Some(Token::Identifier(name)) => name,
+            _ => return Err(ParserError::SyntaxError("Expected variable name.".to_string(), self.previous_span())),
+        };
+        let name_span = self.previous_span();
+
+        if self.registry.constant(&name).is_some() {
+            return Err(ParserError::SyntaxError(format!("Cannot assign to constant '{}'.", name), name_span));
+        }
+        if units::is_unit(&name) {
+            return Err(ParserError::SyntaxError(format!("Cannot assign to unit '{}'.", name), name_span));
+        }
+        if name == IMAGINARY_UNIT {
+            return Err(ParserError::SyntaxError(format!("Cannot assign to the imaginary unit '{}'.", name), name_span));
+        }
+
+        self.consume();
+        let value = self.parse()?;
+
+        Ok(Statement::Assignment { name, value })
+    }
+
+    fn function_definition(&mut self) -> Result<Statement, ParserError> {
+        let name = match self.next() {
+            Some(Token::Identifier(name)) => name,
+            _ => return Err(ParserError::SyntaxError("Expected function name.".to_string(), self.previous_span())),
+        };
+        let name_span = self.previous_span();
+
+        if self.registry.function(&name).is_some() || special_arity(&name).is_some() {
+            return Err(ParserError::SyntaxError(format!("Cannot redefine built-in function '{}'.", name), name_span));
+        }
+
+        if self.next() != Some(Token::LeftParen) {
+            return Err(ParserError::SyntaxError("Expect '(' after function name.".to_string(), self.previous_span()));
+        }
+
+        let mut params: Vec<String> = Vec::new();
+        if let Some(Token::RightParen) = self.peek() {
+            self.consume();
+        } else {
+            loop {
+                match self.next() {
+                    Some(Token::Identifier(param)) if params.contains(&param) => {
+                        return Err(ParserError::SyntaxError(format!("Duplicate parameter '{}'.", param), self.previous_span()));
+                    }
+                    Some(Token::Identifier(param)) => params.push(param),
+                    _ => return Err(ParserError::SyntaxError("Expected parameter name.".to_string(), self.previous_span())),
+                }
+                match self.next() {
+                    Some(Token::Comma) => continue,
+                    Some(Token::RightParen) => break,
+                    _ => return Err(ParserError::SyntaxError("Expect ')' after parameters.".to_string(), self.previous_span())),
+                }
+            }
+        }
+
+        if self.next() != Some(Token::Equals) {
+            return Err(ParserError::SyntaxError("Expect '=' after parameters.".to_string(), self.previous_span()));
+        }
+
+        self.defining = Some((name.clone(), params.len()));
+        let body = self.expression()?;
+        self.expect_end()?;
+
+        if let Some((param, span)) = self.free_variables.iter().find(|(variable, _)| !params.contains(variable)) {
+            return Err(ParserError::SyntaxError(format!("Undefined parameter '{}' in definition of '{}'.", param, name), *span));
+        }
+
+        Ok(Statement::FunctionDefinition { name, params, body })
+    }
+
+    fn expect_end(&self) -> Result<(), ParserError> {
+        match self.peek() {
+            Some(token) => Err(ParserError::SyntaxError(format!("Unexpected '{}'.", token), self.current_span())),
+            None => Ok(()),
+        }
     }
 
     fn expression(&mut self) -> Result<Expression, ParserError> {
-        let expression = self.term();
+        let expression = self.bit_or()?;
+
+        if let Some(Token::Arrow) = self.peek() {
+            self.consume();
+            let target = self.term()?;
+            return Ok(Expression::Convert(Box::new(expression), Box::new(target)));
+        }
 
-        expression
+        Ok(expression)
+    }
+
+    // bitwise operators bind looser than arithmetic: `|`, then `xor`, then `&`, then shifts
+    fn bit_or(&mut self) -> Result<Expression, ParserError> {
+        self.bitwise_operands(Parser::bit_xor, |token| matches!(token, Token::Pipe).then_some(BitwiseOp::Or))
+    }
+
+    fn bit_xor(&mut self) -> Result<Expression, ParserError> {
+        self.bitwise_operands(Parser::bit_and, |token| match token {
+            Token::Identifier(name) if name == "xor" => Some(BitwiseOp::Xor),
+            _ => None,
+        })
+    }
+
+    fn bit_and(&mut self) -> Result<Expression, ParserError> {
+        self.bitwise_operands(Parser::shift, |token| matches!(token, Token::Ampersand).then_some(BitwiseOp::And))
+    }
+
+    fn shift(&mut self) -> Result<Expression, ParserError> {
+        self.bitwise_operands(Parser::term, |token| match token {
+            Token::ShiftLeft => Some(BitwiseOp::ShiftLeft),
+            Token::ShiftRight => Some(BitwiseOp::ShiftRight),
+            _ => None,
+        })
+    }
+
+    fn bitwise_operands(
+        &mut self,
+        operand: fn(&mut Self) -> Result<Expression, ParserError>,
+        operator: fn(&Token) -> Option<BitwiseOp>,
+    ) -> Result<Expression, ParserError> {
+        let mut expression = operand(self)?;
+
+        while let Some(op) = self.peek().as_ref().and_then(operator) {
+            self.consume();
+            let right = operand(self)?;
+            expression = Expression::Bitwise(op, Box::new(expression), Box::new(right));
+        }
+
+        Ok(expression)
     }
 
     fn term(&mut self) -> Result<Expression, ParserError> {
@@ -57,10 +265,10 @@ impl Parser {
                     expression = Expression::Subtract(Box::new(expression), Box::new(right));
                 }
                 Some(Token::RightParen) if self.bracket_count == 0 => {
-                    return Err(ParserError::SyntaxError(r#"Too many ')'."#.to_string()));
+                    return Err(ParserError::SyntaxError(r#"Too many ')'."#.to_string(), self.current_span()));
                 },
                 Some(Token::LeftParen) if self.bracket_count == 0 => {
-                    return Err(ParserError::SyntaxError(r#"Unexpected '('."#.to_string()));
+                    return Err(ParserError::SyntaxError(r#"Unexpected '('."#.to_string(), self.current_span()));
                 }
                 _ => break,
             }
@@ -70,22 +278,22 @@ impl Parser {
     }
 
     fn factor(&mut self) -> Result<Expression, ParserError> {
-        let mut expression = self.unary()?;
+        let mut expression = self.quantity()?;
 
         loop {
             match self.peek() {
                 Some(Token::Star) => {
                     self.consume();
-                    let right = self.unary()?;
+                    let right = self.quantity()?;
                     expression = Expression::Multiply(Box::new(expression), Box::new(right));
                 }
                 Some(Token::Slash) => {
                     self.consume();
-                    let right = self.unary()?;
+                    let right = self.quantity()?;
                     expression = Expression::Divide(Box::new(expression), Box::new(right));
                 },
                 Some(Token::RightParen) if self.bracket_count == 0 => {
-                    return Err(ParserError::SyntaxError(r#"Too many ')'."#.to_string()));
+                    return Err(ParserError::SyntaxError(r#"Too many ')'."#.to_string(), self.current_span()));
                 }
                 _ => break,
             }
@@ -94,6 +302,22 @@ impl Parser {
         Ok(expression)
     }
 
+    // units written right after a value multiply it before any other operator applies: `2 s` in `4 m / 2 s`
+    fn quantity(&mut self) -> Result<Expression, ParserError> {
+        let mut expression = self.unary()?;
+
+        while let Some(Token::Identifier(name)) = self.peek() {
+            // `2 m` and `3i` bind tighter than any operator
+            if !(units::is_unit(&name) || name == IMAGINARY_UNIT) || self.peek_at(1) == Some(Token::LeftParen) {
+                break;
+            }
+            let unit = self.power()?;
+            expression = Expression::Multiply(Box::new(expression), Box::new(unit));
+        }
+
+        Ok(expression)
+    }
+
     fn unary(&mut self) -> Result<Expression, ParserError> {
         if let Some(Token::Minus) = self.peek() {
             self.consume();
@@ -101,25 +325,120 @@ impl Parser {
             let right = self.unary()?;
             return Ok(Expression::Negate(Box::new(right)));
         }
+        if let Some(Token::Tilde) = self.peek() {
+            self.consume();
+
+            let right = self.unary()?;
+            return Ok(Expression::BitNot(Box::new(right)));
+        }
 
-        self.primary()
+        self.power()
+    }
+
+    fn power(&mut self) -> Result<Expression, ParserError> {
+        let base = self.primary()?;
+
+        if let Some(Token::Caret) = self.peek() {
+            self.consume();
+
+            let exponent = self.unary()?;
+            return Ok(Expression::Power(Box::new(base), Box::new(exponent)));
+        }
+
+        Ok(base)
     }
 
     fn primary(&mut self) -> Result<Expression, ParserError> {
         let expression = match self.next() {
             Some(Token::Number(n)) => Expression::Number(n),
+            Some(Token::Integer(n)) => Expression::Integer(n),
+            Some(Token::Identifier(name)) => {
+                if let Some(Token::LeftParen) = self.peek() {
+                    self.consume();
+                    self.call(name)?
+                } else {
+                    self.variable(name)
+                }
+            }
             Some(Token::LeftParen) => {
                 self.bracket_count += 1;
                 let expression = self.expression()?;
                 self.consume_right_paren()?;
                 Expression::Grouping(Box::new(expression))
             }
-            _ => return Err(ParserError::SyntaxError("Expected number or '('.".to_string())),
+            _ => return Err(ParserError::SyntaxError("Expected number or '('.".to_string(), self.previous_span())),
         };
 
         Ok(expression)
     }
 
+    fn call(&mut self, name: String) -> Result<Expression, ParserError> {
+        let name_span = self.span_at(self.current_token_index - 2);
+        let free_variables_start = self.free_variables.len();
+        self.bracket_count += 1;
+
+        let mut args = Vec::new();
+        let mut arg_spans = Vec::new();
+        if let Some(Token::RightParen) = self.peek() {
+            self.consume();
+            self.bracket_count -= 1;
+        } else {
+            loop {
+                let arg_start = self.current_span();
+                let mut arg = self.expression()?;
+                if name == solver::SOLVE && args.is_empty() && self.peek() == Some(Token::Equals) {
+                    self.consume();
+                    arg = Expression::Equation(Box::new(arg), Box::new(self.expression()?));
+                }
+                args.push(arg);
+                arg_spans.push(arg_start.to(self.previous_span()));
+                match self.next() {
+                    Some(Token::Comma) => continue,
+                    Some(Token::RightParen) => {
+                        self.bracket_count -= 1;
+                        break;
+                    }
+                    _ => return Err(ParserError::SyntaxError("Expect ')' after arguments.".to_string(), self.previous_span())),
+                }
+            }
+        }
+
+        let arity_check = match (special_arity(&name), &self.defining) {
+            (Some(arity), _) => arity.check(&name, args.len()),
+            (None, Some((defining, param_count))) if *defining == name => Arity::Exact(*param_count).check(&name, args.len()),
+            (None, _) => self.registry.check_arity(&name, args.len()),
+        };
+        arity_check.map_err(|error| match error {
+            FunctionError::UnknownFunction(_) => ParserError::FunctionError(error, name_span),
+            _ => ParserError::FunctionError(error, name_span.to(self.previous_span())),
+        })?;
+
+        if special_arity(&name).is_some() {
+            if name == symbolic::DIFF && !matches!(args[1], Expression::Variable(_)) {
+                return Err(ParserError::SyntaxError("Expected a variable to differentiate by.".to_string(), arg_spans[1]));
+            }
+            if name == solver::SOLVE && !matches!(args[1], Expression::Variable(_)) {
+                return Err(ParserError::SyntaxError("Expected a variable to solve for.".to_string(), arg_spans[1]));
+            }
+            if name == solver::SOLVE && args.len() != 2 && args.len() != 4 {
+                return Err(ParserError::SyntaxError("Expected a bracket 'low, high' after the variable.".to_string(), arg_spans[2]));
+            }
+            self.free_variables.truncate(free_variables_start);
+        }
+
+        Ok(Expression::Call(name, args))
+    }
+
+    // names that are not known constants are reported by `parse` unless used inside a symbolic call
+    fn variable(&mut self, name: String) -> Expression {
+        let bound = self.registry.constant(&name).is_some() || self.registry.variable(&name).is_some();
+        if !bound && !units::is_unit(&name) && name != IMAGINARY_UNIT {
+            self.free_variables.push((name.clone(), self.previous_span()));
+        }
+
+        Expression::Variable(name)
+    }
+
     fn consume(&mut self) {
         if !self.is_at_end() {
             self.current_token_index += 1;
@@ -129,11 +448,11 @@ impl Parser {
     fn consume_right_paren(&mut self) -> Result<(), ParserError> {
         if let Some(Token::RightParen) = self.next() {
             self.bracket_count -= 1;
-            return Ok(());
+            Ok(())
         }
         else
         {
-            Err(ParserError::SyntaxError("Expect ')' after expression.".to_string()))
+            Err(ParserError::SyntaxError("Expect ')' after expression.".to_string(), self.previous_span()))
         }
     }
 
@@ -142,23 +461,49 @@ impl Parser {
     }
 
     fn peek(&self) -> Option<Token> {
-        if self.is_at_end() {
-            return None;
+        self.peek_at(0)
+    }
+
+    fn peek_at(&self, offset: usize) -> Option<Token> {
+        self.tokens
+            .get(self.current_token_index + offset)
+            .map(|(token, _)| token.clone())
+    }
+
+    fn span_at(&self, index: usize) -> Span {
+        match self.tokens.get(index) {
+            Some((_, span)) => *span,
+            None => Span::new(self.end_of_input, self.end_of_input),
         }
-        Some(self.tokens[self.current_token_index].clone())
+    }
+
+    fn current_span(&self) -> Span {
+        self.span_at(self.current_token_index)
+    }
+
+    fn previous_span(&self) -> Span {
+        self.span_at(self.current_token_index.saturating_sub(1))
     }
 
     fn next(&mut self) -> Option<Token> {
         let token = self.peek();
         self.current_token_index += 1;
-        return token;
+        token
     }
 }
 
+// calls whose arguments may mention variables that are not defined, like `x` in `diff(x^2, x)`
+fn special_arity(name: &str) -> Option<Arity> {
+    symbolic::arity(name).or_else(|| solver::arity(name))
+}
+
 #[cfg(test)]
 mod tests {
-    use crate::parsemath::ast::Expression;
+    use crate::parsemath::ast::{Expression, Statement};
+    use crate::parsemath::functions::{Arity, FunctionError, FunctionRegistry};
+    use crate::parsemath::numeric::BitwiseOp;
     use crate::parsemath::parser::{Parser, ParserError};
+    use crate::parsemath::token::Span;
     use rstest::*;
     use crate::parsemath::tokenizer::TokenizingError;
 
@@ -306,49 +651,337 @@ mod tests {
     }
 
     #[rstest]
-    #[case::lb_1("(1")]
-    #[case::lb_lb_lb_1_rb_2_rb("(((1)2)")]
-    fn parse_unclosed_bracket(#[case] expression: &str) {
+    #[case::lb_1("(1", Span::new(2, 2))]
+    #[case::lb_lb_lb_1_rb_2_rb("(((1)2)", Span::new(5, 6))]
+    fn parse_unclosed_bracket(#[case] expression: &str, #[case] span: Span) {
         let mut parser = Parser::new(expression).unwrap();
         let parser_error = parser.parse().unwrap_err();
 
-        assert_eq!(parser_error, ParserError::SyntaxError(r#"Expect ')' after expression."#.to_string()));
+        assert_eq!(parser_error, ParserError::SyntaxError(r#"Expect ')' after expression."#.to_string(), span));
     }
 
     #[rstest]
     #[case::lb_1_plus_2_rb_rb("(1))")]
     #[case::lb_1_plus_2_rb_rb("(1))+2")]
     fn parse_too_many_closing_brackets(#[case] expression: &str) {
+        let span = Span::new(3, 4);
         let mut parser = Parser::new(expression).unwrap();
         let parser_error = parser.parse().unwrap_err();
 
-        assert_eq!(parser_error, ParserError::SyntaxError(r#"Too many ')'."#.to_string()));
+        assert_eq!(parser_error, ParserError::SyntaxError(r#"Too many ')'."#.to_string(), span));
     }
 
     #[rstest]
-    #[case::expr_plus_plus("++")]
-    #[case::expr_1_minus("1-")]
-    #[case::expr_rb_1(")1")]
-    fn parse_invalid_expression(#[case] expression: &str) {
+    #[case::expr_plus_plus("++", Span::new(0, 1))]
+    #[case::expr_1_minus("1-", Span::new(2, 2))]
+    #[case::expr_rb_1(")1", Span::new(0, 1))]
+    fn parse_invalid_expression(#[case] expression: &str, #[case] span: Span) {
         let mut parser = Parser::new(expression).unwrap();
         let parser_error = parser.parse().unwrap_err();
-        assert_eq!(parser_error, ParserError::SyntaxError(r#"Expected number or '('."#.to_string()));
+        assert_eq!(parser_error, ParserError::SyntaxError(r#"Expected number or '('."#.to_string(), span));
     }
 
     #[rstest]
     fn parsing_error_unexpected_tokens() {
         let expr = "2#";
         let parser_error = Parser::new(expr).unwrap_err();
-        assert_eq!(parser_error, ParserError::UnexpectedToken(TokenizingError::InvalidCharacter('#')));
+        assert_eq!(parser_error, ParserError::UnexpectedToken(TokenizingError::InvalidCharacter('#'), Span::new(1, 2)));
         assert_eq!(format!("{}", parser_error), "Syntax error: Unexpected token \'#\'");
     }
 
+    #[rstest]
+    #[case::constant("pi", Expression::Variable("pi".to_string()))]
+    #[case::call_one_arg(
+        "sqrt(4)",
+        Expression::Call("sqrt".to_string(), vec![Expression::Number(4.0)])
+    )]
+    #[case::call_two_args(
+        "log(2, 8)",
+        Expression::Call("log".to_string(), vec![Expression::Number(2.0), Expression::Number(8.0)])
+    )]
+    #[case::call_nested(
+        "max(1, min(2, 3) * 2)",
+        Expression::Call(
+            "max".to_string(),
+            vec![
+                Expression::Number(1.0),
+                Expression::Multiply(
+                    Box::new(Expression::Call(
+                        "min".to_string(),
+                        vec![Expression::Number(2.0), Expression::Number(3.0)]
+                    )),
+                    Box::new(Expression::Number(2.0))
+                )
+            ]
+        )
+    )]
+    fn parse_function_calls(#[case] expression: &str, #[case] expected_ast: Expression) {
+        let mut parser = Parser::new(expression).unwrap();
+
+        let ast = parser.parse().unwrap();
+        assert_eq!(ast, expected_ast);
+    }
+
+    #[rstest]
+    #[case::unknown_function("foo(1)", FunctionError::UnknownFunction("foo".to_string()), Span::new(0, 3))]
+    #[case::unknown_constant("2 * tau", FunctionError::UnknownConstant("tau".to_string()), Span::new(4, 7))]
+    #[case::too_few_args("log(8)", FunctionError::ArityMismatch { name: "log".to_string(), expected: Arity::Exact(2), actual: 1 }, Span::new(0, 6))]
+    #[case::too_many_args("1 + sin(1, 2)", FunctionError::ArityMismatch { name: "sin".to_string(), expected: Arity::Exact(1), actual: 2 }, Span::new(4, 13))]
+    #[case::no_args("max()", FunctionError::ArityMismatch { name: "max".to_string(), expected: Arity::AtLeast(1), actual: 0 }, Span::new(0, 5))]
+    fn parse_invalid_function_calls(#[case] expression: &str, #[case] expected: FunctionError, #[case] span: Span) {
+        let mut parser = Parser::new(expression).unwrap();
+        let parser_error = parser.parse().unwrap_err();
+
+        assert_eq!(parser_error, ParserError::FunctionError(expected, span));
+    }
+
+    #[rstest]
+    #[case::unclosed_call("max(1, 2", Span::new(8, 8))]
+    #[case::missing_comma("max(1 2)", Span::new(6, 7))]
+    fn parse_unclosed_call(#[case] expression: &str, #[case] span: Span) {
+        let mut parser = Parser::new(expression).unwrap();
+        let parser_error = parser.parse().unwrap_err();
+
+        assert_eq!(parser_error, ParserError::SyntaxError("Expect ')' after arguments.".to_string(), span));
+    }
+
+    #[test]
+    fn parse_with_custom_registry() {
+        let mut registry = FunctionRegistry::with_builtins();
+        registry.register("double", Arity::Exact(1), |args| Ok(args[0] * 2.0));
+
+        let mut parser = Parser::with_registry("double(21)", &registry).unwrap();
+        let ast = parser.parse().unwrap();
+
+        assert_eq!(ast.evaluate_with(&registry), Ok(42.0));
+    }
+
+    #[rstest]
+    #[case::power("2^3", Expression::Power(Box::new(Expression::Number(2.0)), Box::new(Expression::Number(3.0))))]
+    #[case::power_right_assoc(
+        "2^3^2",
+        Expression::Power(
+            Box::new(Expression::Number(2.0)),
+            Box::new(Expression::Power(Box::new(Expression::Number(3.0)), Box::new(Expression::Number(2.0))))
+        )
+    )]
+    #[case::power_binds_tighter_than_negate(
+        "-2^2",
+        Expression::Negate(Box::new(Expression::Power(Box::new(Expression::Number(2.0)), Box::new(Expression::Number(2.0)))))
+    )]
+    #[case::negative_exponent(
+        "2^-1",
+        Expression::Power(Box::new(Expression::Number(2.0)), Box::new(Expression::Negate(Box::new(Expression::Number(1.0)))))
+    )]
+    fn parse_power(#[case] expression: &str, #[case] expected_ast: Expression) {
+        let mut parser = Parser::new(expression).unwrap();
+
+        let ast = parser.parse().unwrap();
+        assert_eq!(ast, expected_ast);
+    }
+
+    #[rstest]
+    #[case::diff("diff(x^2, x)")]
+    #[case::simplify("simplify(x * y)")]
+    #[case::nested("1 + diff(simplify(a * x), x)")]
+    fn parse_free_variables_inside_symbolic_calls(#[case] expression: &str) {
+        let mut parser = Parser::new(expression).unwrap();
+
+        assert!(parser.parse().is_ok());
+    }
+
+    #[rstest]
+    #[case::outside_diff("diff(x, x) + y", FunctionError::UnknownConstant("y".to_string()), Span::new(13, 14))]
+    #[case::diff_arity("diff(x)", FunctionError::ArityMismatch { name: "diff".to_string(), expected: Arity::Exact(2), actual: 1 }, Span::new(0, 7))]
+    fn parse_invalid_symbolic_calls(#[case] expression: &str, #[case] expected: FunctionError, #[case] span: Span) {
+        let mut parser = Parser::new(expression).unwrap();
+        let parser_error = parser.parse().unwrap_err();
+
+        assert_eq!(parser_error, ParserError::FunctionError(expected, span));
+    }
+
+    #[rstest]
+    #[case::equation("solve(x^2 = 2, x)", "solve(x^2 = 2, x)")]
+    #[case::without_equals("solve(x - 1, x)", "solve(x - 1, x)")]
+    #[case::bracket("solve(cos(x) = x, x, 0, 1)", "solve(cos(x) = x, x, 0, 1)")]
+    #[case::roots("roots(y^2 - 1)", "roots(y^2 - 1)")]
+    fn parse_solver_calls(#[case] expression: &str, #[case] expected: &str) {
+        let mut parser = Parser::new(expression).unwrap();
+
+        assert_eq!(parser.parse().unwrap().to_string(), expected);
+    }
+
+    #[test]
+    fn parse_solver_call_in_definition() {
+        let mut parser = Parser::new("f(a) = solve(x = a, x)").unwrap();
+
+        assert_eq!(
+            parser.parse_statement().unwrap(),
+            Statement::FunctionDefinition {
+                name: "f".to_string(),
+                params: vec!["a".to_string()],
+                body: Expression::Call(
+                    "solve".to_string(),
+                    vec![
+                        Expression::Equation(
+                            Box::new(Expression::Variable("x".to_string())),
+                            Box::new(Expression::Variable("a".to_string()))
+                        ),
+                        Expression::Variable("x".to_string()),
+                    ]
+                ),
+            }
+        );
+    }
+
+    #[rstest]
+    #[case::not_a_variable("solve(x = 1, 2)", "Expected a variable to solve for.", Span::new(13, 14))]
+    #[case::half_bracket("solve(x = 1, x, 0)", "Expected a bracket 'low, high' after the variable.", Span::new(16, 17))]
+    #[case::equation_elsewhere("max(x = 1, 2)", "Expect ')' after arguments.", Span::new(6, 7))]
+    fn parse_invalid_solver_calls(#[case] expression: &str, #[case] message: &str, #[case] span: Span) {
+        let mut parser = Parser::new(expression).unwrap();
+
+        assert_eq!(parser.parse(), Err(ParserError::SyntaxError(message.to_string(), span)));
+    }
+
+    #[test]
+    fn parse_diff_requires_variable() {
+        let mut parser = Parser::new("diff(x^2, 2 * x)").unwrap();
+        let parser_error = parser.parse().unwrap_err();
+
+        assert_eq!(parser_error, ParserError::SyntaxError("Expected a variable to differentiate by.".to_string(), Span::new(10, 15)));
+    }
+
+    #[rstest]
+    #[case::two_params(
+        "f(x, y) = x^2 + y",
+        Statement::FunctionDefinition {
+            name: "f".to_string(),
+            params: vec!["x".to_string(), "y".to_string()],
+            body: Expression::Add(
+                Box::new(Expression::Power(Box::new(Expression::Variable("x".to_string())), Box::new(Expression::Number(2.0)))),
+                Box::new(Expression::Variable("y".to_string()))
+            )
+        }
+    )]
+    #[case::no_params(
+        "answer() = 42",
+        Statement::FunctionDefinition { name: "answer".to_string(), params: vec![], body: Expression::Number(42.0) }
+    )]
+    #[case::assignment(
+        "x = 3 m",
+        Statement::Assignment {
+            name: "x".to_string(),
+            value: Expression::Multiply(Box::new(Expression::Number(3.0)), Box::new(Expression::Variable("m".to_string())))
+        }
+    )]
+    #[case::expression("1 + 2", Statement::Expression(Expression::Add(Box::new(Expression::Number(1.0)), Box::new(Expression::Number(2.0)))))]
+    fn parse_statements(#[case] statement: &str, #[case] expected: Statement) {
+        let mut parser = Parser::new(statement).unwrap();
+
+        assert_eq!(parser.parse_statement(), Ok(expected));
+    }
+
+    #[rstest]
+    #[case::undefined_parameter("f(x) = x * z", "Undefined parameter 'z' in definition of 'f'.", Span::new(11, 12))]
+    #[case::missing_name("(x) = x", "Expected function name.", Span::new(0, 1))]
+    #[case::missing_equals("f(x) + 1 = x", "Expect '=' after parameters.", Span::new(5, 6))]
+    #[case::bad_parameter("f(1) = 1", "Expected parameter name.", Span::new(2, 3))]
+    #[case::trailing("f(x) = x = 1", "Unexpected '='.", Span::new(9, 10))]
+    #[case::assign_constant("pi = 3", "Cannot assign to constant 'pi'.", Span::new(0, 2))]
+    #[case::assign_unit("m = 3", "Cannot assign to unit 'm'.", Span::new(0, 1))]
+    #[case::assign_imaginary_unit("i = 3", "Cannot assign to the imaginary unit 'i'.", Span::new(0, 1))]
+    #[case::chained_assignment("x = y = 1", "Unexpected '='.", Span::new(6, 7))]
+    fn parse_invalid_definitions(#[case] statement: &str, #[case] message: &str, #[case] span: Span) {
+        let mut parser = Parser::new(statement).unwrap();
+
+        assert_eq!(parser.parse_statement(), Err(ParserError::SyntaxError(message.to_string(), span)));
+    }
+
+    #[rstest]
+    #[case::quantity("3 m", "3 * m")]
+    #[case::unit_binds_to_value("4 m^2 / 2 s", "4 * m^2 / (2 * s)")]
+    #[case::compound_unit("60 km/h * 2 h", "60 * km / h * 2 * h")]
+    #[case::conversion("5 kg -> lb", "5 * kg -> lb")]
+    #[case::min_as_function("min(1, 2) min", "min(1, 2) * min")]
+    fn parse_units(#[case] expression: &str, #[case] expected: &str) {
+        let mut parser = Parser::new(expression).unwrap();
+
+        assert_eq!(parser.parse().unwrap().to_string(), expected);
+    }
+
+    #[rstest]
+    fn parse_rejects_unknown_unit() {
+        let mut parser = Parser::new("3 parsec").unwrap();
+        let parser_error = parser.parse().unwrap_err();
+
+        assert_eq!(parser_error, ParserError::SyntaxError("Unexpected 'parsec'.".to_string(), Span::new(2, 8)));
+    }
+
+    #[test]
+    fn parse_with_declared_variables() {
+        let mut parser = Parser::new("x * y + z").unwrap();
+
+        assert_eq!(
+            parser.parse_with_variables(&["x", "y"]),
+            Err(ParserError::FunctionError(FunctionError::UnknownConstant("z".to_string()), Span::new(8, 9)))
+        );
+        assert!(Parser::new("x * y + z").unwrap().parse_with_variables(&["x", "y", "z"]).is_ok());
+    }
+
+    #[rstest]
+    #[case::complex_literal("3+4i", "3 + 4 * i")]
+    #[case::negative_imaginary("1-0.5i", "1 - 0.5 * i")]
+    #[case::binds_before_power("2i^2", "2 * i^2")]
+    #[case::bare_unit("i * i", "i * i")]
+    fn parse_imaginary_unit(#[case] expression: &str, #[case] expected: &str) {
+        let mut parser = Parser::new(expression).unwrap();
+
+        assert_eq!(parser.parse().unwrap().to_string(), expected);
+    }
+
+    #[rstest]
+    #[case::or_and("1 | 2 & 3", "1 | 2 & 3")]
+    #[case::grouped_or("(1 | 2) & 3", "(1 | 2) & 3")]
+    #[case::xor_between("1 & 2 xor 3 | 4", "1 & 2 xor 3 | 4")]
+    #[case::shift_of_sum("1 << 2 + 3", "1 << 2 + 3")]
+    #[case::sum_of_shift("(1 << 2) + 3", "(1 << 2) + 3")]
+    #[case::not("~0xFF & 0b1", "~255 & 1")]
+    #[case::not_of_sum("~(1 + 2)", "~(1 + 2)")]
+    fn parse_bitwise_operators(#[case] expression: &str, #[case] expected: &str) {
+        let mut parser = Parser::new(expression).unwrap();
+
+        assert_eq!(parser.parse().unwrap().to_string(), expected);
+    }
+
+    #[test]
+    fn parse_bitwise_tree() {
+        let mut parser = Parser::new("0x10 >> 2 xor 1").unwrap();
+
+        assert_eq!(
+            parser.parse(),
+            Ok(Expression::Bitwise(
+                BitwiseOp::Xor,
+                Box::new(Expression::Bitwise(BitwiseOp::ShiftRight, Box::new(Expression::Integer(16)), Box::new(Expression::Number(2.0)))),
+                Box::new(Expression::Number(1.0))
+            ))
+        );
+    }
+
+    #[rstest]
+    fn parse_trailing_comma_outside_call() {
+        let mut parser = Parser::new("1, 2").unwrap();
+        let parser_error = parser.parse().unwrap_err();
+
+        assert_eq!(parser_error, ParserError::SyntaxError("Unexpected ','.".to_string(), Span::new(1, 2)));
+    }
+
     #[rstest]
     fn bug() {
         let expr = "(1)(";
         let mut parser = Parser::new(expr).unwrap();
         let parser_error = parser.parse().unwrap_err();
-        assert_eq!(parser_error, ParserError::SyntaxError("Unexpected \'(\'.".to_string()));
+        assert_eq!(parser_error, ParserError::SyntaxError("Unexpected \'(\'.".to_string(), Span::new(3, 4)));
 
     }
 }
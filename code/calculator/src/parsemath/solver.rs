@@ -0,0 +1,436 @@
+use crate::parsemath::ast::{EvalError, Expression};
+use crate::parsemath::functions::{Arity, FunctionRegistry};
+use crate::parsemath::numeric::IMAGINARY_UNIT;
+use crate::parsemath::units;
+use std::collections::HashMap;
+
+pub const SOLVE: &str = "solve";
+pub const ROOTS: &str = "roots";
+
+// searched when `solve` is not given a bracket and the equation is not a polynomial
+const DEFAULT_BRACKET: (f64, f64) = (-100.0, 100.0);
+const SAMPLES: usize = 1000;
+const MAX_DEGREE: usize = 64;
+const MAX_ITERATIONS: usize = 200;
+
+pub fn arity(name: &str) -> Option<Arity> {
+    match name {
+        SOLVE => Some(Arity::AtLeast(2)),
+        ROOTS => Some(Arity::Exact(1)),
+        _ => None,
+    }
+}
+
+#[derive(Debug, PartialEq, Clone)]
+pub struct Solutions {
+    pub variable: String,
+    pub values: Vec<f64>,
+}
+
+// The difference of the two sides of an equation, as a function of the unknown
+struct Residual<'r> {
+    expression: Expression,
+    variable: &'r str,
+    registry: &'r FunctionRegistry,
+    bindings: &'r HashMap<String, f64>,
+}
+
+impl Residual<'_> {
+    fn at(&self, x: f64) -> Result<f64, EvalError> {
+        let mut bindings = self.bindings.clone();
+        bindings.insert(self.variable.to_string(), x);
+        self.expression.evaluate_with_bindings(self.registry, bindings)
+    }
+
+    // points where the residual cannot be evaluated, like ln(x) for x <= 0, never hold a root
+    fn value(&self, x: f64) -> f64 {
+        self.at(x).unwrap_or(f64::NAN)
+    }
+
+    fn constant(&self, expression: &Expression) -> Result<f64, EvalError> {
+        expression.evaluate_with_bindings(self.registry, self.bindings.clone())
+    }
+
+    // coefficients from the constant term up, or None when the residual is not a polynomial in the unknown
+    fn coefficients(&self, expression: &Expression) -> Result<Option<Vec<f64>>, EvalError> {
+        if !expression.depends_on(self.variable) {
+            return Ok(Some(vec![self.constant(expression)?]));
+        }
+
+        let both = |a: &Expression, b: &Expression| {
+            self.coefficients(a).and_then(|p| Ok(p.zip(self.coefficients(b)?)))
+        };
+        let coefficients = match expression {
+            Expression::Variable(_) => Some(vec![0.0, 1.0]),
+            Expression::Grouping(expr) => self.coefficients(expr)?,
+            Expression::Negate(expr) => self.coefficients(expr)?.map(|p| scale(&p, -1.0)),
+            Expression::Add(a, b) => both(a, b)?.map(|(p, q)| sum(&p, &q, 1.0)),
+            Expression::Subtract(a, b) => both(a, b)?.map(|(p, q)| sum(&p, &q, -1.0)),
+            Expression::Multiply(a, b) => both(a, b)?.and_then(|(p, q)| product(&p, &q)),
+            Expression::Divide(a, b) if !b.depends_on(self.variable) => match self.constant(b)? {
+                0.0 => return Err(EvalError::DivisionByZero(expression.to_string())),
+                divisor => self.coefficients(a)?.map(|p| scale(&p, 1.0 / divisor)),
+            },
+            Expression::Power(base, exponent) if !exponent.depends_on(self.variable) => {
+                let exponent = self.constant(exponent)?;
+                if exponent.fract() != 0.0 || !(0.0..=MAX_DEGREE as f64).contains(&exponent) {
+                    return Ok(None);
+                }
+                self.coefficients(base)?.and_then(|p| (0..exponent as usize).try_fold(vec![1.0], |power, _| product(&power, &p)))
+            }
+            _ => None,
+        };
+
+        Ok(coefficients.map(|mut p| {
+            while p.len() > 1 && p.last() == Some(&0.0) {
+                p.pop();
+            }
+            p
+        }))
+    }
+
+    // real roots in `bracket` wherever the residual changes sign between neighbouring samples
+    fn scan(&self, (low, high): (f64, f64)) -> Vec<f64> {
+        let step = (high - low) / SAMPLES as f64;
+        let mut roots = Vec::new();
+
+        let mut previous = (low, self.value(low));
+        for i in 1..=SAMPLES {
+            let x = if i == SAMPLES { high } else { low + step * i as f64 };
+            let fx = self.value(x);
+            let (a, fa) = previous;
+
+            if fa == 0.0 {
+                roots.push(a);
+            } else if fa.is_finite() && fx.is_finite() && fx != 0.0 && fa.signum() != fx.signum() {
+                let root = brent(|x| self.value(x), a, x);
+                // a sign change across a pole, like tan(x) at pi/2, converges to a huge residual
+                if self.value(root).abs() <= 1e-6 * (1.0 + fa.abs().min(fx.abs())) {
+                    roots.push(root);
+                }
+            }
+            previous = (x, fx);
+        }
+        if previous.1 == 0.0 {
+            roots.push(previous.0);
+        }
+
+        roots
+    }
+}
+
+impl Expression {
+    // every real solution of a `solve(...)` or `roots(...)` call
+    pub fn solutions(&self, registry: &FunctionRegistry) -> Result<Solutions, EvalError> {
+        self.solutions_with(registry, &HashMap::new())
+    }
+
+    pub(crate) fn solutions_with(&self, registry: &FunctionRegistry, bindings: &HashMap<String, f64>) -> Result<Solutions, EvalError> {
+        let domain_error = |message: String| EvalError::DomainError { expression: self.to_string(), message };
+
+        let (equation, variable, bracket) = match self {
+            Expression::Call(name, args) if name == SOLVE => match args.as_slice() {
+                [equation, Expression::Variable(variable)] => (equation, variable.clone(), None),
+                [equation, Expression::Variable(variable), low, high] => {
+                    let bracket = (
+                        low.evaluate_with_bindings(registry, bindings.clone())?,
+                        high.evaluate_with_bindings(registry, bindings.clone())?,
+                    );
+                    (equation, variable.clone(), Some(bracket))
+                }
+                _ => return Err(domain_error("expected solve(equation, variable) or solve(equation, variable, low, high)".to_string())),
+            },
+            Expression::Call(name, args) if name == ROOTS => match (args.as_slice(), unknowns(args, registry, bindings).as_slice()) {
+                ([polynomial], [variable]) => (polynomial, variable.clone(), None),
+                _ => return Err(domain_error("expected a polynomial in one variable".to_string())),
+            },
+            _ => return Err(domain_error("not an equation to solve".to_string())),
+        };
+
+        let expression = match equation.expand_symbolic().map_err(|error| domain_error(error.to_string()))? {
+            Expression::Equation(left, right) => Expression::Subtract(left, right),
+            expression => expression,
+        };
+        let residual = Residual { expression, variable: &variable, registry, bindings };
+        if let Some(unknown) = unknowns(std::slice::from_ref(&residual.expression), registry, bindings)
+            .into_iter()
+            .find(|name| *name != variable)
+        {
+            return Err(EvalError::UnknownVariable(unknown));
+        }
+
+        let values = match (residual.coefficients(&residual.expression)?, self) {
+            (Some(coefficients), _) if coefficients.len() == 1 => {
+                let message = match coefficients[0] {
+                    0.0 => format!("every value of {} is a solution", variable),
+                    _ => "no solution".to_string(),
+                };
+                return Err(domain_error(message));
+            }
+            (Some(coefficients), _) => {
+                let roots = polynomial_roots(&coefficients);
+                let (low, high) = bracket.unwrap_or((f64::NEG_INFINITY, f64::INFINITY));
+                roots.into_iter().filter(|root| (low..=high).contains(root)).collect()
+            }
+            (None, Expression::Call(name, _)) if name == ROOTS => {
+                return Err(domain_error(format!("not a polynomial in {}", variable)));
+            }
+            (None, _) => residual.scan(bracket.unwrap_or(DEFAULT_BRACKET)),
+        };
+
+        let values = tidy(values, |x| residual.value(x));
+        if values.is_empty() {
+            return Err(domain_error("no real solution".to_string()));
+        }
+
+        Ok(Solutions { variable, values })
+    }
+}
+
+// names that are neither bound, known constants, units nor the imaginary unit
+fn unknowns(expressions: &[Expression], registry: &FunctionRegistry, bindings: &HashMap<String, f64>) -> Vec<String> {
+    fn collect(expression: &Expression, names: &mut Vec<String>) {
+        match expression {
+            Expression::Number(_) | Expression::Integer(_) => {}
+            Expression::Variable(name) => {
+                if !names.contains(name) {
+                    names.push(name.clone());
+                }
+            }
+            Expression::Add(a, b)
+            | Expression::Subtract(a, b)
+            | Expression::Multiply(a, b)
+            | Expression::Divide(a, b)
+            | Expression::Power(a, b)
+            | Expression::Convert(a, b)
+            | Expression::Bitwise(_, a, b)
+            | Expression::Equation(a, b) => {
+                collect(a, names);
+                collect(b, names);
+            }
+            Expression::Negate(expr) | Expression::Grouping(expr) | Expression::BitNot(expr) => collect(expr, names),
+            Expression::Call(_, args) => args.iter().for_each(|arg| collect(arg, names)),
+        }
+    }
+
+    let mut names = Vec::new();
+    expressions.iter().for_each(|expression| collect(expression, &mut names));
+    names.retain(|name| {
+        !bindings.contains_key(name)
+            && registry.constant(name).is_none()
+            && registry.variable(name).is_none()
+            && !units::is_unit(name)
+            && name != IMAGINARY_UNIT
+    });
+    names
+}
+
+fn scale(p: &[f64], factor: f64) -> Vec<f64> {
+    p.iter().map(|c| c * factor).collect()
+}
+
+fn sum(p: &[f64], q: &[f64], sign: f64) -> Vec<f64> {
+    (0..p.len().max(q.len()))
+        .map(|i| p.get(i).unwrap_or(&0.0) + sign * q.get(i).unwrap_or(&0.0))
+        .collect()
+}
+
+fn product(p: &[f64], q: &[f64]) -> Option<Vec<f64>> {
+    if p.len() + q.len() - 2 > MAX_DEGREE {
+        return None;
+    }
+    let mut result = vec![0.0; p.len() + q.len() - 1];
+    for (i, a) in p.iter().enumerate() {
+        for (j, b) in q.iter().enumerate() {
+            result[i + j] += a * b;
+        }
+    }
+    Some(result)
+}
+
+fn horner(coefficients: &[f64], x: f64) -> f64 {
+    coefficients.iter().rev().fold(0.0, |acc, c| acc * x + c)
+}
+
+// Real roots in increasing order. Linear and quadratic polynomials are solved in closed form; higher
+// degrees bracket one root between each pair of neighbouring roots of the derivative
+fn polynomial_roots(coefficients: &[f64]) -> Vec<f64> {
+    match *coefficients {
+        [c, b] => vec![-c / b],
+        [c, b, a] => quadratic(a, b, c),
+        _ => {
+            let degree = coefficients.len() - 1;
+            let leading = coefficients[degree];
+            // Cauchy's bound: every root lies strictly inside it
+            let bound = 1.0 + coefficients[..degree].iter().map(|c| (c / leading).abs()).fold(0.0, f64::max);
+            let derivative = coefficients[1..].iter().enumerate().map(|(i, c)| c * (i + 1) as f64).collect::<Vec<_>>();
+
+            let mut points = vec![-bound];
+            points.extend(polynomial_roots(&derivative).into_iter().filter(|x| x.abs() < bound));
+            points.push(bound);
+
+            let size = coefficients.iter().fold(0.0, |max: f64, c| max.max(c.abs()));
+            let mut roots = Vec::new();
+            for pair in points.windows(2) {
+                let (a, b) = (pair[0], pair[1]);
+                let (fa, fb) = (horner(coefficients, a), horner(coefficients, b));
+                // a repeated root touches zero at a root of the derivative without changing sign
+                if fa.abs() <= 1e-12 * size {
+                    roots.push(a);
+                } else if fb != 0.0 && fa.signum() != fb.signum() {
+                    roots.push(brent(|x| horner(coefficients, x), a, b));
+                }
+            }
+            roots
+        }
+    }
+}
+
+// the numerically stable form of the quadratic formula, which avoids subtracting nearly equal values
+fn quadratic(a: f64, b: f64, c: f64) -> Vec<f64> {
+    let discriminant = b * b - 4.0 * a * c;
+    if discriminant < 0.0 {
+        return Vec::new();
+    }
+    if discriminant == 0.0 {
+        return vec![-b / (2.0 * a)];
+    }
+
+    let q = -(b + b.signum() * discriminant.sqrt()) / 2.0;
+    let mut roots = vec![q / a, c / q];
+    roots.sort_by(f64::total_cmp);
+    roots
+}
+
+// Brent's method: inverse quadratic interpolation that falls back to bisection whenever
+// interpolation would not shrink the bracket fast enough. `f(a)` and `f(b)` must differ in sign
+fn brent(f: impl Fn(f64) -> f64, mut a: f64, mut b: f64) -> f64 {
+    let (mut fa, mut fb) = (f(a), f(b));
+    if fa.abs() < fb.abs() {
+        std::mem::swap(&mut a, &mut b);
+        std::mem::swap(&mut fa, &mut fb);
+    }
+    let (mut c, mut fc) = (a, fa);
+    let mut d = c;
+    let mut bisected = true;
+
+    for _ in 0..MAX_ITERATIONS {
+        if fb == 0.0 || (b - a).abs() <= f64::EPSILON * b.abs().max(1.0) {
+            break;
+        }
+
+        let mut s = if fa != fc && fb != fc {
+            a * fb * fc / ((fa - fb) * (fa - fc)) + b * fa * fc / ((fb - fa) * (fb - fc)) + c * fa * fb / ((fc - fa) * (fc - fb))
+        } else {
+            b - fb * (b - a) / (fb - fa)
+        };
+
+        let outside = (s - (3.0 * a + b) / 4.0) * (s - b) >= 0.0;
+        let slow = if bisected { (s - b).abs() >= (b - c).abs() / 2.0 } else { (s - b).abs() >= (c - d).abs() / 2.0 };
+        bisected = outside || slow;
+        if bisected {
+            s = (a + b) / 2.0;
+        }
+
+        let fs = f(s);
+        d = c;
+        (c, fc) = (b, fb);
+        if fa.signum() != fs.signum() {
+            (b, fb) = (s, fs);
+        } else {
+            (a, fa) = (s, fs);
+        }
+        if fa.abs() < fb.abs() {
+            std::mem::swap(&mut a, &mut b);
+            std::mem::swap(&mut fa, &mut fb);
+        }
+    }
+
+    b
+}
+
+// sorts, prefers an integer when it solves the equation exactly, and drops duplicates
+fn tidy(mut roots: Vec<f64>, f: impl Fn(f64) -> f64) -> Vec<f64> {
+    for root in roots.iter_mut() {
+        if f(root.round()) == 0.0 {
+            *root = root.round() + 0.0;
+        }
+    }
+    roots.sort_by(f64::total_cmp);
+    roots.dedup_by(|b, a| (*b - *a).abs() <= 1e-9 * a.abs().max(1.0));
+    roots
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parsemath::parser::Parser;
+    use rstest::rstest;
+
+    fn solve(expression: &str) -> Result<Vec<f64>, EvalError> {
+        let ast = Parser::new(expression).unwrap().parse().unwrap();
+        ast.solutions(FunctionRegistry::builtins()).map(|solutions| solutions.values)
+    }
+
+    #[rstest]
+    #[case::linear("solve(2 * x + 1 = 7, x)", vec![3.0])]
+    #[case::both_sides("solve(3 * y - 2 = y + 4, y)", vec![3.0])]
+    #[case::fraction("solve(x / 4 = 1 / 8, x)", vec![0.5])]
+    #[case::quadratic("solve(x^2 = 4, x)", vec![-2.0, 2.0])]
+    #[case::double_root("solve((x - 3)^2 = 0, x)", vec![3.0])]
+    #[case::expanded("solve(x * (x + 1) = 6, x)", vec![-3.0, 2.0])]
+    #[case::equals_zero("solve(x^2 - 5 * x + 6, x)", vec![2.0, 3.0])]
+    #[case::cubic("solve(x^3 - 6 * x^2 + 11 * x = 6, x)", vec![1.0, 2.0, 3.0])]
+    #[case::transcendental("solve(cos(x) = x, x)", vec![0.7390851332151607])]
+    #[case::exponential("solve(e^x = 10, x)", vec![10f64.ln()])]
+    #[case::bracket("solve(sin(x) = 0, x, 1, 4)", vec![std::f64::consts::PI])]
+    #[case::bracket_filters_polynomial("solve(x^2 = 4, x, 0, 10)", vec![2.0])]
+    #[case::symbolic("solve(diff(x^2, x) = 4, x)", vec![2.0])]
+    fn solves_equations(#[case] expression: &str, #[case] expected: Vec<f64>) {
+        let solutions = solve(expression).unwrap();
+
+        assert_eq!(solutions.len(), expected.len(), "{:?}", solutions);
+        for (solution, expected) in solutions.iter().zip(expected) {
+            assert!((solution - expected).abs() < 1e-12, "{} != {}", solution, expected);
+        }
+    }
+
+    #[rstest]
+    #[case::quadratic("roots(x^2 - 3 * x + 2)", vec![1.0, 2.0])]
+    #[case::quartic("roots(t^4 - 5 * t^2 + 4)", vec![-2.0, -1.0, 1.0, 2.0])]
+    #[case::repeated("roots((x - 1)^3)", vec![1.0])]
+    #[case::irrational("roots(x^3 - 2)", vec![2f64.cbrt()])]
+    fn finds_polynomial_roots(#[case] expression: &str, #[case] expected: Vec<f64>) {
+        let roots = solve(expression).unwrap();
+
+        assert_eq!(roots.len(), expected.len(), "{:?}", roots);
+        for (root, expected) in roots.iter().zip(expected) {
+            assert!((root - expected).abs() < 1e-9, "{} != {}", root, expected);
+        }
+    }
+
+    #[rstest]
+    #[case::no_real_solution("solve(x^2 = -1, x)", "no real solution")]
+    #[case::contradiction("solve(x + 1 = x, x)", "no solution")]
+    #[case::identity("solve(2 * x = x + x, x)", "every value of x is a solution")]
+    #[case::pole_is_not_a_root("solve(tan(x) = 0, x, 1, 2)", "no real solution")]
+    #[case::not_polynomial("roots(sin(x))", "not a polynomial in x")]
+    #[case::two_unknowns("roots(x^2 - a)", "expected a polynomial in one variable")]
+    fn reports_unsolvable_equations(#[case] expression: &str, #[case] message: &str) {
+        let error = solve(expression).unwrap_err();
+
+        assert_eq!(error, EvalError::DomainError { expression: expression.to_string(), message: message.to_string() });
+    }
+
+    #[test]
+    fn reports_other_unknowns() {
+        assert_eq!(solve("solve(x = y, x)"), Err(EvalError::UnknownVariable("y".to_string())));
+    }
+
+    #[test]
+    fn brent_converges_on_a_bracketed_root() {
+        let root = brent(|x| x * x - 2.0, 0.0, 2.0);
+
+        assert!((root - 2f64.sqrt()).abs() < 1e-15);
+    }
+}
@@ -0,0 +1,575 @@
+use crate::parsemath::ast::Expression;
+use crate::parsemath::functions::Arity;
+use crate::parsemath::numeric::{Backend, RationalBackend};
+use crate::parsemath::solver;
+use thiserror::Error;
+
+pub const DIFF: &str = "diff";
+pub const SIMPLIFY: &str = "simplify";
+
+const MAX_SIMPLIFY_PASSES: usize = 16;
+
+#[derive(Error, Debug, PartialEq, Clone)]
+pub enum SymbolicError {
+    #[error("Cannot differentiate '{0}'")]
+    NotDifferentiable(String),
+}
+
+pub fn arity(name: &str) -> Option<Arity> {
+    match name {
+        DIFF => Some(Arity::Exact(2)),
+        SIMPLIFY => Some(Arity::Exact(1)),
+        _ => None,
+    }
+}
+
+impl Expression {
+    pub fn is_symbolic(&self) -> bool {
+        match self {
+            Expression::Number(_) | Expression::Integer(_) | Expression::Variable(_) => false,
+            Expression::Add(a, b)
+            | Expression::Subtract(a, b)
+            | Expression::Multiply(a, b)
+            | Expression::Divide(a, b)
+            | Expression::Power(a, b)
+            | Expression::Convert(a, b)
+            | Expression::Bitwise(_, a, b)
+            | Expression::Equation(a, b) => a.is_symbolic() || b.is_symbolic(),
+            Expression::Negate(expr) | Expression::Grouping(expr) | Expression::BitNot(expr) => expr.is_symbolic(),
+            // the solver expands its own arguments
+            Expression::Call(name, _) if solver::arity(name).is_some() => false,
+            Expression::Call(name, args) => arity(name).is_some() || args.iter().any(Expression::is_symbolic),
+        }
+    }
+
+    pub fn depends_on(&self, variable: &str) -> bool {
+        match self {
+            Expression::Number(_) | Expression::Integer(_) => false,
+            Expression::Variable(name) => name == variable,
+            Expression::Add(a, b)
+            | Expression::Subtract(a, b)
+            | Expression::Multiply(a, b)
+            | Expression::Divide(a, b)
+            | Expression::Power(a, b)
+            | Expression::Convert(a, b)
+            | Expression::Bitwise(_, a, b)
+            | Expression::Equation(a, b) => a.depends_on(variable) || b.depends_on(variable),
+            Expression::Negate(expr) | Expression::Grouping(expr) | Expression::BitNot(expr) => expr.depends_on(variable),
+            Expression::Call(_, args) => args.iter().any(|arg| arg.depends_on(variable)),
+        }
+    }
+
+    // Replaces every `diff(...)` and `simplify(...)` call with its result
+    pub fn expand_symbolic(&self) -> Result<Expression, SymbolicError> {
+        let expanded = match self {
+            Expression::Number(_) | Expression::Integer(_) | Expression::Variable(_) => self.clone(),
+            Expression::Add(a, b) => add(a.expand_symbolic()?, b.expand_symbolic()?),
+            Expression::Subtract(a, b) => subtract(a.expand_symbolic()?, b.expand_symbolic()?),
+            Expression::Multiply(a, b) => multiply(a.expand_symbolic()?, b.expand_symbolic()?),
+            Expression::Divide(a, b) => divide(a.expand_symbolic()?, b.expand_symbolic()?),
+            Expression::Power(a, b) => power(a.expand_symbolic()?, b.expand_symbolic()?),
+            Expression::Negate(expr) => negate(expr.expand_symbolic()?),
+            Expression::Grouping(expr) => expr.expand_symbolic()?,
+            Expression::Convert(value, target) => {
+                Expression::Convert(Box::new(value.expand_symbolic()?), Box::new(target.expand_symbolic()?))
+            }
+            Expression::Bitwise(op, a, b) => {
+                Expression::Bitwise(*op, Box::new(a.expand_symbolic()?), Box::new(b.expand_symbolic()?))
+            }
+            Expression::BitNot(expr) => Expression::BitNot(Box::new(expr.expand_symbolic()?)),
+            Expression::Equation(left, right) => {
+                Expression::Equation(Box::new(left.expand_symbolic()?), Box::new(right.expand_symbolic()?))
+            }
+            Expression::Call(name, args) => match (name.as_str(), args.as_slice()) {
+                (DIFF, [expr, Expression::Variable(variable)]) => expr.expand_symbolic()?.derivative(variable)?.simplify(),
+                (SIMPLIFY, [expr]) => expr.expand_symbolic()?.simplify(),
+                _ => Expression::Call(
+                    name.clone(),
+                    args.iter().map(Expression::expand_symbolic).collect::<Result<_, _>>()?,
+                ),
+            },
+        };
+
+        Ok(expanded)
+    }
+
+    pub fn derivative(&self, variable: &str) -> Result<Expression, SymbolicError> {
+        if !self.is_symbolic() && !self.depends_on(variable) {
+            return Ok(Expression::Number(0.0));
+        }
+
+        let derivative = match self {
+            Expression::Number(_) | Expression::Integer(_) => Expression::Number(0.0),
+            Expression::Variable(name) => Expression::Number(if name == variable { 1.0 } else { 0.0 }),
+            Expression::Add(a, b) => add(a.derivative(variable)?, b.derivative(variable)?),
+            Expression::Subtract(a, b) => subtract(a.derivative(variable)?, b.derivative(variable)?),
+            Expression::Multiply(a, b) => add(
+                multiply(a.derivative(variable)?, *b.clone()),
+                multiply(*a.clone(), b.derivative(variable)?),
+            ),
+            Expression::Divide(a, b) => divide(
+                subtract(
+                    multiply(a.derivative(variable)?, *b.clone()),
+                    multiply(*a.clone(), b.derivative(variable)?),
+                ),
+                power(*b.clone(), Expression::Number(2.0)),
+            ),
+            Expression::Power(base, exponent) if !exponent.depends_on(variable) => multiply(
+                multiply(
+                    *exponent.clone(),
+                    power(*base.clone(), subtract(*exponent.clone(), Expression::Number(1.0))),
+                ),
+                base.derivative(variable)?,
+            ),
+            Expression::Power(base, exponent) if !base.depends_on(variable) => multiply(
+                multiply(self.clone(), call("ln", vec![*base.clone()])),
+                exponent.derivative(variable)?,
+            ),
+            Expression::Power(base, exponent) => multiply(
+                self.clone(),
+                add(
+                    multiply(exponent.derivative(variable)?, call("ln", vec![*base.clone()])),
+                    divide(multiply(*exponent.clone(), base.derivative(variable)?), *base.clone()),
+                ),
+            ),
+            Expression::Negate(expr) => negate(expr.derivative(variable)?),
+            Expression::Grouping(expr) => expr.derivative(variable)?,
+            Expression::Convert(_, _) | Expression::Bitwise(_, _, _) | Expression::BitNot(_) | Expression::Equation(_, _) => {
+                return Err(SymbolicError::NotDifferentiable(self.to_string()));
+            }
+            Expression::Call(name, _) if arity(name).is_some() => self.expand_symbolic()?.derivative(variable)?,
+            Expression::Call(name, args) => match (name.as_str(), args.as_slice()) {
+                ("sin", [u]) => multiply(call("cos", vec![u.clone()]), u.derivative(variable)?),
+                ("cos", [u]) => multiply(negate(call("sin", vec![u.clone()])), u.derivative(variable)?),
+                ("tan", [u]) => divide(
+                    u.derivative(variable)?,
+                    power(call("cos", vec![u.clone()]), Expression::Number(2.0)),
+                ),
+                ("sqrt", [u]) => divide(
+                    u.derivative(variable)?,
+                    multiply(Expression::Number(2.0), call("sqrt", vec![u.clone()])),
+                ),
+                ("ln", [u]) => divide(u.derivative(variable)?, u.clone()),
+                ("abs", [u]) => divide(multiply(u.clone(), u.derivative(variable)?), call("abs", vec![u.clone()])),
+                ("log", [base, u]) => divide(call("ln", vec![u.clone()]), call("ln", vec![base.clone()])).derivative(variable)?,
+                _ => return Err(SymbolicError::NotDifferentiable(self.to_string())),
+            },
+        };
+
+        Ok(derivative)
+    }
+
+    pub fn simplify(&self) -> Expression {
+        let mut current = self.clone();
+        for _ in 0..MAX_SIMPLIFY_PASSES {
+            let next = current.simplify_once();
+            if next == current {
+                break;
+            }
+            current = next;
+        }
+        current
+    }
+
+    fn simplify_once(&self) -> Expression {
+        match self {
+            Expression::Number(_) | Expression::Variable(_) => self.clone(),
+            // folding works on numbers, so literals join in like any other coefficient
+            Expression::Integer(n) => Expression::Number(*n as f64),
+            Expression::Grouping(expr) => expr.simplify_once(),
+            Expression::Add(a, b) => collect_sum(&add(a.simplify_once(), b.simplify_once())),
+            Expression::Subtract(a, b) => collect_sum(&subtract(a.simplify_once(), b.simplify_once())),
+            Expression::Multiply(a, b) => collect_product(&multiply(a.simplify_once(), b.simplify_once())),
+            Expression::Divide(a, b) => simplify_division(a.simplify_once(), b.simplify_once()),
+            Expression::Power(a, b) => simplify_power(a.simplify_once(), b.simplify_once()),
+            Expression::Negate(expr) => match expr.simplify_once() {
+                Expression::Number(n) => Expression::Number(-n),
+                Expression::Negate(inner) => *inner,
+                other => negate(other),
+            },
+            Expression::Call(name, args) => match (name.as_str(), args.as_slice()) {
+                ("ln", [Expression::Variable(base)]) if base == "e" => Expression::Number(1.0),
+                _ => Expression::Call(name.clone(), args.iter().map(Expression::simplify_once).collect()),
+            },
+            Expression::Convert(value, target) => Expression::Convert(Box::new(value.simplify_once()), target.clone()),
+            Expression::Bitwise(op, a, b) => Expression::Bitwise(*op, Box::new(a.simplify_once()), Box::new(b.simplify_once())),
+            Expression::BitNot(expr) => Expression::BitNot(Box::new(expr.simplify_once())),
+            Expression::Equation(left, right) => Expression::Equation(Box::new(left.simplify_once()), Box::new(right.simplify_once())),
+        }
+    }
+}
+
+fn add(a: Expression, b: Expression) -> Expression {
+    Expression::Add(Box::new(a), Box::new(b))
+}
+
+fn subtract(a: Expression, b: Expression) -> Expression {
+    Expression::Subtract(Box::new(a), Box::new(b))
+}
+
+fn multiply(a: Expression, b: Expression) -> Expression {
+    Expression::Multiply(Box::new(a), Box::new(b))
+}
+
+fn divide(a: Expression, b: Expression) -> Expression {
+    Expression::Divide(Box::new(a), Box::new(b))
+}
+
+fn power(a: Expression, b: Expression) -> Expression {
+    Expression::Power(Box::new(a), Box::new(b))
+}
+
+fn negate(a: Expression) -> Expression {
+    Expression::Negate(Box::new(a))
+}
+
+fn call(name: &str, args: Vec<Expression>) -> Expression {
+    Expression::Call(name.to_string(), args)
+}
+
+// Flattens a sum into `coefficient * term` pairs so that like terms can be combined
+fn collect_sum(expr: &Expression) -> Expression {
+    fn gather(expr: &Expression, sign: f64, terms: &mut Vec<(f64, Expression)>, constant: &mut f64) {
+        match expr {
+            Expression::Add(a, b) => {
+                gather(a, sign, terms, constant);
+                gather(b, sign, terms, constant);
+            }
+            Expression::Subtract(a, b) => {
+                gather(a, sign, terms, constant);
+                gather(b, -sign, terms, constant);
+            }
+            Expression::Negate(a) => gather(a, -sign, terms, constant),
+            Expression::Number(n) => *constant += sign * n,
+            // `2 * (x - 1)` contributes `2 * x` and `-2`
+            Expression::Multiply(a, b) if matches!(**a, Expression::Number(_)) => {
+                let Expression::Number(coefficient) = **a else { unreachable!() };
+                gather(b, sign * coefficient, terms, constant);
+            }
+            term => add_term(terms, sign, term),
+        }
+    }
+
+    fn add_term(terms: &mut Vec<(f64, Expression)>, coefficient: f64, term: &Expression) {
+        match terms.iter_mut().find(|(_, existing)| same_term(existing, term)) {
+            Some((existing, _)) => *existing += coefficient,
+            None => terms.push((coefficient, term.clone())),
+        }
+    }
+
+    let mut terms = Vec::new();
+    let mut constant = 0.0;
+    gather(expr, 1.0, &mut terms, &mut constant);
+
+    let mut result: Option<Expression> = None;
+    for (coefficient, term) in terms.into_iter().filter(|(coefficient, _)| *coefficient != 0.0) {
+        result = Some(match result {
+            None => scaled(coefficient, term),
+            Some(sum) if coefficient < 0.0 => subtract(sum, scaled(-coefficient, term)),
+            Some(sum) => add(sum, scaled(coefficient, term)),
+        });
+    }
+
+    match result {
+        None => Expression::Number(constant),
+        Some(sum) if constant == 0.0 => sum,
+        Some(sum) if constant < 0.0 => subtract(sum, Expression::Number(-constant)),
+        Some(sum) => add(sum, Expression::Number(constant)),
+    }
+}
+
+// Terms are alike when they multiply the same factors, in any order
+fn same_term(a: &Expression, b: &Expression) -> bool {
+    fn factors<'e>(expr: &'e Expression, result: &mut Vec<&'e Expression>) {
+        match expr {
+            Expression::Multiply(a, b) => {
+                factors(a, result);
+                factors(b, result);
+            }
+            factor => result.push(factor),
+        }
+    }
+
+    let (mut left, mut right) = (Vec::new(), Vec::new());
+    factors(a, &mut left);
+    factors(b, &mut right);
+
+    left.len() == right.len()
+        && left.iter().all(|factor| {
+            left.iter().filter(|other| other == &factor).count() == right.iter().filter(|other| other == &factor).count()
+        })
+}
+
+fn scaled(coefficient: f64, term: Expression) -> Expression {
+    if coefficient == 1.0 {
+        term
+    } else if coefficient == -1.0 {
+        negate(term)
+    } else {
+        multiply(Expression::Number(coefficient), term)
+    }
+}
+
+// Flattens a product into a numeric coefficient and `base^exponent` factors, merging equal bases
+fn collect_product(expr: &Expression) -> Expression {
+    fn gather(expr: &Expression, coefficient: &mut f64, factors: &mut Vec<(Expression, Expression)>) {
+        match expr {
+            Expression::Multiply(a, b) => {
+                gather(a, coefficient, factors);
+                gather(b, coefficient, factors);
+            }
+            Expression::Number(n) => *coefficient *= n,
+            Expression::Negate(a) => {
+                *coefficient = -*coefficient;
+                gather(a, coefficient, factors);
+            }
+            Expression::Power(base, exponent) => add_factor(factors, base, exponent),
+            factor => add_factor(factors, factor, &Expression::Number(1.0)),
+        }
+    }
+
+    fn add_factor(factors: &mut Vec<(Expression, Expression)>, base: &Expression, exponent: &Expression) {
+        match factors.iter_mut().find(|(existing, _)| existing == base) {
+            Some((_, existing)) => *existing = collect_sum(&add(existing.clone(), exponent.clone())),
+            None => factors.push((base.clone(), exponent.clone())),
+        }
+    }
+
+    let mut coefficient = 1.0;
+    let mut factors = Vec::new();
+    gather(expr, &mut coefficient, &mut factors);
+
+    if coefficient == 0.0 {
+        return Expression::Number(0.0);
+    }
+
+    let product = factors
+        .into_iter()
+        .filter(|(_, exponent)| *exponent != Expression::Number(0.0))
+        .map(|(base, exponent)| simplify_power(base, exponent))
+        .reduce(multiply);
+
+    match product {
+        None => Expression::Number(coefficient),
+        Some(product) => scaled(coefficient, product),
+    }
+}
+
+// Collects the factors of both sides with their exponents so that common factors cancel
+fn simplify_division(a: Expression, b: Expression) -> Expression {
+    struct Factor {
+        base: Expression,
+        numerator: Expression,
+        denominator: Expression,
+    }
+
+    // `scale` is the integer power the factors are raised to, negative in the denominator
+    fn gather(expr: &Expression, scale: i32, coefficients: &mut (f64, f64), factors: &mut Vec<Factor>) {
+        match expr {
+            Expression::Multiply(a, b) => {
+                gather(a, scale, coefficients, factors);
+                gather(b, scale, coefficients, factors);
+            }
+            Expression::Divide(a, b) => {
+                gather(a, scale, coefficients, factors);
+                gather(b, -scale, coefficients, factors);
+            }
+            Expression::Grouping(a) => gather(a, scale, coefficients, factors),
+            Expression::Negate(a) => {
+                if scale % 2 != 0 {
+                    coefficients.0 = -coefficients.0;
+                }
+                gather(a, scale, coefficients, factors);
+            }
+            Expression::Number(n) if scale > 0 => coefficients.0 *= n.powi(scale),
+            Expression::Number(n) => coefficients.1 *= n.powi(-scale),
+            Expression::Power(base, exponent) => match **exponent {
+                Expression::Number(e) if e.fract() == 0.0 && e.abs() <= i32::MAX as f64 => {
+                    match base.as_ref() {
+                        Expression::Multiply(_, _) | Expression::Divide(_, _) | Expression::Negate(_) | Expression::Number(_) => {
+                            gather(base, scale * e as i32, coefficients, factors)
+                        }
+                        _ => add_factor(factors, base, Expression::Number(e * scale as f64)),
+                    }
+                }
+                _ => add_factor(factors, base, scaled(scale as f64, *exponent.clone())),
+            },
+            factor => add_factor(factors, factor, Expression::Number(scale as f64)),
+        }
+    }
+
+    fn add_factor(factors: &mut Vec<Factor>, base: &Expression, exponent: Expression) {
+        let index = match factors.iter().position(|factor| factor.base == *base) {
+            Some(index) => index,
+            None => {
+                factors.push(Factor {
+                    base: base.clone(),
+                    numerator: Expression::Number(0.0),
+                    denominator: Expression::Number(0.0),
+                });
+                factors.len() - 1
+            }
+        };
+        let factor = &mut factors[index];
+        let (side, exponent) = match exponent {
+            Expression::Number(e) if e < 0.0 => (&mut factor.denominator, Expression::Number(-e)),
+            Expression::Negate(e) => (&mut factor.denominator, *e),
+            Expression::Multiply(c, e) if matches!(*c, Expression::Number(c) if c < 0.0) => {
+                let Expression::Number(c) = *c else { unreachable!() };
+                (&mut factor.denominator, scaled(-c, *e))
+            }
+            exponent => (&mut factor.numerator, exponent),
+        };
+        *side = collect_sum(&add(side.clone(), exponent));
+    }
+
+    if a == b && b != Expression::Number(0.0) {
+        return Expression::Number(1.0);
+    }
+
+    let mut coefficients = (1.0, 1.0);
+    let mut factors = Vec::new();
+    gather(&a, 1, &mut coefficients, &mut factors);
+    gather(&b, -1, &mut coefficients, &mut factors);
+
+    let (mut numerator, mut denominator) = coefficients;
+    if denominator == 0.0 || !numerator.is_finite() || !denominator.is_finite() {
+        return divide(a, b);
+    }
+    if numerator == 0.0 {
+        return Expression::Number(0.0);
+    }
+    if denominator < 0.0 {
+        (numerator, denominator) = (-numerator, -denominator);
+    }
+
+    let (mut above, mut below) = (Vec::new(), Vec::new());
+    for Factor { base, numerator: up, denominator: down } in factors {
+        match (&up, &down) {
+            (Expression::Number(up), Expression::Number(down)) if up > down => above.push((base, Expression::Number(up - down))),
+            (Expression::Number(up), Expression::Number(down)) if up < down => below.push((base, Expression::Number(down - up))),
+            _ if up == down => {}
+            _ => {
+                if up != Expression::Number(0.0) {
+                    above.push((base.clone(), up));
+                }
+                if down != Expression::Number(0.0) {
+                    below.push((base, down));
+                }
+            }
+        }
+    }
+
+    let symbolic = !above.is_empty() || !below.is_empty();
+    if (numerator / denominator).fract() == 0.0 {
+        (numerator, denominator) = (numerator / denominator, 1.0);
+    } else if symbolic && (denominator / numerator).fract() == 0.0 {
+        (numerator, denominator) = (numerator.signum(), denominator / numerator.abs());
+    } else if is_terminating_quotient(numerator, denominator) {
+        (numerator, denominator) = (numerator / denominator, 1.0);
+    }
+
+    let product = |factors: Vec<(Expression, Expression)>| {
+        factors.into_iter().map(|(base, exponent)| simplify_power(base, exponent)).reduce(multiply)
+    };
+    let numerator = match product(above) {
+        None => Expression::Number(numerator),
+        Some(product) => scaled(numerator, product),
+    };
+    match product(below) {
+        None if denominator == 1.0 => numerator,
+        None => divide(numerator, Expression::Number(denominator)),
+        Some(product) => divide(numerator, scaled(denominator, product)),
+    }
+}
+
+// Folding `1 / 3` would replace an exact expression with a rounded number
+fn is_terminating_quotient(n: f64, d: f64) -> bool {
+    let backend = RationalBackend;
+    match (backend.number(n), backend.number(d)) {
+        (Ok(n), Ok(d)) => backend.divide(&n, &d).is_ok_and(|quotient| quotient.is_terminating_decimal()),
+        _ => false,
+    }
+}
+
+fn simplify_power(base: Expression, exponent: Expression) -> Expression {
+    match (&base, &exponent) {
+        (_, Expression::Number(e)) if *e == 0.0 => Expression::Number(1.0),
+        (_, Expression::Number(e)) if *e == 1.0 => base,
+        (Expression::Number(b), _) if *b == 1.0 => base,
+        (Expression::Number(b), Expression::Number(e)) if e.fract() == 0.0 && b.powf(*e).is_finite() => Expression::Number(b.powf(*e)),
+        (Expression::Power(inner, e1), Expression::Number(e2)) if matches!(**e1, Expression::Number(_)) => {
+            let Expression::Number(e1) = **e1 else { unreachable!() };
+            simplify_power(*inner.clone(), Expression::Number(e1 * e2))
+        }
+        _ => power(base, exponent),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::parsemath::parser::Parser;
+    use crate::parsemath::symbolic::SymbolicError;
+    use rstest::rstest;
+
+    fn expand(expression: &str) -> Result<String, SymbolicError> {
+        let ast = Parser::new(expression).unwrap().parse().unwrap();
+        ast.expand_symbolic().map(|result| result.to_string())
+    }
+
+    #[rstest]
+    #[case::constant("diff(5, x)", "0")]
+    #[case::linear("diff(3 * x + 1, x)", "3")]
+    #[case::square("diff(x^2, x)", "2 * x")]
+    #[case::polynomial("diff(x^3 + 2 * x, x)", "3 * x^2 + 2")]
+    #[case::product("diff(sin(x) * x, x)", "cos(x) * x + sin(x)")]
+    #[case::quotient("diff(1 / x, x)", "-1 / x^2")]
+    #[case::chain("diff(sin(x^2), x)", "2 * cos(x^2) * x")]
+    #[case::ln("diff(ln(x), x)", "1 / x")]
+    #[case::exponential("diff(2^x, x)", "2^x * ln(2)")]
+    #[case::other_variable("diff(x * y + y, y)", "x + 1")]
+    #[case::nested("diff(diff(x^3, x), x)", "6 * x")]
+    #[case::named_constant("diff(pi * x, x)", "pi")]
+    #[case::difference_of_squares("diff(x^2 - (x - 1)^2, x)", "2")]
+    #[case::natural_exponential("diff(e^x, x)", "e^x")]
+    #[case::logarithm("diff(log(2, x), x)", "1 / (x * ln(2))")]
+    fn differentiate(#[case] expression: &str, #[case] expected: &str) {
+        assert_eq!(expand(expression), Ok(expected.to_string()));
+    }
+
+    #[rstest]
+    #[case::add_zero("simplify(x + 0)", "x")]
+    #[case::multiply_one("simplify(1 * x * 1)", "x")]
+    #[case::multiply_zero("simplify(x * 0 + y)", "y")]
+    #[case::constant_folding("simplify(2 * 3 + 4 - 1)", "9")]
+    #[case::like_terms("simplify(x + 2 * x - y + 3 * y)", "3 * x + 2 * y")]
+    #[case::cancelling_terms("simplify(x - x)", "0")]
+    #[case::powers("simplify(x * x^2 * 2)", "2 * x^3")]
+    #[case::double_negation("simplify(--x)", "x")]
+    #[case::division("simplify((x + 1) / (x + 1) + 6 / 4)", "2.5")]
+    #[case::inexact_division_is_kept("simplify(1 / 3)", "1 / 3")]
+    #[case::distributed_coefficient("simplify(2 * x - 2 * (x - 1))", "2")]
+    #[case::ln_e("simplify(e^x * ln(e))", "e^x")]
+    #[case::common_factors("simplify(2 * y / (2 * y)^2)", "1 / (2 * y)")]
+    #[case::nested_quotient("simplify(1 / x * ln(2) / ln(2)^2)", "1 / (x * ln(2))")]
+    #[case::coefficient_kept_below("simplify(3 * x / 6)", "x / 2")]
+    fn simplify(#[case] expression: &str, #[case] expected: &str) {
+        assert_eq!(expand(expression), Ok(expected.to_string()));
+    }
+
+    #[rstest]
+    #[case::subtract_group("simplify(x - (y + 1))", "x - y - 1")]
+    #[case::negate_group("simplify(-(x + y) * z)", "-(x + y) * z")]
+    #[case::divide_group("simplify(x / (y * z))", "x / (y * z)")]
+    #[case::power_base("simplify((x + 1)^2)", "(x + 1)^2")]
+    #[case::power_exponent("simplify(2^(x + 1))", "2^(x + 1)")]
+    #[case::right_assoc_power("simplify(x^y^z)", "x^y^z")]
+    fn print_with_minimal_parentheses(#[case] expression: &str, #[case] expected: &str) {
+        assert_eq!(expand(expression), Ok(expected.to_string()));
+    }
+
+    #[test]
+    fn non_differentiable_functions_are_reported() {
+        assert_eq!(expand("diff(max(x, 1), x)"), Err(SymbolicError::NotDifferentiable("max(x, 1)".to_string())));
+    }
+}
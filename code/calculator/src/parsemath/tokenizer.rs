@@ -1,22 +1,46 @@
-use crate::parsemath::token::Token;
+use crate::parsemath::token::{Span, Token};
 use std::iter::Peekable;
 use std::str::Chars;
 use thiserror::Error;
 
 pub struct Tokenizer<'a> {
     expr: Peekable<Chars<'a>>,
+    position: usize,
+    span: Span,
+    exact_integers: bool,
 }
 
 impl<'a> Tokenizer<'a> {
     pub fn new(expr: &str) -> Tokenizer<'_> {
         Tokenizer {
             expr: expr.chars().peekable(),
+            position: 0,
+            span: Span::new(0, 0),
+            exact_integers: false,
         }
     }
 
+    /// Lexes decimal literals without a fraction as 64-bit integers, for integer arithmetic
+    /// that must not round them.
+    pub fn with_exact_integers(mut self) -> Self {
+        self.exact_integers = true;
+        self
+    }
+
+    /// Byte range of the token (or invalid input) most recently returned by `next`.
+    pub fn span(&self) -> Span {
+        self.span
+    }
+
+    fn advance(&mut self) -> Option<char> {
+        let c = self.expr.next()?;
+        self.position += c.len_utf8();
+        Some(c)
+    }
+
     fn skip_whitespaces(&mut self) {
         while let Some(' ') = self.expr.peek() {
-            self.expr.next();
+            self.advance();
         }
     }
 }
@@ -27,6 +51,8 @@ pub enum TokenizingError {
     InvalidCharacter(char),
     #[error("Invalid number format")]
     InvalidNumber,
+    #[error("Integer literal does not fit in 64 bits")]
+    IntegerOverflow,
 }
 
 impl<'a> Iterator for Tokenizer<'a> {
@@ -35,32 +61,79 @@ impl<'a> Iterator for Tokenizer<'a> {
     fn next(&mut self) -> Option<Self::Item> {
         self.skip_whitespaces();
 
-        if let Some(c) = self.expr.next() {
+        let start = self.position;
+        let token = if let Some(c) = self.advance() {
             match c {
                 '+' => Some(Ok(Token::Plus)),
+                '-' if self.expr.peek() == Some(&'>') => {
+                    self.advance();
+                    Some(Ok(Token::Arrow))
+                }
                 '-' => Some(Ok(Token::Minus)),
                 '*' => Some(Ok(Token::Star)),
                 '/' => Some(Ok(Token::Slash)),
                 '^' => Some(Ok(Token::Caret)),
                 '(' => Some(Ok(Token::LeftParen)),
                 ')' => Some(Ok(Token::RightParen)),
+                ',' => Some(Ok(Token::Comma)),
+                '=' => Some(Ok(Token::Equals)),
+                '&' => Some(Ok(Token::Ampersand)),
+                '|' => Some(Ok(Token::Pipe)),
+                '~' => Some(Ok(Token::Tilde)),
+                '<' | '>' if self.expr.peek() == Some(&c) => {
+                    self.advance();
+                    Some(Ok(if c == '<' { Token::ShiftLeft } else { Token::ShiftRight }))
+                }
+                '0' if matches!(self.expr.peek(), Some('x' | 'b' | 'o')) => {
+                    let radix = match self.advance() {
+                        Some('x') => 16,
+                        Some('b') => 2,
+                        _ => 8,
+                    };
+                    let mut digits = String::new();
+                    while let Some(c) = self.expr.peek().filter(|c| c.is_ascii_alphanumeric() || **c == '_') {
+                        digits.push(*c);
+                        self.advance();
+                    }
+
+                    // the literal is a 64-bit pattern, so 0xFFFFFFFFFFFFFFFF is -1
+                    let number = u64::from_str_radix(&digits.replace('_', ""), radix)
+                        .map(|value| Token::Integer(value as i64))
+                        .map_err(|_| TokenizingError::InvalidNumber);
+
+                    Some(number)
+                }
                 '0'..='9' => {
                     let mut number_str = c.to_string();
-                    while let Some('0'..='9') | Some('.') = self.expr.peek() {
-                        number_str.push(self.expr.next().unwrap());
+                    while let Some('0'..='9') | Some('.') | Some('_') = self.expr.peek() {
+                        number_str.push(self.advance().unwrap());
                     }
 
-                    let number = number_str.parse::<f64>()
-                        .map(|n| Token::Number(n))
-                        .map_err(|_| TokenizingError::InvalidNumber);
+                    let digits = number_str.replace('_', "");
+                    let number = if self.exact_integers && !digits.contains('.') {
+                        digits.parse::<i64>().map(Token::Integer).map_err(|_| TokenizingError::IntegerOverflow)
+                    } else {
+                        digits.parse::<f64>().map(Token::Number).map_err(|_| TokenizingError::InvalidNumber)
+                    };
 
                     Some(number)
                 }
+                'a'..='z' | 'A'..='Z' | '_' => {
+                    let mut name = c.to_string();
+                    while let Some('a'..='z') | Some('A'..='Z') | Some('0'..='9') | Some('_') = self.expr.peek() {
+                        name.push(self.advance().unwrap());
+                    }
+
+                    Some(Ok(Token::Identifier(name)))
+                }
                 invalid => Some(Err(TokenizingError::InvalidCharacter(invalid))),
             }
         } else {
             None
-        }
+        };
+
+        self.span = Span::new(start, self.position);
+        token
     }
 }
 
@@ -74,6 +147,11 @@ mod tests {
     #[case("*", vec![Token::Star])]
     #[case("/", vec![Token::Slash])]
     #[case("^", vec![Token::Caret])]
+    #[case("&", vec![Token::Ampersand])]
+    #[case("|", vec![Token::Pipe])]
+    #[case("~", vec![Token::Tilde])]
+    #[case("<<", vec![Token::ShiftLeft])]
+    #[case(">>", vec![Token::ShiftRight])]
     fn tokenizer_operators(#[case] expr: &str, #[case] expected_tokens: Vec<Token>) {
         let tokenizer = Tokenizer::new(expr);
 
@@ -84,6 +162,14 @@ mod tests {
     #[rstest]
     #[case("3", vec![Token::Number(3.0)])]
     #[case("3.14", vec![Token::Number(3.14)])]
+    #[case("1_000_000", vec![Token::Number(1_000_000.0)])]
+    #[case("0xFF", vec![Token::Integer(255)])]
+    #[case("0b1010", vec![Token::Integer(10)])]
+    #[case("0o17", vec![Token::Integer(15)])]
+    #[case("0xffff_ffff", vec![Token::Integer(0xffff_ffff)])]
+    #[case("0xFFFFFFFFFFFFFFFF", vec![Token::Integer(-1)])]
+    #[case("0", vec![Token::Number(0.0)])]
+    #[allow(clippy::approx_constant)]
     fn tokenizer_numbers(#[case] expr: &str, #[case] expected_tokens: Vec<Token>) {
         let tokenizer = Tokenizer::new(expr);
 
@@ -94,6 +180,9 @@ mod tests {
     #[rstest]
     #[case("(", vec![Token::LeftParen])]
     #[case(")", vec![Token::RightParen])]
+    #[case(",", vec![Token::Comma])]
+    #[case("=", vec![Token::Equals])]
+    #[case("->", vec![Token::Arrow])]
     fn tokenizer_parens(#[case] expr: &str, #[case] expected_tokens: Vec<Token>) {
         let tokenizer = Tokenizer::new(expr);
 
@@ -101,9 +190,36 @@ mod tests {
         assert_eq!(tokens, expected_tokens);
     }
 
+    #[rstest]
+    #[case("pi", vec![Token::Identifier("pi".to_string())])]
+    #[case("log10", vec![Token::Identifier("log10".to_string())])]
+    #[case("_x1", vec![Token::Identifier("_x1".to_string())])]
+    fn tokenizer_identifiers(#[case] expr: &str, #[case] expected_tokens: Vec<Token>) {
+        let tokenizer = Tokenizer::new(expr);
+
+        let tokens = tokenizer.collect::<Result<Vec<Token>, TokenizingError>>().unwrap();
+        assert_eq!(tokens, expected_tokens);
+    }
+
     #[rstest]
     #[case("1+2", vec![Token::Number(1.0), Token::Plus, Token::Number(2.0)])]
     #[case("1 + 2", vec![Token::Number(1.0), Token::Plus, Token::Number(2.0)])]
+    #[case("max(1, x)", vec![
+        Token::Identifier("max".to_string()),
+        Token::LeftParen,
+        Token::Number(1.0),
+        Token::Comma,
+        Token::Identifier("x".to_string()),
+        Token::RightParen
+    ])]
+    #[case("3 m", vec![Token::Number(3.0), Token::Identifier("m".to_string())])]
+    #[case("5kg->lb", vec![
+        Token::Number(5.0),
+        Token::Identifier("kg".to_string()),
+        Token::Arrow,
+        Token::Identifier("lb".to_string())
+    ])]
+    #[case("1--2", vec![Token::Number(1.0), Token::Minus, Token::Minus, Token::Number(2.0)])]
     fn tokenizer_expressions(#[case] expr: &str, #[case] expected_tokens: Vec<Token>) {
         let tokenizer = Tokenizer::new(expr);
 
@@ -112,8 +228,9 @@ mod tests {
     }
 
     #[rstest]
-    #[case("1a", 'a')]
+    #[case("1$", '$')]
     #[case("2#", '#')]
+    #[case("1 < 2", '<')]
     fn tokenizer_invalid_characters(#[case] expr: &str, #[case] expected: char) {
         let tokenizer = Tokenizer::new(expr);
 
@@ -133,10 +250,56 @@ mod tests {
         assert_eq!(format!("{}", result), "Unexpected token \'#\'");
     }
 
+    #[test]
+    fn tokenizer_tracks_byte_offsets() {
+        let mut tokenizer = Tokenizer::new("12 +  sqrt(π)");
+
+        let mut spans = Vec::new();
+        while let Some(result) = tokenizer.next() {
+            spans.push((result, tokenizer.span()));
+        }
+
+        assert_eq!(spans, vec![
+            (Ok(Token::Number(12.0)), Span::new(0, 2)),
+            (Ok(Token::Plus), Span::new(3, 4)),
+            (Ok(Token::Identifier("sqrt".to_string())), Span::new(6, 10)),
+            (Ok(Token::LeftParen), Span::new(10, 11)),
+            (Err(TokenizingError::InvalidCharacter('π')), Span::new(11, 13)),
+            (Ok(Token::RightParen), Span::new(13, 14)),
+        ]);
+    }
+
+    #[rstest]
+    #[case("3", vec![Token::Integer(3)])]
+    #[case("1_000_000", vec![Token::Integer(1_000_000)])]
+    #[case("2.0", vec![Token::Number(2.0)])]
+    #[case("9223372036854775807", vec![Token::Integer(i64::MAX)])]
+    #[case("12345678901234567890.5", vec![Token::Number(12345678901234567890.5)])]
+    fn tokenizer_exact_integers(#[case] expr: &str, #[case] expected_tokens: Vec<Token>) {
+        let tokenizer = Tokenizer::new(expr).with_exact_integers();
+
+        let tokens: Vec<Token> = tokenizer.collect::<Result<Vec<Token>, TokenizingError>>().unwrap();
+        assert_eq!(tokens, expected_tokens);
+    }
+
+    #[rstest]
+    #[case("9223372036854775808")]
+    #[case("1_0000_0000_0000_0000_0000")]
+    fn tokenizer_exact_integer_overflow(#[case] expr: &str) {
+        let tokenizer = Tokenizer::new(expr).with_exact_integers();
+
+        let result = tokenizer.collect::<Result<Vec<Token>, TokenizingError>>().unwrap_err();
+        assert_eq!(result, TokenizingError::IntegerOverflow);
+        assert_eq!(format!("{}", result), "Integer literal does not fit in 64 bits");
+    }
+
     #[rstest]
     #[case("1.324.3")]
     #[case("1....")]
     #[case("1 + 3.33.3.3")]
+    #[case("0x")]
+    #[case("0b102")]
+    #[case("0x1_0000_0000_0000_0000")]
     fn tokenizer_invalid_number(#[case] expr: &str) {
         let tokenizer = Tokenizer::new(expr);
 
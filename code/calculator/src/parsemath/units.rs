@@ -0,0 +1,452 @@
+use crate::parsemath::ast::Expression;
+use crate::parsemath::functions::FunctionRegistry;
+use crate::parsemath::numeric::{Backend, BitwiseOp, NumericError};
+use crate::parsemath::parser::Parser;
+use std::fmt;
+
+// exponents of length, mass and time
+pub type Dimension = [i32; 3];
+
+const DIMENSIONLESS: Dimension = [0, 0, 0];
+const LENGTH: Dimension = [1, 0, 0];
+const MASS: Dimension = [0, 1, 0];
+const TIME: Dimension = [0, 0, 1];
+const VOLUME: Dimension = [3, 0, 0];
+const FORCE: Dimension = [1, 1, -2];
+const ENERGY: Dimension = [2, 1, -2];
+
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub struct Unit {
+    pub name: &'static str,
+    // numerator and denominator, kept apart so that every backend can build the factor exactly
+    pub factor: (i64, i64),
+    pub dimension: Dimension,
+}
+
+const fn unit(name: &'static str, numerator: i64, denominator: i64, dimension: Dimension) -> Unit {
+    Unit { name, factor: (numerator, denominator), dimension }
+}
+
+// factors convert to the SI base unit of the dimension
+const UNITS: &[Unit] = &[
+    unit("mm", 1, 1_000, LENGTH),
+    unit("cm", 1, 100, LENGTH),
+    unit("m", 1, 1, LENGTH),
+    unit("km", 1_000, 1, LENGTH),
+    unit("in", 254, 10_000, LENGTH),
+    unit("ft", 3_048, 10_000, LENGTH),
+    unit("yd", 9_144, 10_000, LENGTH),
+    unit("mi", 1_609_344, 1_000, LENGTH),
+    unit("g", 1, 1_000, MASS),
+    unit("kg", 1, 1, MASS),
+    unit("oz", 28_349_523_125, 1_000_000_000_000, MASS),
+    unit("lb", 45_359_237, 100_000_000, MASS),
+    unit("ms", 1, 1_000, TIME),
+    unit("s", 1, 1, TIME),
+    unit("min", 60, 1, TIME),
+    unit("h", 3_600, 1, TIME),
+    unit("day", 86_400, 1, TIME),
+    unit("ml", 1, 1_000_000, VOLUME),
+    unit("l", 1, 1_000, VOLUME),
+    unit("N", 1, 1, FORCE),
+    unit("J", 1, 1, ENERGY),
+];
+
+pub fn lookup(name: &str) -> Option<&'static Unit> {
+    UNITS.iter().find(|unit| unit.name == name)
+}
+
+pub fn is_unit(name: &str) -> bool {
+    lookup(name).is_some()
+}
+
+#[derive(Debug, PartialEq, Clone)]
+pub struct Quantity<V> {
+    pub magnitude: V,
+    pub units: Vec<(&'static str, i32)>,
+}
+
+impl<V> Quantity<V> {
+    pub fn dimension(&self) -> Dimension {
+        let mut dimension = DIMENSIONLESS;
+        for (name, power) in &self.units {
+            let unit = lookup(name).expect("quantities only hold known units");
+            for (total, exponent) in dimension.iter_mut().zip(unit.dimension) {
+                *total += exponent * power;
+            }
+        }
+        dimension
+    }
+
+    pub fn is_dimensionless(&self) -> bool {
+        self.dimension() == DIMENSIONLESS
+    }
+
+    fn describe(&self) -> String {
+        if self.units.is_empty() {
+            "dimensionless".to_string()
+        } else {
+            format_units(&self.units)
+        }
+    }
+}
+
+impl<V: fmt::Display> Quantity<V> {
+    // a literal that evaluates back to this quantity, used to remember assigned values
+    pub fn to_expression(&self) -> Expression {
+        let literal = |text: &str| {
+            Parser::with_exact_integers(&fit_integer_literals(text), FunctionRegistry::builtins())
+                .and_then(|mut parser| parser.parse())
+                .expect("quantities format as valid expressions")
+        };
+
+        let magnitude = literal(&self.magnitude.to_string());
+        if self.units.is_empty() {
+            return magnitude;
+        }
+        Expression::Multiply(
+            Box::new(Expression::Grouping(Box::new(magnitude))),
+            Box::new(Expression::Grouping(Box::new(literal(&format_units(&self.units))))),
+        )
+    }
+}
+
+impl<V: fmt::Display> fmt::Display for Quantity<V> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if self.units.is_empty() {
+            write!(f, "{}", self.magnitude)
+        } else {
+            write!(f, "{} {}", self.magnitude, format_units(&self.units))
+        }
+    }
+}
+
+// Integer literals must fit in an i64, which the digits of a large rational or float don't:
+// those are spelled as floats, and the smallest i64, whose magnitude has no literal, as a difference
+fn fit_integer_literals(text: &str) -> String {
+    let mut spelled = String::new();
+    let mut rest = text;
+    while let Some(start) = rest.find(|c: char| c.is_ascii_digit()) {
+        let end = rest[start..].find(|c: char| !c.is_ascii_digit()).map_or(rest.len(), |length| start + length);
+        let (before, digits, after) = (&rest[..start], &rest[start..end], &rest[end..]);
+        let standalone = !before.ends_with(|c: char| c.is_ascii_alphanumeric() || c == '.') && !after.starts_with('.');
+
+        if !standalone || digits.parse::<i64>().is_ok() {
+            spelled.push_str(&rest[..end]);
+        } else if spelled.is_empty() && before == "-" && let Ok(value) = format!("-{}", digits).parse::<i64>() {
+            spelled.push_str(&format!("({} - 1)", value + 1));
+        } else {
+            spelled.push_str(&format!("{}{}.0", before, digits));
+        }
+        rest = after;
+    }
+    spelled.push_str(rest);
+    spelled
+}
+
+fn format_units(units: &[(&str, i32)]) -> String {
+    let format = |name: &str, power: i32| match power {
+        1 => name.to_string(),
+        _ => format!("{}^{}", name, power),
+    };
+    let numerator = units
+        .iter()
+        .filter(|(_, power)| *power > 0)
+        .map(|(name, power)| format(name, *power))
+        .collect::<Vec<_>>();
+    let denominator = units
+        .iter()
+        .filter(|(_, power)| *power < 0)
+        .map(|(name, power)| format(name, -power))
+        .collect::<Vec<_>>();
+
+    let numerator = if numerator.is_empty() { "1".to_string() } else { numerator.join("*") };
+    match denominator.len() {
+        0 => numerator,
+        1 => format!("{}/{}", numerator, denominator[0]),
+        _ => format!("{}/({})", numerator, denominator.join("*")),
+    }
+}
+
+fn merge_units(a: &[(&'static str, i32)], b: &[(&'static str, i32)], sign: i32) -> Vec<(&'static str, i32)> {
+    let mut units = a.to_vec();
+    for (name, power) in b {
+        match units.iter_mut().find(|(existing, _)| existing == name) {
+            Some((_, existing)) => *existing += sign * power,
+            None => units.push((name, sign * power)),
+        }
+    }
+    units.retain(|(_, power)| *power != 0);
+    units
+}
+
+// Wraps a numeric backend so that values carry the units they were written in
+pub struct UnitBackend<B> {
+    inner: B,
+}
+
+impl<B: Backend> UnitBackend<B> {
+    pub fn new(inner: B) -> Self {
+        UnitBackend { inner }
+    }
+
+    fn plain(&self, magnitude: B::Value) -> Quantity<B::Value> {
+        Quantity { magnitude, units: Vec::new() }
+    }
+
+    // numerator and denominator of the product of the unit factors raised to the powers of `units`,
+    // built from integers so that fixed-point backends don't round factors like 1/1000 to zero
+    fn factor(&self, units: &[(&str, i32)]) -> Result<(B::Value, B::Value), NumericError> {
+        let mut numerator = self.inner.integer(1)?;
+        let mut denominator = self.inner.integer(1)?;
+        for (name, power) in units {
+            let unit = lookup(name).expect("quantities only hold known units");
+            let (mut above, mut below) = unit.factor;
+            if *power < 0 {
+                (above, below) = (below, above);
+            }
+            let exponent = self.inner.integer(power.abs() as i64)?;
+            numerator = self.inner.multiply(&numerator, &self.inner.power(&self.inner.integer(above)?, &exponent)?)?;
+            denominator = self.inner.multiply(&denominator, &self.inner.power(&self.inner.integer(below)?, &exponent)?)?;
+        }
+        Ok((numerator, denominator))
+    }
+
+    // magnitude of `value` once expressed in `units` of the same dimension; reciprocals are never
+    // formed so that fixed-point backends don't round factors like 1/3600 away
+    fn magnitude_in(&self, value: &Quantity<B::Value>, units: &[(&str, i32)]) -> Result<B::Value, NumericError> {
+        if value.units == units {
+            return Ok(value.magnitude.clone());
+        }
+        let (from_numerator, from_denominator) = self.factor(&value.units)?;
+        let (to_numerator, to_denominator) = self.factor(units)?;
+        let numerator = self.inner.multiply(&from_numerator, &to_denominator)?;
+        let denominator = self.inner.multiply(&from_denominator, &to_numerator)?;
+        let scaled = self.inner.multiply(&value.magnitude, &numerator)?;
+        self.inner.divide(&scaled, &denominator)
+    }
+
+    fn dimensionless(&self, value: &Quantity<B::Value>) -> Result<B::Value, NumericError> {
+        if !value.is_dimensionless() {
+            return Err(NumericError::DomainError(format!("expected a dimensionless value, got {}", value.describe())));
+        }
+        self.magnitude_in(value, &[])
+    }
+
+    fn check_compatible(&self, a: &Quantity<B::Value>, b: &Quantity<B::Value>) -> Result<(), NumericError> {
+        if a.dimension() != b.dimension() {
+            return Err(NumericError::IncompatibleUnits(a.describe(), b.describe()));
+        }
+        Ok(())
+    }
+
+    // units that cancel out completely (like km/m) are folded into the magnitude
+    fn with_units(&self, magnitude: B::Value, units: Vec<(&'static str, i32)>) -> Result<Quantity<B::Value>, NumericError> {
+        let quantity = Quantity { magnitude, units };
+        if quantity.is_dimensionless() && !quantity.units.is_empty() {
+            return Ok(self.plain(self.magnitude_in(&quantity, &[])?));
+        }
+        Ok(quantity)
+    }
+}
+
+impl<B: Backend> Backend for UnitBackend<B> {
+    type Value = Quantity<B::Value>;
+
+    fn number(&self, value: f64) -> Result<Self::Value, NumericError> {
+        Ok(self.plain(self.inner.number(value)?))
+    }
+
+    fn integer(&self, value: i64) -> Result<Self::Value, NumericError> {
+        Ok(self.plain(self.inner.integer(value)?))
+    }
+
+    fn to_f64(&self, value: &Self::Value) -> Result<f64, NumericError> {
+        self.inner.to_f64(&self.dimensionless(value)?)
+    }
+
+    fn add(&self, a: &Self::Value, b: &Self::Value) -> Result<Self::Value, NumericError> {
+        self.check_compatible(a, b)?;
+        let magnitude = self.inner.add(&a.magnitude, &self.magnitude_in(b, &a.units)?)?;
+        Ok(Quantity { magnitude, units: a.units.clone() })
+    }
+
+    fn subtract(&self, a: &Self::Value, b: &Self::Value) -> Result<Self::Value, NumericError> {
+        self.check_compatible(a, b)?;
+        let magnitude = self.inner.subtract(&a.magnitude, &self.magnitude_in(b, &a.units)?)?;
+        Ok(Quantity { magnitude, units: a.units.clone() })
+    }
+
+    fn multiply(&self, a: &Self::Value, b: &Self::Value) -> Result<Self::Value, NumericError> {
+        let magnitude = self.inner.multiply(&a.magnitude, &b.magnitude)?;
+        self.with_units(magnitude, merge_units(&a.units, &b.units, 1))
+    }
+
+    fn divide(&self, a: &Self::Value, b: &Self::Value) -> Result<Self::Value, NumericError> {
+        let magnitude = self.inner.divide(&a.magnitude, &b.magnitude)?;
+        self.with_units(magnitude, merge_units(&a.units, &b.units, -1))
+    }
+
+    fn power(&self, a: &Self::Value, b: &Self::Value) -> Result<Self::Value, NumericError> {
+        if !b.is_dimensionless() {
+            return Err(NumericError::DomainError(format!("exponent must be dimensionless, got {}", b.describe())));
+        }
+        let exponent = self.magnitude_in(b, &[])?;
+        if a.is_dimensionless() {
+            return Ok(self.plain(self.inner.power(&self.magnitude_in(a, &[])?, &exponent)?));
+        }
+
+        let integer = self.inner.to_f64(&exponent)?;
+        if integer.fract() != 0.0 || integer.abs() > i32::MAX as f64 {
+            return Err(NumericError::DomainError(format!("cannot raise {} to a non-integer power", a.describe())));
+        }
+        let units = a.units.iter().map(|(name, power)| (*name, power * integer as i32)).collect();
+        self.with_units(self.inner.power(&a.magnitude, &exponent)?, units)
+    }
+
+    fn negate(&self, a: &Self::Value) -> Result<Self::Value, NumericError> {
+        Ok(Quantity { magnitude: self.inner.negate(&a.magnitude)?, units: a.units.clone() })
+    }
+
+    fn bitwise(&self, op: BitwiseOp, a: &Self::Value, b: &Self::Value) -> Result<Self::Value, NumericError> {
+        let magnitude = self.inner.bitwise(op, &self.dimensionless(a)?, &self.dimensionless(b)?)?;
+        Ok(self.plain(magnitude))
+    }
+
+    fn bit_not(&self, a: &Self::Value) -> Result<Self::Value, NumericError> {
+        Ok(self.plain(self.inner.bit_not(&self.dimensionless(a)?)?))
+    }
+
+    fn imaginary_unit(&self) -> Result<Self::Value, NumericError> {
+        Ok(self.plain(self.inner.imaginary_unit()?))
+    }
+
+    // quantities with a dimension fall back to the f64 path, which reports them
+    fn call(&self, name: &str, args: &[Self::Value]) -> Option<Result<Self::Value, NumericError>> {
+        let magnitudes = args.iter().map(|arg| self.dimensionless(arg)).collect::<Result<Vec<_>, _>>().ok()?;
+        let result = self.inner.call(name, &magnitudes)?;
+        Some(result.map(|magnitude| self.plain(magnitude)))
+    }
+
+    fn unit(&self, name: &str) -> Option<Result<Self::Value, NumericError>> {
+        let unit = lookup(name)?;
+        Some(self.inner.number(1.0).map(|magnitude| Quantity { magnitude, units: vec![(unit.name, 1)] }))
+    }
+
+    fn convert(&self, value: &Self::Value, target: &Self::Value) -> Result<Self::Value, NumericError> {
+        self.check_compatible(value, target)?;
+        let magnitude = self.inner.divide(&self.magnitude_in(value, &target.units)?, &target.magnitude)?;
+        Ok(Quantity { magnitude, units: target.units.clone() })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parsemath::ast::EvalError;
+    use crate::parsemath::functions::FunctionRegistry;
+    use crate::parsemath::numeric::{ComplexBackend, DecimalBackend, FloatBackend, IntegerBackend, OutputBase, RationalBackend};
+    use crate::parsemath::parser::Parser;
+    use rstest::rstest;
+
+    fn evaluate<B: Backend>(backend: B, expression: &str) -> Result<String, EvalError> {
+        let ast = Parser::new(expression).unwrap().parse().unwrap();
+        ast.evaluate_in(&UnitBackend::new(backend), FunctionRegistry::builtins())
+            .map(|value| value.to_string())
+    }
+
+    #[rstest]
+    #[case::sum_of_lengths("3 m + 20 cm", "3.2 m")]
+    #[case::speed_times_time("60 km/h * 2 h", "120 km")]
+    #[case::area("2 m * 3 m", "6 m^2")]
+    #[case::squared_unit("4 m^2 / 2 s", "2 m^2/s")]
+    #[case::acceleration("10 m / 2 s^2", "5 m/s^2")]
+    #[case::cancelled_units("3 km / 1 m", "3000")]
+    #[case::plain_numbers("1 + 2 * 3", "7")]
+    #[case::function_of_ratio("sqrt(8 m / 2 m)", "2")]
+    #[case::convert_length("1 mi -> km", "1.609344 km")]
+    #[case::convert_speed("36 km/h -> m/s", "10 m/s")]
+    #[case::convert_volume("1 m^3 -> l", "1000 l")]
+    #[case::convert_force("2 kg * 3 m/s^2 -> N", "6 N")]
+    #[case::negated("-(5 min) -> s", "-300 s")]
+    #[case::small_unit("1 mm + 1 m", "1001 mm")]
+    fn units_float(#[case] expression: &str, #[case] expected: &str) {
+        assert_eq!(evaluate(FloatBackend, expression), Ok(expected.to_string()));
+    }
+
+    #[rstest]
+    #[case::pounds("5 kg -> lb", "500000000/45359237 lb")]
+    #[case::inches("1 ft -> in", "12 in")]
+    #[case::mixed("1 h + 30 min", "1.5 h")]
+    #[case::speed("36 km/h -> m/s", "10 m/s")]
+    #[case::small_unit("1 mm + 1 m", "1001 mm")]
+    fn units_rational(#[case] expression: &str, #[case] expected: &str) {
+        assert_eq!(evaluate(RationalBackend, expression), Ok(expected.to_string()));
+    }
+
+    #[rstest]
+    #[case::small_unit("1 mm + 1 m", "1001 mm")]
+    #[case::to_smaller_unit("10 kg -> g", "10000 g")]
+    #[case::millilitres("250 ml -> l", "0.25 l")]
+    #[case::rounded("1 mm -> m", "0 m")]
+    fn units_decimal(#[case] expression: &str, #[case] expected: &str) {
+        assert_eq!(evaluate(DecimalBackend::new(2), expression), Ok(expected.to_string()));
+    }
+
+    #[rstest]
+    #[case::small_unit("1 mm + 1 m", "1001 mm")]
+    #[case::to_smaller_unit("10 kg -> g", "10000 g")]
+    #[case::pounds("1 lb -> oz", "16 oz")]
+    fn units_complex(#[case] expression: &str, #[case] expected: &str) {
+        assert_eq!(evaluate(ComplexBackend, expression), Ok(expected.to_string()));
+    }
+
+    #[rstest]
+    #[case::small_unit("1 mm + 1 m", "1001 mm")]
+    #[case::to_smaller_unit("10 kg -> g", "10000 g")]
+    #[case::truncated("1500 mm -> m", "1 m")]
+    fn units_programmer(#[case] expression: &str, #[case] expected: &str) {
+        assert_eq!(evaluate(IntegerBackend::new(OutputBase::Dec), expression), Ok(expected.to_string()));
+    }
+
+    #[rstest]
+    #[case::plain("2 * 3")]
+    #[case::fraction("1 / 3")]
+    #[case::pounds("5 kg -> lb")]
+    #[case::speed("-36 km/h")]
+    fn quantities_convert_to_equivalent_literals(#[case] expression: &str) {
+        let backend = UnitBackend::new(RationalBackend);
+        let ast = Parser::new(expression).unwrap().parse().unwrap();
+        let value = ast.evaluate_in(&backend, FunctionRegistry::builtins()).unwrap();
+
+        let literal = value.to_expression().evaluate_in(&backend, FunctionRegistry::builtins());
+
+        assert_eq!(literal, Ok(value));
+    }
+
+    #[rstest]
+    #[case::largest("9223372036854775807")]
+    #[case::smallest("-9223372036854775807 - 1")]
+    #[case::smallest_length("(-9223372036854775807 - 1) * m")]
+    fn integer_quantities_convert_to_equivalent_literals(#[case] expression: &str) {
+        let backend = UnitBackend::new(IntegerBackend::new(OutputBase::Dec));
+        let ast = Parser::with_exact_integers(expression, FunctionRegistry::builtins()).unwrap().parse().unwrap();
+        let value = ast.evaluate_in(&backend, FunctionRegistry::builtins()).unwrap();
+
+        let literal = value.to_expression().evaluate_in(&backend, FunctionRegistry::builtins());
+
+        assert_eq!(literal, Ok(value));
+    }
+
+    #[rstest]
+    #[case::add_length_and_time("1 m + 1 s", "Incompatible units in '1 * m + 1 * s': m and s")]
+    #[case::add_length_and_number("1 m + 1", "Incompatible units in '1 * m + 1': m and dimensionless")]
+    #[case::convert_mass_to_length("5 kg -> m", "Incompatible units in '5 * kg -> m': kg and m")]
+    #[case::function_argument("sin(1 m)", "Domain error in 'sin(1 * m)': expected a dimensionless value, got m")]
+    #[case::fractional_power("(4 m)^0.5", "Domain error in '(4 * m)^0.5': cannot raise m to a non-integer power")]
+    fn units_report_incompatible_dimensions(#[case] expression: &str, #[case] message: &str) {
+        let error = evaluate(FloatBackend, expression).unwrap_err();
+
+        assert_eq!(error.to_string(), message);
+    }
+}
@@ -2,60 +2,258 @@ use std::cell::RefCell;
 use std::collections::HashMap;
 use std::rc::Rc;
 
+use crate::arguments::Signature;
+use crate::buffers::Buffers;
 use crate::commands::Command;
 use crate::console::Console;
+use crate::macros::Macros;
+
+/// The most command lines a single macro playback runs.
+const MAX_PLAYBACK_LINES: usize = 10_000;
 
 pub struct Application {
-    //document: Rc<RefCell<&'a mut Document>>,
+    buffers: Option<Rc<RefCell<Buffers>>>,
     console: Rc<RefCell<dyn Console>>,
     commands: HashMap<String, Rc<RefCell<dyn Command>>>,
+    macros: Macros,
+    confirm_exit: bool,
 }
 
 impl Application {
     pub fn run(&mut self) {
+        // unsaved changes are only discarded by an Exit that directly follows the warning
+        let mut exit_confirmed = false;
         loop {
             self.console
                 .as_ref()
                 .borrow_mut()
-                .print_line("Enter a command: ");
+                .prompt("Enter a command: ");
 
             let line = self.console.as_ref().borrow_mut().read_line();
-            let tokens: Vec<_> = line.splitn(2, ' ').collect();
-            let command_name = tokens[0].to_string();
+            let command_name = line.split_whitespace().next().unwrap_or_default().to_string();
+
+            if command_name != "Close" {
+                if let Some(buffers) = &self.buffers {
+                    buffers.borrow_mut().forget_close_warning();
+                }
+            }
 
             if command_name == "Exit" {
-                break;
+                let modified = self.modified_buffers();
+                if exit_confirmed || !self.confirm_exit || modified.is_empty() {
+                    break;
+                }
+                let changed = match self.buffers.as_ref().map(|buffers| buffers.borrow().all().len()) {
+                    Some(1) => "The document has".to_string(),
+                    _ => format!("Buffers {} have", modified.join(", ")),
+                };
+                self.console.as_ref().borrow_mut().print_line(&format!(
+                    "{} unsaved changes. Save them, or enter Exit again to discard them.",
+                    changed
+                ));
+                exit_confirmed = true;
+                continue;
+            }
+            exit_confirmed = false;
+
+            match command_name.as_str() {
+                "Help" => self.print_help(),
+                "Record" | "Stop" | "Play" => self.execute_macro_command(&command_name, &line),
+                _ => {
+                    if self.execute_line(&command_name, &line) {
+                        self.macros.add_line(&line);
+                    }
+                }
             }
+        }
+    }
+
+    /// Parses and executes a command line, returning whether it could be parsed.
+    fn execute_line(&self, command_name: &str, line: &str) -> bool {
+        let Some(cmd) = self.commands.get(command_name) else {
+            self.print_error(&format!("Unknown command: {}", command_name));
+            return false;
+        };
 
-            if let Some(cmd) = self.commands.get(&command_name) {
-                let _ = cmd.as_ref().borrow_mut().parse(&line);
+        let parsed = cmd.as_ref().borrow_mut().parse(line);
+        match parsed {
+            Ok(()) => {
                 cmd.as_ref().borrow_mut().execute();
-            } else {
-                self.console
-                    .as_ref()
-                    .borrow_mut()
-                    .print_line(&format!("Unknown command: {}", command_name));
+                true
+            }
+            Err(error) => {
+                self.print_error(&error.message);
+                false
             }
         }
     }
 
+    fn execute_macro_command(&mut self, command_name: &str, line: &str) {
+        let signature = Macros::signatures()
+            .into_iter()
+            .find(|signature| signature.name == command_name)
+            .expect("every macro command has a signature");
+        let arguments = match signature.parse(line) {
+            Ok(arguments) => arguments,
+            Err(error) => return self.print_error(&error.message),
+        };
+
+        let name = arguments.text("name").unwrap_or_default();
+        match command_name {
+            "Record" => match self.macros.record(name) {
+                Ok(()) => self.print_line(&format!(
+                    "Recording macro '{}', enter Stop to finish",
+                    name
+                )),
+                Err(message) => self.print_error(&message),
+            },
+            "Stop" => self.stop_recording(),
+            _ => self.play_macro(name, arguments.count("times").unwrap_or(1)),
+        }
+    }
+
+    fn stop_recording(&mut self) {
+        let message = match self.macros.stop() {
+            Ok((name, lines)) => format!("Recorded macro '{}' with {} commands", name, lines.len()),
+            Err(message) => return self.print_error(&message),
+        };
+        self.print_line(&message);
+
+        if let Err(error) = self.macros.save() {
+            let path = self.macros.path().map(|path| path.display().to_string());
+            self.print_error(&format!(
+                "Cannot save macros to {}: {}",
+                path.unwrap_or_default(),
+                error
+            ));
+        }
+    }
+
+    /// Executes the lines of a macro, grouped so that a single Undo reverts the whole playback.
+    fn play_macro(&mut self, name: &str, times: usize) {
+        let Some(lines) = self.macros.get(name).map(<[String]>::to_vec) else {
+            return self.print_error(&format!("Unknown macro '{}'", name));
+        };
+        let Some(count) = lines
+            .len()
+            .checked_mul(times)
+            .filter(|count| *count <= MAX_PLAYBACK_LINES)
+        else {
+            return self.print_error(&format!(
+                "Cannot play macro '{}' {} times, a playback runs at most {} commands",
+                name, times, MAX_PLAYBACK_LINES
+            ));
+        };
+
+        // the playback is grouped in the history of the buffer it started in
+        let history = self
+            .buffers
+            .as_ref()
+            .map(|buffers| buffers.borrow().active().command_history.clone());
+        if let Some(history) = &history {
+            history.borrow_mut().begin_group();
+        }
+        for line in lines.iter().cycle().take(count) {
+            let command_name = line.split_whitespace().next().unwrap_or_default();
+            self.execute_line(command_name, line);
+        }
+        if let Some(history) = &history {
+            history.borrow_mut().end_group();
+        }
+    }
+
+    fn print_line(&self, line: &str) {
+        self.console.as_ref().borrow_mut().print_line(line);
+    }
+
+    fn print_error(&self, message: &str) {
+        self.console.as_ref().borrow_mut().print_error(message);
+    }
+
     pub fn add_command(&mut self, command_name: String, command: Rc<RefCell<dyn Command>>) {
         self.commands.insert(command_name, command);
     }
+
+    /// Whether a command is known, added or handled by the application itself.
+    pub fn has_command(&self, command_name: &str) -> bool {
+        self.commands.contains_key(command_name)
+            || ["Help", "Exit"].contains(&command_name)
+            || Macros::signatures().iter().any(|signature| signature.name == command_name)
+    }
+
+    /// Lists every command with its usage, generated from the command signatures.
+    fn print_help(&self) {
+        let mut signatures: Vec<Signature> = self
+            .commands
+            .values()
+            .map(|cmd| cmd.as_ref().borrow().signature())
+            .collect();
+        signatures.extend(Macros::signatures());
+        signatures.sort_by_key(|signature| signature.name);
+        signatures.push(Signature::new("Help", "Lists the commands"));
+        signatures.push(Signature::new("Exit", "Quits the editor"));
+
+        let usages: Vec<String> = signatures.iter().map(Signature::usage).collect();
+        let width = usages.iter().map(|usage| usage.len()).max().unwrap_or_default();
+
+        let mut console = self.console.as_ref().borrow_mut();
+        console.print_line("Commands:");
+        for (usage, signature) in usages.iter().zip(&signatures) {
+            console.print_line(&format!("  {:width$}  {}", usage, signature.summary));
+        }
+    }
+
+    /// The names of the buffers with unsaved changes.
+    fn modified_buffers(&self) -> Vec<String> {
+        let Some(buffers) = &self.buffers else {
+            return Vec::new();
+        };
+        buffers
+            .borrow()
+            .all()
+            .iter()
+            .filter(|buffer| buffer.document.borrow().is_modified())
+            .map(|buffer| buffer.name.clone())
+            .collect()
+    }
 }
 
 pub struct ApplicationBuilder {
+    buffers: Option<Rc<RefCell<Buffers>>>,
     console: Option<Rc<RefCell<dyn Console>>>,
+    macros: Option<Macros>,
+    confirm_exit: bool,
 }
 
 impl ApplicationBuilder {
     pub fn new() -> ApplicationBuilder {
         ApplicationBuilder {
-            //document: None,
+            buffers: None,
             console: None,
+            macros: None,
+            confirm_exit: true,
         }
     }
 
+    /// Macros recorded earlier; without them macros only last for the session.
+    pub fn with_macros(mut self, macros: Macros) -> ApplicationBuilder {
+        self.macros = Some(macros);
+        self
+    }
+
+    /// The buffers whose unsaved changes are guarded on Exit, and in whose active history
+    /// a macro playback is grouped into a single change.
+    pub fn with_buffers(mut self, buffers: Rc<RefCell<Buffers>>) -> ApplicationBuilder {
+        self.buffers = Some(buffers);
+        self
+    }
+
+    /// Whether Exit asks before discarding unsaved changes; a script has no one to ask.
+    pub fn with_exit_confirmation(mut self, confirm_exit: bool) -> ApplicationBuilder {
+        self.confirm_exit = confirm_exit;
+        self
+    }
+
     pub fn with_console(mut self, console: Rc<RefCell<dyn Console>>) -> ApplicationBuilder {
         self.console = Some(console.clone());
         self
@@ -63,9 +261,11 @@ impl ApplicationBuilder {
 
     pub fn build(self) -> Application {
         Application {
-            //document: self.document.unwrap(),
+            buffers: self.buffers,
             console: self.console.unwrap(),
             commands: HashMap::new(),
+            macros: self.macros.unwrap_or_default(),
+            confirm_exit: self.confirm_exit,
         }
     }
 }
@@ -73,8 +273,14 @@ impl ApplicationBuilder {
 #[cfg(test)]
 pub(crate) mod tests_application {
     use crate::application::ApplicationBuilder;
-    use crate::commands::{MockCommand};    
-    use crate::console::{MockConsole, tests_console::mock_console};
+    use crate::arguments::{Parameter, ParameterKind, Signature};
+    use crate::commands::{
+        AddTextCommand, CommandParseError, DeleteCommand, MockCommand, UndoCommand,
+    };
+    use crate::console::{MockConsole, ScriptConsole, tests_console::mock_console};
+    use crate::buffers::{Buffers, CloseCommand};
+    use crate::macros::Macros;
+    use crate::storage::tests_storage::TempFile;
     use mockall::{Sequence, predicate::eq};
     use rstest::rstest;
     use std::{cell::RefCell, rc::Rc};
@@ -114,7 +320,7 @@ pub(crate) mod tests_application {
                 .returning(|| "Exit".to_string())
                 .in_sequence(&mut seq);
     
-            console.expect_print_line().times(1..).returning(|_| ());
+            console.expect_print_error().times(1).returning(|_| ());
         }
     
         let mut app = ApplicationBuilder::new()
@@ -185,7 +391,7 @@ pub(crate) mod tests_application {
                 .in_sequence(&mut seq);
     
             console
-                .expect_print_line()
+                .expect_print_error()
                 .withf(|line| line.contains("Unknown command: Unknown"))
                 .times(1)
                 .returning(|_| ());
@@ -241,6 +447,439 @@ pub(crate) mod tests_application {
     
         app.run();
     }
-    
-}
 
+    #[rstest]
+    fn run_exit_with_unsaved_changes_warns_and_needs_a_second_exit(
+        mock_console: Rc<RefCell<MockConsole>>,
+    ) {
+        {
+            let mut console = mock_console.as_ref().borrow_mut();
+            let mut seq = Sequence::new();
+
+            console
+                .expect_read_line()
+                .times(1)
+                .returning(|| "Exit".to_string())
+                .in_sequence(&mut seq);
+
+            console
+                .expect_read_line()
+                .times(1)
+                .returning(|| "Cmd".to_string())
+                .in_sequence(&mut seq);
+
+            console
+                .expect_read_line()
+                .times(2)
+                .returning(|| "Exit".to_string())
+                .in_sequence(&mut seq);
+
+            console
+                .expect_print_line()
+                .withf(|line| line.contains("unsaved changes"))
+                .times(2)
+                .returning(|_| ());
+        }
+
+        let buffers = Rc::new(RefCell::new(Buffers::new(10)));
+        buffers.borrow().active().document.borrow_mut().add_line("Line1".to_string());
+
+        let mut app = ApplicationBuilder::new()
+            .with_console(mock_console.clone())
+            .with_buffers(buffers)
+            .build();
+
+        {
+            let mock_cmd_rc: Rc<RefCell<MockCommand>> = Rc::new(RefCell::new(MockCommand::new()));
+            let mut mock_cmd = mock_cmd_rc.as_ref().borrow_mut();
+
+            mock_cmd.expect_parse().returning(|_| Ok(()));
+            mock_cmd.expect_execute().times(1).returning(|| ());
+
+            app.add_command("Cmd".to_string(), mock_cmd_rc.clone());
+        }
+
+        app.run();
+    }
+
+    #[rstest]
+    fn run_exit_without_confirmation_discards_unsaved_changes(
+        mock_console: Rc<RefCell<MockConsole>>,
+    ) {
+        {
+            let mut console = mock_console.as_ref().borrow_mut();
+            console
+                .expect_read_line()
+                .times(1)
+                .returning(|| "Exit".to_string());
+            console.expect_print_line().never();
+        }
+
+        let buffers = Rc::new(RefCell::new(Buffers::new(10)));
+        buffers.borrow().active().document.borrow_mut().add_line("Line1".to_string());
+
+        let mut app = ApplicationBuilder::new()
+            .with_console(mock_console.clone())
+            .with_buffers(buffers)
+            .with_exit_confirmation(false)
+            .build();
+
+        app.run();
+    }
+
+    #[rstest]
+    fn run_exit_without_changes_exits_at_once(mock_console: Rc<RefCell<MockConsole>>) {
+        mock_console
+            .as_ref()
+            .borrow_mut()
+            .expect_read_line()
+            .times(1)
+            .returning(|| "Exit".to_string());
+
+        let mut app = ApplicationBuilder::new()
+            .with_console(mock_console.clone())
+            .with_buffers(Rc::new(RefCell::new(Buffers::new(10))))
+            .build();
+
+        app.run();
+    }
+
+    #[rstest]
+    fn run_exit_names_the_buffers_with_unsaved_changes(mock_console: Rc<RefCell<MockConsole>>) {
+        run_lines(&mock_console, &["Exit"]);
+        mock_console
+            .as_ref()
+            .borrow_mut()
+            .expect_print_line()
+            .with(eq("Buffers main, todo have unsaved changes. Save them, or enter Exit again to discard them."))
+            .times(1)
+            .returning(|_| ());
+
+        let buffers = Rc::new(RefCell::new(Buffers::new(10)));
+        for name in ["notes", "todo"] {
+            buffers.borrow_mut().create(name).unwrap();
+        }
+        for name in ["main", "todo"] {
+            buffers.borrow_mut().switch(name).unwrap();
+            buffers.borrow().active().document.borrow_mut().add_line("Line1".to_string());
+        }
+
+        let mut app = ApplicationBuilder::new()
+            .with_console(mock_console.clone())
+            .with_buffers(buffers)
+            .build();
+
+        app.run();
+    }
+
+    #[rstest]
+    fn run_close_warns_again_after_another_command(mock_console: Rc<RefCell<MockConsole>>) {
+        run_lines(&mock_console, &["Close notes", "AddText more", "Close notes", "Close notes"]);
+        {
+            let mut console = mock_console.as_ref().borrow_mut();
+            console
+                .expect_print_error()
+                .with(eq("Buffer 'notes' has unsaved changes. Save them, or enter Close again to discard them."))
+                .times(2)
+                .returning(|_| ());
+            console
+                .expect_print_line()
+                .with(eq("Closed buffer 'notes', the active buffer is 'main'"))
+                .times(1)
+                .returning(|_| ());
+        }
+
+        let buffers = Rc::new(RefCell::new(Buffers::new(10)));
+        buffers.borrow_mut().create("notes").unwrap();
+        buffers.borrow().active().document.borrow_mut().add_line("Line1".to_string());
+
+        let mut app = ApplicationBuilder::new()
+            .with_console(mock_console.clone())
+            .with_buffers(buffers.clone())
+            .build();
+        app.add_command(
+            "AddText".to_string(),
+            Rc::new(RefCell::new(AddTextCommand::new(
+                Buffers::current_document(&buffers),
+                Buffers::current_history(&buffers),
+            ))),
+        );
+        app.add_command(
+            "Close".to_string(),
+            Rc::new(RefCell::new(CloseCommand::new(buffers.clone(), mock_console.clone()))),
+        );
+
+        app.run();
+
+        assert_eq!(buffers.borrow().all().len(), 1);
+    }
+
+    #[rstest]
+    fn run_prints_parse_errors_instead_of_executing(mock_console: Rc<RefCell<MockConsole>>) {
+        {
+            let mut console = mock_console.as_ref().borrow_mut();
+            let mut seq = Sequence::new();
+
+            console
+                .expect_read_line()
+                .times(1)
+                .returning(|| "Cmd".to_string())
+                .in_sequence(&mut seq);
+
+            console
+                .expect_read_line()
+                .times(1)
+                .returning(|| "Exit".to_string())
+                .in_sequence(&mut seq);
+
+            console
+                .expect_print_error()
+                .with(eq("Missing <arg>. Usage: Cmd <arg>"))
+                .times(1)
+                .returning(|_| ());
+        }
+
+        let mut app = ApplicationBuilder::new()
+            .with_console(mock_console.clone())
+            .build();
+
+        {
+            let mock_cmd_rc: Rc<RefCell<MockCommand>> = Rc::new(RefCell::new(MockCommand::new()));
+            let mut mock_cmd = mock_cmd_rc.as_ref().borrow_mut();
+
+            mock_cmd.expect_parse().times(1).returning(|_| {
+                Err(CommandParseError {
+                    message: "Missing <arg>. Usage: Cmd <arg>".to_string(),
+                })
+            });
+            mock_cmd.expect_execute().never();
+
+            app.add_command("Cmd".to_string(), mock_cmd_rc.clone());
+        }
+
+        app.run();
+    }
+
+    #[rstest]
+    fn run_help_lists_command_signatures(mock_console: Rc<RefCell<MockConsole>>) {
+        {
+            let mut console = mock_console.as_ref().borrow_mut();
+            let mut seq = Sequence::new();
+
+            console
+                .expect_read_line()
+                .times(1)
+                .returning(|| "Help".to_string())
+                .in_sequence(&mut seq);
+
+            console
+                .expect_read_line()
+                .times(1)
+                .returning(|| "Exit".to_string())
+                .in_sequence(&mut seq);
+
+            let mut output = Sequence::new();
+            for line in [
+                "Commands:",
+                "  Cmd <line> [text...]  Does something",
+                "  Play <name> [times]   Plays a macro, undone as a single change",
+                "  Record <name>         Records the following commands as a macro",
+                "  Stop                  Stops recording and saves the macro",
+                "  Help                  Lists the commands",
+                "  Exit                  Quits the editor",
+            ] {
+                console
+                    .expect_print_line()
+                    .with(eq(line))
+                    .times(1)
+                    .in_sequence(&mut output)
+                    .returning(|_| ());
+            }
+        }
+
+        let mut app = ApplicationBuilder::new()
+            .with_console(mock_console.clone())
+            .build();
+
+        {
+            let mock_cmd_rc: Rc<RefCell<MockCommand>> = Rc::new(RefCell::new(MockCommand::new()));
+            let mut mock_cmd = mock_cmd_rc.as_ref().borrow_mut();
+
+            mock_cmd.expect_signature().returning(|| {
+                Signature::new("Cmd", "Does something")
+                    .with(Parameter::required("line", ParameterKind::Line))
+                    .with(Parameter::optional("text", ParameterKind::Text))
+            });
+
+            app.add_command("Cmd".to_string(), mock_cmd_rc.clone());
+        }
+
+        app.run();
+    }
+
+    fn run_lines(console: &Rc<RefCell<MockConsole>>, lines: &[&'static str]) {
+        let mut console = console.as_ref().borrow_mut();
+        let mut seq = Sequence::new();
+        for &line in lines.iter().chain(&["Exit"]) {
+            console
+                .expect_read_line()
+                .times(1)
+                .returning(|| line.to_string())
+                .in_sequence(&mut seq);
+        }
+    }
+
+    #[rstest]
+    fn run_plays_recorded_macro_as_single_change(mock_console: Rc<RefCell<MockConsole>>) {
+        run_lines(
+            &mock_console,
+            &["Record greet", "AddText Hello", "Stop", "Play greet 2", "Undo", "Exit"],
+        );
+        {
+            let mut console = mock_console.as_ref().borrow_mut();
+            for line in [
+                "Recording macro 'greet', enter Stop to finish",
+                "Recorded macro 'greet' with 1 commands",
+            ] {
+                console.expect_print_line().with(eq(line)).times(1).returning(|_| ());
+            }
+            console
+                .expect_print_line()
+                .withf(|line| line.contains("unsaved changes"))
+                .times(1)
+                .returning(|_| ());
+        }
+
+        let buffers = Rc::new(RefCell::new(Buffers::new(10)));
+        let document = buffers.borrow().active().document.clone();
+        let command_history = buffers.borrow().active().command_history.clone();
+        let mut app = ApplicationBuilder::new()
+            .with_console(mock_console.clone())
+            .with_buffers(buffers)
+            .build();
+        app.add_command(
+            "AddText".to_string(),
+            Rc::new(RefCell::new(AddTextCommand::new(
+                document.clone(),
+                command_history.clone(),
+            ))),
+        );
+        app.add_command(
+            "Undo".to_string(),
+            Rc::new(RefCell::new(UndoCommand::new(command_history.clone()))),
+        );
+
+        app.run();
+
+        assert_eq!(document.borrow().content(), &["Hello"]);
+        assert_eq!(command_history.borrow().commands.len(), 1);
+    }
+
+    #[rstest]
+    #[case("Play missing", "Unknown macro 'missing'")]
+    #[case("Stop", "Not recording a macro")]
+    #[case("Record", "Missing <name>. Usage: Record <name>")]
+    fn run_reports_macro_misuse(
+        mock_console: Rc<RefCell<MockConsole>>,
+        #[case] line: &'static str,
+        #[case] message: &'static str,
+    ) {
+        run_lines(&mock_console, &[line]);
+        mock_console
+            .as_ref()
+            .borrow_mut()
+            .expect_print_error()
+            .with(eq(message))
+            .times(1)
+            .returning(|_| ());
+
+        let mut app = ApplicationBuilder::new()
+            .with_console(mock_console.clone())
+            .build();
+
+        app.run();
+    }
+
+    #[rstest]
+    fn run_rejects_playback_running_too_many_commands(mock_console: Rc<RefCell<MockConsole>>) {
+        let file = TempFile::new("playback.json");
+        std::fs::write(&file.0, r#"{"twice": ["Cmd", "Cmd"]}"#).unwrap();
+        run_lines(&mock_console, &["Play twice 18446744073709551615", "Play twice 5001"]);
+        for times in ["18446744073709551615", "5001"] {
+            mock_console
+                .as_ref()
+                .borrow_mut()
+                .expect_print_error()
+                .with(eq(format!(
+                    "Cannot play macro 'twice' {} times, a playback runs at most 10000 commands",
+                    times
+                )))
+                .times(1)
+                .returning(|_| ());
+        }
+
+        let mut app = ApplicationBuilder::new()
+            .with_console(mock_console.clone())
+            .with_macros(Macros::load(file.0.clone()).unwrap())
+            .build();
+
+        app.run();
+    }
+
+    #[test]
+    fn run_script_stops_at_first_failure() {
+        let script = "AddText one\nDelete 5\nAddText two\n";
+        let console = Rc::new(RefCell::new(ScriptConsole::new(
+            "edit.cmds",
+            script.as_bytes(),
+            std::io::sink(),
+        )));
+        let buffers = Rc::new(RefCell::new(Buffers::new(10)));
+        let document = buffers.borrow().active().document.clone();
+        let command_history = buffers.borrow().active().command_history.clone();
+
+        let mut app = ApplicationBuilder::new()
+            .with_console(console.clone())
+            .with_buffers(buffers)
+            .build();
+        app.add_command(
+            "AddText".to_string(),
+            Rc::new(RefCell::new(AddTextCommand::new(
+                document.clone(),
+                command_history.clone(),
+            ))),
+        );
+        app.add_command(
+            "Delete".to_string(),
+            Rc::new(RefCell::new(DeleteCommand::new(
+                document.clone(),
+                command_history.clone(),
+                console.clone(),
+            ))),
+        );
+
+        app.run();
+
+        assert_eq!(document.borrow().content(), &["one"]);
+        assert_eq!(
+            console.borrow().errors(),
+            ["edit.cmds:2: Line 5 does not exist, the document has 1 lines"]
+        );
+    }
+
+    #[test]
+    fn has_command_knows_added_and_own_commands() {
+        let mut app = ApplicationBuilder::new()
+            .with_console(Rc::new(RefCell::new(MockConsole::new())))
+            .build();
+        app.add_command(
+            "Cmd".to_string(),
+            Rc::new(RefCell::new(MockCommand::new())),
+        );
+
+        for command_name in ["Cmd", "Help", "Exit", "Record", "Play"] {
+            assert!(app.has_command(command_name), "{}", command_name);
+        }
+        assert!(!app.has_command("Other"));
+    }
+}
@@ -0,0 +1,542 @@
+use std::collections::HashMap;
+use std::ops::Range;
+
+use crate::commands::CommandParseError;
+
+/// The kinds of values a command accepts as arguments.
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub enum ParameterKind {
+    /// A single word; quote it to include spaces.
+    Word,
+    /// The rest of the line as typed, unquoted only when it is a single quoted string.
+    Text,
+    /// A line number, counted from 1.
+    Line,
+    /// A line number or an inclusive range of line numbers such as `3..7`.
+    Range,
+    /// A character position within a line, counted from 1.
+    Column,
+    /// A number of times, at least 1.
+    Count,
+    /// A regular expression between slashes, such as `/\d+/`.
+    Pattern,
+    /// A word made of the given flag letters, such as `gi`.
+    Flags(&'static str),
+}
+
+#[derive(Debug, PartialEq, Clone)]
+pub struct Parameter {
+    pub name: &'static str,
+    pub kind: ParameterKind,
+    pub required: bool,
+}
+
+impl Parameter {
+    pub fn required(name: &'static str, kind: ParameterKind) -> Parameter {
+        Parameter { name, kind, required: true }
+    }
+
+    pub fn optional(name: &'static str, kind: ParameterKind) -> Parameter {
+        Parameter { name, kind, required: false }
+    }
+
+    fn usage(&self) -> String {
+        let name = match self.kind {
+            ParameterKind::Text => format!("{}...", self.name),
+            ParameterKind::Pattern => format!("/{}/", self.name),
+            ParameterKind::Flags(flags) => flags.chars().map(String::from).collect::<Vec<_>>().join("|"),
+            _ => self.name.to_string(),
+        };
+        match self.required {
+            true => format!("<{}>", name),
+            false => format!("[{}]", name),
+        }
+    }
+}
+
+/// The arguments a command accepts, used to parse command lines and to generate help.
+#[derive(Debug, PartialEq, Clone)]
+pub struct Signature {
+    pub name: &'static str,
+    pub summary: &'static str,
+    pub parameters: Vec<Parameter>,
+}
+
+impl Signature {
+    pub fn new(name: &'static str, summary: &'static str) -> Signature {
+        Signature {
+            name,
+            summary,
+            parameters: Vec::new(),
+        }
+    }
+
+    pub fn with(mut self, parameter: Parameter) -> Signature {
+        self.parameters.push(parameter);
+        self
+    }
+
+    pub fn usage(&self) -> String {
+        self.parameters
+            .iter()
+            .fold(self.name.to_string(), |usage, parameter| {
+                format!("{} {}", usage, parameter.usage())
+            })
+    }
+
+    /// Parses a whole command line, including the command name, against the parameters.
+    /// An optional parameter is left out when the next word does not suit it but suits
+    /// a later parameter, as in `Replace /a/ b g` without a range.
+    pub fn parse(&self, command: &str) -> Result<Arguments, CommandParseError> {
+        let split = |rest| split_word(rest).map_err(|problem| self.error(&problem));
+        let (_, mut rest) = split(command)?.unwrap_or_default();
+
+        let mut arguments = Arguments::default();
+        for (position, parameter) in self.parameters.iter().enumerate() {
+            let argument = match parameter.kind {
+                ParameterKind::Text => {
+                    let text = std::mem::take(&mut rest).trim_start();
+                    (!text.is_empty()).then(|| Argument::Text(unquote(text)))
+                }
+                // a word that cannot be split may still be the start of a later text
+                kind => match split_word(rest) {
+                    Ok(Some((word, after))) => match parse_argument(kind, &word) {
+                        Ok(argument) => {
+                            rest = after;
+                            Some(argument)
+                        }
+                        Err(_) if !parameter.required && self.suits_later(position, &word) => None,
+                        Err(problem) => return Err(self.error(&problem)),
+                    },
+                    Ok(None) => None,
+                    Err(_) if !parameter.required => None,
+                    Err(problem) => return Err(self.error(&problem)),
+                },
+            };
+
+            match argument {
+                Some(argument) => {
+                    arguments.values.insert(parameter.name, argument);
+                }
+                None if parameter.required => {
+                    return Err(self.error(&format!("Missing {}", parameter.usage())));
+                }
+                None => {}
+            }
+        }
+
+        match split(rest)? {
+            Some((word, _)) => Err(self.error(&format!("Unexpected argument '{}'", word))),
+            None => Ok(arguments),
+        }
+    }
+
+    /// Whether a parameter after `position`, other than a catch-all text, accepts `word`.
+    fn suits_later(&self, position: usize, word: &str) -> bool {
+        self.parameters[position + 1..].iter().any(|parameter| {
+            parameter.kind != ParameterKind::Text && parse_argument(parameter.kind, word).is_ok()
+        })
+    }
+
+    fn error(&self, problem: &str) -> CommandParseError {
+        CommandParseError {
+            message: format!("{}. Usage: {}", problem, self.usage()),
+        }
+    }
+}
+
+#[derive(Debug, PartialEq, Clone)]
+pub enum Argument {
+    Text(String),
+    /// A zero-based line index.
+    Line(usize),
+    /// Zero-based line indexes, the end excluded.
+    Range(Range<usize>),
+    /// A zero-based character index.
+    Column(usize),
+    Count(usize),
+}
+
+/// Parsed arguments by parameter name. Optional parameters that were left out are missing.
+#[derive(Debug, PartialEq, Default)]
+pub struct Arguments {
+    values: HashMap<&'static str, Argument>,
+}
+
+impl Arguments {
+    pub fn text(&self, name: &str) -> Option<&str> {
+        match self.values.get(name) {
+            Some(Argument::Text(text)) => Some(text),
+            _ => None,
+        }
+    }
+
+    pub fn line(&self, name: &str) -> Option<usize> {
+        match self.values.get(name) {
+            Some(Argument::Line(line)) => Some(*line),
+            _ => None,
+        }
+    }
+
+    pub fn range(&self, name: &str) -> Option<Range<usize>> {
+        match self.values.get(name) {
+            Some(Argument::Range(range)) => Some(range.clone()),
+            _ => None,
+        }
+    }
+
+    pub fn column(&self, name: &str) -> Option<usize> {
+        match self.values.get(name) {
+            Some(Argument::Column(column)) => Some(*column),
+            _ => None,
+        }
+    }
+
+    pub fn count(&self, name: &str) -> Option<usize> {
+        match self.values.get(name) {
+            Some(Argument::Count(count)) => Some(*count),
+            _ => None,
+        }
+    }
+}
+
+fn parse_argument(kind: ParameterKind, word: &str) -> Result<Argument, String> {
+    match kind {
+        ParameterKind::Word | ParameterKind::Text => Ok(Argument::Text(word.to_string())),
+        ParameterKind::Line => parse_line(word).map(Argument::Line),
+        ParameterKind::Range => match word.split_once("..") {
+            Some((first, last)) => {
+                let (first, last) = (parse_line(first)?, parse_line(last)?);
+                if first > last {
+                    return Err(format!("'{}' is an empty range", word));
+                }
+                Ok(Argument::Range(first..last + 1))
+            }
+            None => parse_line(word).map(|line| Argument::Range(line..line + 1)),
+        },
+        ParameterKind::Column => match word.parse::<usize>() {
+            Ok(0) => Err("Columns start at 1".to_string()),
+            Ok(column) => Ok(Argument::Column(column - 1)),
+            Err(_) => Err(format!("'{}' is not a column", word)),
+        },
+        ParameterKind::Count => match word.parse::<usize>() {
+            Ok(count) if count > 0 => Ok(Argument::Count(count)),
+            _ => Err(format!("'{}' is not a positive number", word)),
+        },
+        ParameterKind::Pattern => match word.strip_prefix('/').and_then(|word| word.strip_suffix('/')) {
+            Some(pattern) => Ok(Argument::Text(pattern.to_string())),
+            None => Err(format!("'{}' is not a /pattern/", word)),
+        },
+        ParameterKind::Flags(flags) => match word.chars().find(|flag| !flags.contains(*flag)) {
+            Some(flag) => Err(format!("Unknown flag '{}'", flag)),
+            None => Ok(Argument::Text(word.to_string())),
+        },
+    }
+}
+
+fn parse_line(word: &str) -> Result<usize, String> {
+    match word.parse::<usize>() {
+        Ok(0) => Err("Line numbers start at 1".to_string()),
+        Ok(line) => Ok(line - 1),
+        Err(_) => Err(format!("'{}' is not a line number", word)),
+    }
+}
+
+/// The first word of `text` and the text after it; nothing when only whitespace is left.
+///
+/// Words are split on whitespace. Single or double quotes group words, and a backslash
+/// takes the next character literally, inside quotes or not. A word starting with a slash
+/// that is closed by another one is a `/pattern/` and is kept as written, so that regular
+/// expression escapes survive; only `\/` becomes a slash.
+fn split_word(text: &str) -> Result<Option<(String, &str)>, String> {
+    let mut word: Option<String> = None;
+    let mut quote: Option<char> = None;
+    let mut chars = text.trim_start().chars();
+
+    while let Some(c) = chars.next() {
+        if c == '/' && quote.is_none() && word.is_none() {
+            if let Some((pattern, rest)) = split_pattern(chars.as_str()) {
+                word = Some(format!("/{}/", pattern));
+                chars = rest.chars();
+                continue;
+            }
+        }
+
+        match (c, quote) {
+            ('\\', _) => match chars.next() {
+                Some(escaped) => word.get_or_insert_with(String::new).push(escaped),
+                None => return Err("Nothing to escape at the end of the line".to_string()),
+            },
+            (c, Some(open)) if c == open => quote = None,
+            (c, Some(_)) => word.get_or_insert_with(String::new).push(c),
+            ('"' | '\'', None) => {
+                quote = Some(c);
+                // quotes start a word even if nothing follows, so "" is an empty argument
+                word.get_or_insert_with(String::new);
+            }
+            (c, None) if c.is_whitespace() => return Ok(word.map(|word| (word, chars.as_str()))),
+            (c, None) => word.get_or_insert_with(String::new).push(c),
+        }
+    }
+
+    if let Some(open) = quote {
+        return Err(format!("Missing closing {}", open));
+    }
+    Ok(word.map(|word| (word, "")))
+}
+
+/// The text without its quotes when it is a single quoted string, otherwise the text itself.
+fn unquote(text: &str) -> String {
+    let quoted = text.starts_with(['"', '\'']) && text.trim_end().ends_with(&text[..1]);
+    match split_word(text) {
+        Ok(Some((word, after))) if quoted && after.trim().is_empty() => word,
+        _ => text.to_string(),
+    }
+}
+
+/// The pattern up to the closing slash, with `\/` unescaped, and the text after the slash.
+fn split_pattern(text: &str) -> Option<(String, &str)> {
+    let mut pattern = String::new();
+    let mut chars = text.char_indices();
+    while let Some((index, c)) = chars.next() {
+        match c {
+            '/' => return Some((pattern, &text[index + 1..])),
+            '\\' => match chars.next() {
+                Some((_, '/')) => pattern.push('/'),
+                Some((_, escaped)) => {
+                    pattern.push('\\');
+                    pattern.push(escaped);
+                }
+                None => pattern.push('\\'),
+            },
+            c => pattern.push(c),
+        }
+    }
+    None
+}
+
+#[cfg(test)]
+mod tests_arguments {
+    use super::{split_word, Argument, Parameter, ParameterKind, Signature};
+    use crate::commands::CommandParseError;
+    use rstest::{fixture, rstest};
+
+    fn tokenize(line: &str) -> Result<Vec<String>, String> {
+        let mut words = Vec::new();
+        let mut rest = line;
+        while let Some((word, after)) = split_word(rest)? {
+            words.push(word);
+            rest = after;
+        }
+        Ok(words)
+    }
+
+    #[rstest]
+    #[case("AddText Hello", vec!["AddText", "Hello"])]
+    #[case("  Print  ", vec!["Print"])]
+    #[case("Save \"my notes.md\"", vec!["Save", "my notes.md"])]
+    #[case("Save 'my notes.md'", vec!["Save", "my notes.md"])]
+    #[case("Say \"it's\" 'a \"quote\"'", vec!["Say", "it's", "a \"quote\""])]
+    #[case("Save my\\ notes.md", vec!["Save", "my notes.md"])]
+    #[case("Say \"\\\"\\\\\"", vec!["Say", "\"\\"])]
+    #[case("ReplaceText old \"\"", vec!["ReplaceText", "old", ""])]
+    #[case("Path a\"b c\"d", vec!["Path", "ab cd"])]
+    #[case("Find /\\d+ \\w/ 2", vec!["Find", "/\\d+ \\w/", "2"])]
+    #[case("Find /a\\/b/", vec!["Find", "/a/b/"])]
+    #[case("Open /tmp/notes.txt", vec!["Open", "/tmp/notes.txt"])]
+    #[case("Open /etc", vec!["Open", "/etc"])]
+    #[case("Say a/b/c", vec!["Say", "a/b/c"])]
+    fn tokenize_splits_words(#[case] line: &str, #[case] expected: Vec<&str>) {
+        assert_eq!(tokenize(line), Ok(expected.into_iter().map(String::from).collect()));
+    }
+
+    #[rstest]
+    #[case("Say \"unterminated", "Missing closing \"")]
+    #[case("Say 'unterminated", "Missing closing '")]
+    #[case("Say trailing\\", "Nothing to escape at the end of the line")]
+    fn tokenize_rejects_incomplete_lines(#[case] line: &str, #[case] expected: &str) {
+        assert_eq!(tokenize(line), Err(expected.to_string()));
+    }
+
+    #[fixture]
+    fn signature() -> Signature {
+        Signature::new("Cmd", "A test command")
+            .with(Parameter::required("line", ParameterKind::Line))
+            .with(Parameter::optional("lines", ParameterKind::Range))
+            .with(Parameter::optional("text", ParameterKind::Text))
+    }
+
+    #[rstest]
+    fn usage_lists_parameters(signature: Signature) {
+        assert_eq!(signature.usage(), "Cmd <line> [lines] [text...]");
+    }
+
+    #[rstest]
+    fn parse_converts_arguments(signature: Signature) {
+        let arguments = signature.parse("Cmd 3 5..7 some  \"quoted text\"").unwrap();
+
+        assert_eq!(arguments.line("line"), Some(2));
+        assert_eq!(arguments.range("lines"), Some(4..7));
+        assert_eq!(arguments.text("text"), Some("some  \"quoted text\""));
+    }
+
+    #[rstest]
+    #[case("Cmd 1 2 it's raining", "it's raining")]
+    #[case("Cmd 1 2 a  b", "a  b")]
+    #[case("Cmd 1 2 C:\\new", "C:\\new")]
+    #[case("Cmd 1 2 \"3", "\"3")]
+    #[case("Cmd 1 2 \"  padded  \"", "  padded  ")]
+    #[case("Cmd 1 2 'it\\'s'", "it's")]
+    #[case("Cmd 1 2 \"a\" \"b\"", "\"a\" \"b\"")]
+    #[case("Cmd 1 2 \"a\"b", "\"a\"b")]
+    fn parse_keeps_text_as_typed(signature: Signature, #[case] command: &str, #[case] expected: &str) {
+        let arguments = signature.parse(command).unwrap();
+
+        assert_eq!(arguments.text("text"), Some(expected));
+    }
+
+    #[rstest]
+    fn parse_leaves_out_missing_optional_arguments(signature: Signature) {
+        let arguments = signature.parse("Cmd 1 4").unwrap();
+
+        assert_eq!(arguments.line("line"), Some(0));
+        assert_eq!(arguments.range("lines"), Some(3..4));
+        assert_eq!(arguments.text("text"), None);
+    }
+
+    #[rstest]
+    #[case("Cmd", "Missing <line>")]
+    #[case("Cmd x", "'x' is not a line number")]
+    #[case("Cmd 0", "Line numbers start at 1")]
+    #[case("Cmd -1", "'-1' is not a line number")]
+    #[case("Cmd 1 7..3", "'7..3' is an empty range")]
+    #[case("Cmd 1 3..x", "'x' is not a line number")]
+    #[case("Cmd \"3", "Missing closing \"")]
+    fn parse_reports_problem_with_usage(
+        signature: Signature,
+        #[case] command: &str,
+        #[case] problem: &str,
+    ) {
+        assert_eq!(
+            signature.parse(command),
+            Err(CommandParseError {
+                message: format!("{}. Usage: Cmd <line> [lines] [text...]", problem)
+            })
+        );
+    }
+
+    #[rstest]
+    #[case("Split 2 1", Ok(0))]
+    #[case("Split 2 12", Ok(11))]
+    #[case("Split 2 0", Err("Columns start at 1. Usage: Split <line> <column>"))]
+    #[case("Split 2 x", Err("'x' is not a column. Usage: Split <line> <column>"))]
+    fn parse_columns(#[case] command: &str, #[case] expected: Result<usize, &str>) {
+        let signature = Signature::new("Split", "Splits a line")
+            .with(Parameter::required("line", ParameterKind::Line))
+            .with(Parameter::required("column", ParameterKind::Column));
+
+        let column = signature
+            .parse(command)
+            .map(|arguments| arguments.column("column").unwrap())
+            .map_err(|error| error.message);
+
+        assert_eq!(column, expected.map_err(str::to_string));
+    }
+
+    #[fixture]
+    fn replace_signature() -> Signature {
+        Signature::new("Replace", "Replaces matches")
+            .with(Parameter::required("regex", ParameterKind::Pattern))
+            .with(Parameter::required("replacement", ParameterKind::Word))
+            .with(Parameter::optional("lines", ParameterKind::Range))
+            .with(Parameter::optional("flags", ParameterKind::Flags("gi")))
+    }
+
+    #[rstest]
+    fn usage_shows_patterns_and_flags(replace_signature: Signature) {
+        assert_eq!(
+            replace_signature.usage(),
+            "Replace </regex/> <replacement> [lines] [g|i]"
+        );
+    }
+
+    #[rstest]
+    #[case("Replace /\\d+/ N", None, None)]
+    #[case("Replace /\\d+/ N 2..3", Some(1..3), None)]
+    #[case("Replace /\\d+/ N gi", None, Some("gi"))]
+    #[case("Replace /\\d+/ N 2 g", Some(1..2), Some("g"))]
+    fn parse_skips_optional_arguments_that_do_not_apply(
+        replace_signature: Signature,
+        #[case] command: &str,
+        #[case] lines: Option<std::ops::Range<usize>>,
+        #[case] flags: Option<&str>,
+    ) {
+        let arguments = replace_signature.parse(command).unwrap();
+
+        assert_eq!(arguments.text("regex"), Some("\\d+"));
+        assert_eq!(arguments.range("lines"), lines);
+        assert_eq!(arguments.text("flags"), flags);
+    }
+
+    #[rstest]
+    #[case("Replace d+ N", "'d+' is not a /pattern/")]
+    #[case("Replace /a/ N x", "'x' is not a line number")]
+    #[case("Replace /a/ N 2 gx", "Unknown flag 'x'")]
+    #[case("Replace /a/ N g 2", "Unexpected argument '2'")]
+    fn parse_reports_pattern_and_flag_problems(
+        replace_signature: Signature,
+        #[case] command: &str,
+        #[case] problem: &str,
+    ) {
+        assert_eq!(
+            replace_signature.parse(command).unwrap_err().message,
+            format!("{}. Usage: Replace </regex/> <replacement> [lines] [g|i]", problem)
+        );
+    }
+
+    #[rstest]
+    #[case("Play m 3", Ok(Some(3)))]
+    #[case("Play m", Ok(None))]
+    #[case("Play m 0", Err("'0' is not a positive number. Usage: Play <name> [times]"))]
+    #[case("Play m -2", Err("'-2' is not a positive number. Usage: Play <name> [times]"))]
+    fn parse_counts(#[case] command: &str, #[case] expected: Result<Option<usize>, &str>) {
+        let signature = Signature::new("Play", "Plays a macro")
+            .with(Parameter::required("name", ParameterKind::Word))
+            .with(Parameter::optional("times", ParameterKind::Count));
+
+        let count = signature
+            .parse(command)
+            .map(|arguments| arguments.count("times"))
+            .map_err(|error| error.message);
+
+        assert_eq!(count, expected.map_err(str::to_string));
+    }
+
+    #[test]
+    fn parse_rejects_extra_arguments() {
+        let signature = Signature::new("Print", "Prints the document");
+
+        assert_eq!(
+            signature.parse("Print Invalid"),
+            Err(CommandParseError {
+                message: "Unexpected argument 'Invalid'. Usage: Print".to_string()
+            })
+        );
+    }
+
+    #[test]
+    fn parse_keeps_quoted_words_whole() {
+        let signature = Signature::new("ReplaceText", "Replaces text")
+            .with(Parameter::required("old", ParameterKind::Word))
+            .with(Parameter::required("new", ParameterKind::Word));
+
+        let arguments = signature.parse("ReplaceText \"two words\" ''").unwrap();
+
+        assert_eq!(arguments.text("old"), Some("two words"));
+        assert_eq!(arguments.text("new"), Some(""));
+        assert_eq!(
+            arguments.values.get("new"),
+            Some(&Argument::Text(String::new()))
+        );
+    }
+}
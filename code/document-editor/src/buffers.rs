@@ -0,0 +1,525 @@
+use std::cell::RefCell;
+use std::rc::Rc;
+
+use crate::arguments::{Parameter, ParameterKind, Signature};
+use crate::commands::{Command, CommandHistory, CommandParseError};
+use crate::console::Console;
+use crate::document::Document;
+
+/// How a command reaches its document or history: looked up each time it runs,
+/// so that commands act on whichever buffer is active.
+pub struct Current<T> {
+    resolve: Rc<dyn Fn() -> Rc<RefCell<T>>>,
+}
+
+impl<T> Current<T> {
+    pub fn get(&self) -> Rc<RefCell<T>> {
+        (self.resolve)()
+    }
+}
+
+impl<T> Clone for Current<T> {
+    fn clone(&self) -> Self {
+        Current {
+            resolve: self.resolve.clone(),
+        }
+    }
+}
+
+/// Always the same document or history.
+impl<T: 'static> From<Rc<RefCell<T>>> for Current<T> {
+    fn from(fixed: Rc<RefCell<T>>) -> Self {
+        Current {
+            resolve: Rc::new(move || fixed.clone()),
+        }
+    }
+}
+
+/// A named document with its own history.
+pub struct Buffer {
+    pub name: String,
+    pub document: Rc<RefCell<Document>>,
+    pub command_history: Rc<RefCell<CommandHistory>>,
+}
+
+/// The open buffers, one of which is active. There is always at least one.
+pub struct Buffers {
+    buffers: Vec<Buffer>,
+    active: usize,
+    /// The most changes kept for undo in each buffer.
+    history_size: usize,
+    /// The buffer last refused by `Close` because of unsaved changes.
+    close_warned: Option<String>,
+}
+
+pub const FIRST_BUFFER: &str = "main";
+
+impl Buffers {
+    pub fn new(history_size: usize) -> Buffers {
+        let mut buffers = Buffers {
+            buffers: Vec::new(),
+            active: 0,
+            history_size,
+            close_warned: None,
+        };
+        buffers.buffers.push(buffers.buffer(FIRST_BUFFER));
+        buffers
+    }
+
+    fn buffer(&self, name: &str) -> Buffer {
+        Buffer {
+            name: name.to_string(),
+            document: Rc::new(RefCell::new(Document::new())),
+            command_history: Rc::new(RefCell::new(CommandHistory::with_limit(self.history_size))),
+        }
+    }
+
+    pub fn active(&self) -> &Buffer {
+        &self.buffers[self.active]
+    }
+
+    pub fn all(&self) -> &[Buffer] {
+        &self.buffers
+    }
+
+    /// The document of the buffer that is active whenever it is looked up.
+    pub fn current_document(buffers: &Rc<RefCell<Buffers>>) -> Current<Document> {
+        let buffers = buffers.clone();
+        Current {
+            resolve: Rc::new(move || buffers.borrow().active().document.clone()),
+        }
+    }
+
+    /// The history of the buffer that is active whenever it is looked up.
+    pub fn current_history(buffers: &Rc<RefCell<Buffers>>) -> Current<CommandHistory> {
+        let buffers = buffers.clone();
+        Current {
+            resolve: Rc::new(move || buffers.borrow().active().command_history.clone()),
+        }
+    }
+
+    /// Opens an empty buffer and makes it the active one.
+    pub fn create(&mut self, name: &str) -> Result<(), String> {
+        if self.position(name).is_some() {
+            return Err(format!("Buffer '{}' already exists", name));
+        }
+        self.buffers.push(self.buffer(name));
+        self.active = self.buffers.len() - 1;
+        Ok(())
+    }
+
+    pub fn switch(&mut self, name: &str) -> Result<(), String> {
+        self.active = self.find(name)?;
+        Ok(())
+    }
+
+    /// Closes a buffer; when it was the active one, the buffer before it becomes active.
+    pub fn close(&mut self, name: &str) -> Result<(), String> {
+        let position = self.find(name)?;
+        if self.buffers.len() == 1 {
+            return Err("Cannot close the last buffer".to_string());
+        }
+
+        self.buffers.remove(position);
+        if self.active >= position {
+            self.active = self.active.saturating_sub(1);
+        }
+        Ok(())
+    }
+
+    /// Makes the next `Close` warn again about unsaved changes, once another command ran
+    /// after the warning.
+    pub fn forget_close_warning(&mut self) {
+        self.close_warned = None;
+    }
+
+    fn position(&self, name: &str) -> Option<usize> {
+        self.buffers.iter().position(|buffer| buffer.name == name)
+    }
+
+    fn find(&self, name: &str) -> Result<usize, String> {
+        self.position(name).ok_or_else(|| format!("No buffer '{}'", name))
+    }
+}
+
+/// A command to open a new, empty buffer.
+pub struct NewCommand {
+    buffers: Rc<RefCell<Buffers>>,
+    console: Rc<RefCell<dyn Console>>,
+    name: Option<String>,
+}
+
+impl NewCommand {
+    pub fn new(buffers: Rc<RefCell<Buffers>>, console: Rc<RefCell<dyn Console>>) -> NewCommand {
+        NewCommand {
+            buffers,
+            console,
+            name: None,
+        }
+    }
+}
+
+impl Command for NewCommand {
+    fn execute(&mut self) {
+        if let Some(name) = &self.name {
+            let result = self.buffers.borrow_mut().create(name);
+            let mut console = self.console.borrow_mut();
+            match result {
+                Ok(()) => console.print_line(&format!("Created buffer '{}'", name)),
+                Err(message) => console.print_error(&message),
+            }
+        }
+    }
+
+    fn parse(&mut self, command: &str) -> Result<(), CommandParseError> {
+        let arguments = self.signature().parse(command)?;
+        self.name = arguments.text("name").map(str::to_string);
+        Ok(())
+    }
+
+    fn signature(&self) -> Signature {
+        Signature::new("New", "Opens an empty buffer and switches to it")
+            .with(Parameter::required("name", ParameterKind::Word))
+    }
+}
+
+/// A command to list the buffers, marking the active one.
+pub struct BuffersCommand {
+    buffers: Rc<RefCell<Buffers>>,
+    console: Rc<RefCell<dyn Console>>,
+}
+
+impl BuffersCommand {
+    pub fn new(buffers: Rc<RefCell<Buffers>>, console: Rc<RefCell<dyn Console>>) -> BuffersCommand {
+        BuffersCommand { buffers, console }
+    }
+}
+
+impl Command for BuffersCommand {
+    fn execute(&mut self) {
+        let buffers = self.buffers.borrow();
+        let mut console = self.console.borrow_mut();
+        for (position, buffer) in buffers.all().iter().enumerate() {
+            let document = buffer.document.borrow();
+            let marker = if position == buffers.active { "*" } else { " " };
+            let file = document
+                .path()
+                .map(|path| path.display().to_string())
+                .unwrap_or("no file".to_string());
+            let modified = if document.is_modified() { ", modified" } else { "" };
+            console.print_line(&format!(
+                "{} {}: {}, {} lines{}",
+                marker,
+                buffer.name,
+                file,
+                document.content().len(),
+                modified
+            ));
+        }
+    }
+
+    fn parse(&mut self, command: &str) -> Result<(), CommandParseError> {
+        self.signature().parse(command).map(|_| ())
+    }
+
+    fn signature(&self) -> Signature {
+        Signature::new("Buffers", "Lists the buffers, the active one marked with *")
+    }
+}
+
+/// A command to make another buffer the active one.
+///
+/// A transaction begun in the buffer left stays open in its history, which the message says.
+pub struct SwitchCommand {
+    buffers: Rc<RefCell<Buffers>>,
+    console: Rc<RefCell<dyn Console>>,
+    name: Option<String>,
+}
+
+impl SwitchCommand {
+    pub fn new(buffers: Rc<RefCell<Buffers>>, console: Rc<RefCell<dyn Console>>) -> SwitchCommand {
+        SwitchCommand {
+            buffers,
+            console,
+            name: None,
+        }
+    }
+}
+
+impl Command for SwitchCommand {
+    fn execute(&mut self) {
+        if let Some(name) = &self.name {
+            let mut buffers = self.buffers.borrow_mut();
+            let left = buffers.active().name.clone();
+            let open = buffers.active().command_history.borrow().is_grouping();
+            let result = buffers.switch(name);
+            let mut console = self.console.borrow_mut();
+            match result {
+                Ok(()) if open && left != *name => console.print_line(&format!(
+                    "Switched to buffer '{}', the transaction in buffer '{}' is still open",
+                    name, left
+                )),
+                Ok(()) => console.print_line(&format!("Switched to buffer '{}'", name)),
+                Err(message) => console.print_error(&message),
+            }
+        }
+    }
+
+    fn parse(&mut self, command: &str) -> Result<(), CommandParseError> {
+        let arguments = self.signature().parse(command)?;
+        self.name = arguments.text("name").map(str::to_string);
+        Ok(())
+    }
+
+    fn signature(&self) -> Signature {
+        Signature::new("Switch", "Makes another buffer the active one")
+            .with(Parameter::required("name", ParameterKind::Word))
+    }
+}
+
+/// A command to close a buffer, the active one if no name is given.
+///
+/// A buffer with unsaved changes is only closed by a `Close` of the same buffer that directly
+/// follows the warning, and one with an open transaction not at all.
+pub struct CloseCommand {
+    buffers: Rc<RefCell<Buffers>>,
+    console: Rc<RefCell<dyn Console>>,
+    name: Option<String>,
+}
+
+impl CloseCommand {
+    pub fn new(buffers: Rc<RefCell<Buffers>>, console: Rc<RefCell<dyn Console>>) -> CloseCommand {
+        CloseCommand {
+            buffers,
+            console,
+            name: None,
+        }
+    }
+
+    fn close(&mut self) -> Result<String, String> {
+        let mut buffers = self.buffers.borrow_mut();
+        let name = self.name.clone().unwrap_or(buffers.active().name.clone());
+
+        let buffer = buffers.all().iter().find(|buffer| buffer.name == name);
+        if buffer.is_some_and(|buffer| buffer.command_history.borrow().is_grouping()) {
+            return Err(format!(
+                "Buffer '{}' has an open transaction. Commit or roll it back first.",
+                name
+            ));
+        }
+        let modified = buffer.is_some_and(|buffer| buffer.document.borrow().is_modified());
+        let warned = buffers.close_warned.take();
+        if modified && buffers.all().len() > 1 && warned.as_ref() != Some(&name) {
+            buffers.close_warned = Some(name.clone());
+            return Err(format!(
+                "Buffer '{}' has unsaved changes. Save them, or enter Close again to discard them.",
+                name
+            ));
+        }
+
+        buffers.close(&name)?;
+        Ok(format!(
+            "Closed buffer '{}', the active buffer is '{}'",
+            name,
+            buffers.active().name
+        ))
+    }
+}
+
+impl Command for CloseCommand {
+    fn execute(&mut self) {
+        let result = self.close();
+        let mut console = self.console.borrow_mut();
+        match result {
+            Ok(message) => console.print_line(&message),
+            Err(message) => console.print_error(&message),
+        }
+    }
+
+    fn parse(&mut self, command: &str) -> Result<(), CommandParseError> {
+        let arguments = self.signature().parse(command)?;
+        self.name = arguments.text("name").map(str::to_string);
+        Ok(())
+    }
+
+    fn signature(&self) -> Signature {
+        Signature::new("Close", "Closes a buffer, the active one if no name is given")
+            .with(Parameter::optional("name", ParameterKind::Word))
+    }
+}
+
+#[cfg(test)]
+mod tests_buffers {
+    use super::{
+        Buffers, BuffersCommand, CloseCommand, Current, NewCommand, SwitchCommand, FIRST_BUFFER,
+    };
+    use crate::commands::test_commands_helpers::parsed_command;
+    use crate::commands::{AddTextCommand, Command, UndoCommand};
+    use crate::console::MockConsole;
+    use crate::document::Document;
+    use mockall::predicate::eq;
+    use rstest::{fixture, rstest};
+    use std::{cell::RefCell, rc::Rc};
+
+    #[fixture]
+    fn buffers() -> Rc<RefCell<Buffers>> {
+        Rc::new(RefCell::new(Buffers::new(10)))
+    }
+
+    fn run(command: &mut dyn Command, line: &str) {
+        command.parse(line).unwrap();
+        command.execute();
+    }
+
+    fn console_expecting(lines: &[&'static str], errors: &[&'static str]) -> Rc<RefCell<MockConsole>> {
+        let mut console = MockConsole::new();
+        for &line in lines {
+            console.expect_print_line().with(eq(line)).times(1).returning(|_| ());
+        }
+        for &error in errors {
+            console.expect_print_error().with(eq(error)).times(1).returning(|_| ());
+        }
+        Rc::new(RefCell::new(console))
+    }
+
+    fn content(buffers: &Rc<RefCell<Buffers>>) -> Vec<String> {
+        buffers.borrow().active().document.borrow().content().clone()
+    }
+
+    #[rstest]
+    fn commands_act_on_the_active_buffer_with_its_own_history(buffers: Rc<RefCell<Buffers>>) {
+        let console = console_expecting(&["Created buffer 'notes'", "Switched to buffer 'main'"], &[]);
+        let document = Buffers::current_document(&buffers);
+        let history = Buffers::current_history(&buffers);
+        let mut add_text = AddTextCommand::new(document.clone(), history.clone());
+        let mut undo = UndoCommand::new(history);
+
+        run(&mut add_text, "AddText first");
+        run(&mut NewCommand::new(buffers.clone(), console.clone()), "New notes");
+        run(&mut add_text, "AddText second");
+        assert_eq!(content(&buffers), vec!["second"]);
+
+        run(&mut SwitchCommand::new(buffers.clone(), console.clone()), "Switch main");
+        assert_eq!(content(&buffers), vec!["first"]);
+        run(&mut undo, "Undo");
+
+        assert!(document.get().borrow().content().is_empty());
+        let buffers = buffers.borrow();
+        let notes = &buffers.all()[1];
+        assert_eq!(notes.document.borrow().content(), &vec!["second"]);
+        assert_eq!(notes.command_history.borrow().commands.len(), 1);
+    }
+
+    #[rstest]
+    fn buffers_lists_every_buffer_and_marks_the_active_one(buffers: Rc<RefCell<Buffers>>) {
+        buffers.borrow_mut().create("notes").unwrap();
+        buffers.borrow().active().document.borrow_mut().add_line("Line1".to_string());
+        let console = console_expecting(
+            &["  main: no file, 0 lines", "* notes: no file, 1 lines, modified"],
+            &[],
+        );
+
+        run(&mut BuffersCommand::new(buffers.clone(), console), "Buffers");
+    }
+
+    #[rstest]
+    #[case("New main", "Buffer 'main' already exists")]
+    #[case("Switch other", "No buffer 'other'")]
+    #[case("Close other", "No buffer 'other'")]
+    #[case("Close", "Cannot close the last buffer")]
+    fn buffer_commands_report_errors(
+        buffers: Rc<RefCell<Buffers>>,
+        #[case] line: &str,
+        #[case] error: &'static str,
+    ) {
+        let console = console_expecting(&[], &[error]);
+        let document = Buffers::current_document(&buffers);
+        let history = Buffers::current_history(&buffers);
+
+        parsed_command(line, document, history, &console, Some(&buffers)).execute();
+
+        assert_eq!(buffers.borrow().all().len(), 1);
+        assert_eq!(buffers.borrow().active().name, FIRST_BUFFER);
+    }
+
+    #[rstest]
+    fn close_activates_the_buffer_before(buffers: Rc<RefCell<Buffers>>) {
+        buffers.borrow_mut().create("notes").unwrap();
+        buffers.borrow_mut().create("todo").unwrap();
+        buffers.borrow_mut().switch("notes").unwrap();
+        let console = console_expecting(&["Closed buffer 'notes', the active buffer is 'main'"], &[]);
+
+        run(&mut CloseCommand::new(buffers.clone(), console), "Close");
+
+        let names: Vec<_> = buffers.borrow().all().iter().map(|buffer| buffer.name.clone()).collect();
+        assert_eq!(names, vec!["main", "todo"]);
+        assert_eq!(buffers.borrow().active().name, "main");
+    }
+
+    #[rstest]
+    fn close_of_modified_buffer_needs_a_second_close(buffers: Rc<RefCell<Buffers>>) {
+        buffers.borrow_mut().create("notes").unwrap();
+        buffers.borrow().active().document.borrow_mut().add_line("Line1".to_string());
+        let console = console_expecting(
+            &["Closed buffer 'notes', the active buffer is 'main'"],
+            &["Buffer 'notes' has unsaved changes. Save them, or enter Close again to discard them."],
+        );
+        let mut close = CloseCommand::new(buffers.clone(), console);
+
+        run(&mut close, "Close notes");
+        assert_eq!(buffers.borrow().all().len(), 2);
+
+        run(&mut close, "Close notes");
+        assert_eq!(buffers.borrow().all().len(), 1);
+    }
+
+    #[rstest]
+    fn switch_says_the_transaction_left_behind_is_still_open(buffers: Rc<RefCell<Buffers>>) {
+        buffers.borrow_mut().create("notes").unwrap();
+        buffers.borrow().active().command_history.borrow_mut().begin_group();
+        let console = console_expecting(
+            &[
+                "Switched to buffer 'notes'",
+                "Switched to buffer 'main', the transaction in buffer 'notes' is still open",
+            ],
+            &[],
+        );
+        let mut switch = SwitchCommand::new(buffers.clone(), console);
+
+        run(&mut switch, "Switch notes");
+        run(&mut switch, "Switch main");
+
+        assert_eq!(buffers.borrow().active().name, "main");
+    }
+
+    #[rstest]
+    fn close_refuses_a_buffer_with_an_open_transaction(buffers: Rc<RefCell<Buffers>>) {
+        buffers.borrow_mut().create("notes").unwrap();
+        let history = buffers.borrow().active().command_history.clone();
+        history.borrow_mut().begin_group();
+        let console = console_expecting(
+            &["Closed buffer 'notes', the active buffer is 'main'"],
+            &[
+                "Buffer 'notes' has an open transaction. Commit or roll it back first.",
+                "Buffer 'notes' has an open transaction. Commit or roll it back first.",
+            ],
+        );
+        let mut close = CloseCommand::new(buffers.clone(), console);
+
+        run(&mut close, "Close");
+        run(&mut close, "Close notes");
+        assert_eq!(buffers.borrow().all().len(), 2);
+
+        history.borrow_mut().end_group();
+        run(&mut close, "Close notes");
+        assert_eq!(buffers.borrow().all().len(), 1);
+    }
+
+    #[test]
+    fn fixed_current_always_gives_the_same_document() {
+        let document = Rc::new(RefCell::new(Document::new()));
+        let current = Current::from(document.clone());
+
+        assert!(Rc::ptr_eq(&current.get(), &document));
+    }
+}
@@ -1,16 +1,25 @@
 use std::cell::RefCell;
 use std::rc::Rc;
 
+use std::ops::Range;
+use std::path::{Path, PathBuf};
+
+use crate::arguments::{Parameter, ParameterKind, Signature};
+use crate::buffers::Current;
 use crate::console::Console;
-use crate::document::{Document, DocumentSnapshot};
+use crate::document::{Document, DocumentError};
+use crate::storage::FileFormat;
 
 use mockall::automock;
+use regex::{Regex, RegexBuilder};
 
 /// A command that can be executed by the application.
 #[automock]
 pub trait Command {
     fn execute(&mut self);
     fn parse(&mut self, _command: &str) -> Result<(), CommandParseError>;
+    /// The arguments the command accepts, used for parsing and to generate help.
+    fn signature(&self) -> Signature;
 }
 
 /// A command that can be undone.
@@ -22,24 +31,157 @@ pub trait ReversibleCommand: Command {
 #[derive(Default)]
 pub struct CommandHistory {
     pub commands: Vec<Box<dyn ReversibleCommand>>,
+    pub undone_commands: Vec<Box<dyn ReversibleCommand>>,
+    /// Commands added since each unfinished `begin_group`, innermost last.
+    groups: Vec<Vec<Box<dyn ReversibleCommand>>>,
+    /// The most commands kept for undo, the oldest ones are forgotten first.
+    limit: Option<usize>,
 }
 
 impl CommandHistory {
     pub fn new() -> CommandHistory {
         CommandHistory {
             commands: Vec::new(),
+            undone_commands: Vec::new(),
+            groups: Vec::new(),
+            limit: None,
+        }
+    }
+
+    /// A history that keeps at most `limit` commands for undo.
+    pub fn with_limit(limit: usize) -> CommandHistory {
+        CommandHistory {
+            limit: Some(limit),
+            ..CommandHistory::new()
         }
     }
 
+    /// Registers an executed command. A new command makes the undone ones obsolete.
     pub fn add(&mut self, command: Box<dyn ReversibleCommand>) {
-        self.commands.push(command);
+        match self.groups.last_mut() {
+            Some(group) => group.push(command),
+            None => {
+                self.commands.push(command);
+                let excess = self.commands.len().saturating_sub(self.limit.unwrap_or(usize::MAX));
+                self.commands.drain(..excess);
+            }
+        }
+        self.undone_commands.clear();
+    }
+
+    /// Collects the commands added from now on until `end_group`, so that they undo as one.
+    pub fn begin_group(&mut self) {
+        self.groups.push(Vec::new());
+    }
+
+    /// Adds the commands collected since the matching `begin_group` as a single command.
+    pub fn end_group(&mut self) {
+        if let Some(mut group) = self.groups.pop() {
+            match group.len() {
+                0 => {}
+                1 => self.add(group.remove(0)),
+                _ => self.add(Box::new(CompositeCommand::new(group))),
+            }
+        }
+    }
+
+    /// Discards the commands collected since the matching `begin_group` after undoing them,
+    /// returning whether there was a group to discard.
+    pub fn rollback_group(&mut self) -> bool {
+        match self.groups.pop() {
+            Some(group) => {
+                CompositeCommand::new(group).undo();
+                true
+            }
+            None => false,
+        }
+    }
+
+    pub fn is_grouping(&self) -> bool {
+        !self.groups.is_empty()
     }
 
+    /// Undoes the last command, taken from the innermost unfinished group if there is one.
     pub fn undo(&mut self) {
-        if let Some(mut cmd) = self.commands.pop() {
+        let commands = self.groups.last_mut().unwrap_or(&mut self.commands);
+        if let Some(mut cmd) = commands.pop() {
+            cmd.undo();
+            self.undone_commands.push(cmd);
+        }
+    }
+
+    /// Re-executes the most recently undone command.
+    ///
+    /// The command registers itself in the history again while executing, so the history
+    /// must not be borrowed meanwhile, and the rest of the redo stack is set aside
+    /// to survive that registration. A composite command registers each of its commands,
+    /// which are grouped into one again.
+    pub fn redo(command_history: &RefCell<CommandHistory>) {
+        let (cmd, undone_commands) = {
+            let mut history = command_history.borrow_mut();
+            let cmd = history.undone_commands.pop();
+            (cmd, std::mem::take(&mut history.undone_commands))
+        };
+
+        if let Some(mut cmd) = cmd {
+            command_history.borrow_mut().begin_group();
+            cmd.execute();
+            command_history.borrow_mut().end_group();
+        }
+
+        command_history.borrow_mut().undone_commands = undone_commands;
+    }
+
+    /// Forgets every command, including those of unfinished groups, which stay open.
+    pub fn clear(&mut self) {
+        self.commands.clear();
+        self.undone_commands.clear();
+        self.groups.iter_mut().for_each(Vec::clear);
+    }
+}
+
+/// Several commands that are undone as one, the last one first.
+///
+/// Executing it executes the commands again, each of which registers itself in the history,
+/// so it should be executed inside a group of `CommandHistory`.
+pub struct CompositeCommand {
+    commands: Vec<Box<dyn ReversibleCommand>>,
+}
+
+impl CompositeCommand {
+    pub fn new(commands: Vec<Box<dyn ReversibleCommand>>) -> CompositeCommand {
+        CompositeCommand { commands }
+    }
+}
+
+impl Command for CompositeCommand {
+    fn execute(&mut self) {
+        for cmd in self.commands.iter_mut() {
+            cmd.execute();
+        }
+    }
+
+    fn parse(&mut self, command: &str) -> Result<(), CommandParseError> {
+        self.signature().parse(command).map(|_| ())
+    }
+
+    fn signature(&self) -> Signature {
+        Signature::new("Composite", "Several commands undone as one")
+    }
+}
+
+impl ReversibleCommand for CompositeCommand {
+    fn undo(&mut self) {
+        for cmd in self.commands.iter_mut().rev() {
             cmd.undo();
         }
     }
+
+    fn clone(&self) -> Box<dyn ReversibleCommand> {
+        Box::new(CompositeCommand {
+            commands: self.commands.iter().map(|cmd| cmd.as_ref().clone()).collect(),
+        })
+    }
 }
 
 #[derive(Debug, PartialEq, Clone)]
@@ -49,49 +191,58 @@ pub struct CommandParseError {
 
 /// A command to print the document content to the console.
 pub struct PrintCommand {
-    document: Rc<RefCell<Document>>,
+    document: Current<Document>,
     console: Rc<RefCell<dyn Console>>,
 }
 
 impl PrintCommand {
-    pub fn new(document: Rc<RefCell<Document>>, console: Rc<RefCell<dyn Console>>) -> PrintCommand {
-        PrintCommand { document, console }
+    pub fn new(document: impl Into<Current<Document>>, console: Rc<RefCell<dyn Console>>) -> PrintCommand {
+        PrintCommand {
+            document: document.into(),
+            console,
+        }
     }
 }
 
 impl Command for PrintCommand {
     fn execute(&mut self) {
-        for line in self.document.borrow().content() {
-            self.console.as_ref().borrow_mut().print_line(&line);
+        for line in self.document.get().borrow().content() {
+            self.console.as_ref().borrow_mut().print_line(line);
         }
     }
 
     fn parse(&mut self, command: &str) -> Result<(), CommandParseError> {
-        match command {
-            "Print" => Ok(()),
-            _ => Err(CommandParseError {
-                message: format!("Unknown command: {}", command),
-            }),
-        }
+        self.signature().parse(command).map(|_| ())
+    }
+
+    fn signature(&self) -> Signature {
+        Signature::new("Print", "Prints the document")
     }
 }
 
-/// Tests for the PrintCommand
+// Tests for the PrintCommand
 
 #[cfg(test)]
-mod test_commands_helpers {
+pub(crate) mod test_commands_helpers {
+    use crate::buffers::{Buffers, CloseCommand, Current, NewCommand, SwitchCommand};
+    use crate::arguments::Signature;
+    use crate::commands::{
+        BeginCommand, Command, CommandHistory, CommandParseError, CommitCommand, CopyCommand,
+        DeleteCommand, FindCommand, InsertCommand, JoinCommand, MoveCommand, ReplaceCommand,
+        ReversibleCommand, RollbackCommand, SplitCommand, UndoCommand,
+    };
     use crate::{console::MockConsole, document::Document};
+    use mockall::mock;
     use rstest::{fixture};
     use std::{cell::RefCell, rc::Rc};
 
     #[fixture]
-    pub(super) fn mock_console() -> Rc<RefCell<MockConsole>> {
-        let mock = Rc::new(RefCell::new(MockConsole::new()));
-        mock
+    pub(crate) fn mock_console() -> Rc<RefCell<MockConsole>> {
+        Rc::new(RefCell::new(MockConsole::new()))
     }
 
     #[fixture]
-    pub(super) fn document() -> Rc<RefCell<Document>> {
+    pub(crate) fn document() -> Rc<RefCell<Document>> {
         let mut doc = Document::new();
         doc.add_line("Line1".to_string());
         doc.add_line("Line2".to_string());
@@ -100,9 +251,56 @@ mod test_commands_helpers {
     }
 
     #[fixture]
-    pub(super) fn command_history() -> Rc<RefCell<crate::commands::CommandHistory>> {
+    pub(crate) fn command_history() -> Rc<RefCell<crate::commands::CommandHistory>> {
         Rc::new(RefCell::new(crate::commands::CommandHistory::new()))
     }
+
+    mock! {
+        pub(crate) TestCommand {}
+
+        impl Command for TestCommand {
+            fn execute(&mut self);
+            fn parse(&mut self, command: &str) -> Result<(), CommandParseError>;
+            fn signature(&self) -> Signature;
+        }
+
+        impl ReversibleCommand for TestCommand {
+            fn undo(&mut self);
+            fn clone(&self) -> Box<dyn ReversibleCommand>;
+        }
+    }
+
+    /// The command named by the first word of `line`, parsed. Buffer commands need `buffers`.
+    pub(crate) fn parsed_command(
+        line: &str,
+        document: impl Into<Current<Document>>,
+        command_history: impl Into<Current<CommandHistory>>,
+        console: &Rc<RefCell<MockConsole>>,
+        buffers: Option<&Rc<RefCell<Buffers>>>,
+    ) -> Box<dyn Command> {
+        let (document, history, console) = (document.into(), command_history.into(), console.clone());
+        let buffers = || buffers.expect("buffer commands need buffers").clone();
+        let mut cmd: Box<dyn Command> = match line.split(' ').next() {
+            Some("Begin") => Box::new(BeginCommand::new(history, console)),
+            Some("Commit") => Box::new(CommitCommand::new(history, console)),
+            Some("Rollback") => Box::new(RollbackCommand::new(history, console)),
+            Some("Undo") => Box::new(UndoCommand::new(history)),
+            Some("Insert") => Box::new(InsertCommand::new(document, history, console)),
+            Some("Delete") => Box::new(DeleteCommand::new(document, history, console)),
+            Some("Move") => Box::new(MoveCommand::new(document, history, console)),
+            Some("Copy") => Box::new(CopyCommand::new(document, history, console)),
+            Some("Join") => Box::new(JoinCommand::new(document, history, console)),
+            Some("Split") => Box::new(SplitCommand::new(document, history, console)),
+            Some("Find") => Box::new(FindCommand::new(document, console)),
+            Some("Replace") => Box::new(ReplaceCommand::new(document, history, console)),
+            Some("New") => Box::new(NewCommand::new(buffers(), console)),
+            Some("Switch") => Box::new(SwitchCommand::new(buffers(), console)),
+            Some("Close") => Box::new(CloseCommand::new(buffers(), console)),
+            _ => panic!("not a test command: {}", line),
+        };
+        cmd.parse(line).unwrap();
+        cmd
+    }
 }
 
 #[cfg(test)]
@@ -169,7 +367,7 @@ mod tests_print_command {
         assert_eq!(
             print_cmd.parse("Print Invalid"),
             Err(CommandParseError {
-                message: "Unknown command: Print Invalid".to_string()
+                message: "Unexpected argument 'Invalid'. Usage: Print".to_string()
             })
         );
     }
@@ -177,25 +375,27 @@ mod tests_print_command {
 
 /// AddTextCommand adds a line of text at the end of document.
 pub struct AddTextCommand {
-    document: Rc<RefCell<Document>>,
-    command_history: Rc<RefCell<CommandHistory>>,
+    document: Current<Document>,
+    command_history: Current<CommandHistory>,
     text: Option<String>,
-    snapshot: Option<DocumentSnapshot>,
+    /// The index of the added line, to undo by erasing it.
+    line: Option<usize>,
 }
 
 impl AddTextCommand {
     pub fn new(
-        document: Rc<RefCell<Document>>,
-        command_history: Rc<RefCell<CommandHistory>>,
+        document: impl Into<Current<Document>>,
+        command_history: impl Into<Current<CommandHistory>>,
     ) -> AddTextCommand {
         AddTextCommand {
-            document,
-            command_history,
+            document: document.into(),
+            command_history: command_history.into(),
             text: None,
-            snapshot: None,
+            line: None,
         }
     }
 
+    #[cfg(test)]
     pub fn with_text(mut self, text: &str) -> AddTextCommand {
         self.text = Some(text.to_string());
         self
@@ -205,30 +405,32 @@ impl AddTextCommand {
 impl Command for AddTextCommand {
     fn execute(&mut self) {
         if let Some(text) = &self.text {
-            self.snapshot = Some(self.document.borrow().create_snapshot());
-            self.command_history.borrow_mut().add(self.clone());
-            self.document.borrow_mut().add_line(text.clone());
+            let document = self.document.get();
+            let mut document = document.borrow_mut();
+            self.line = Some(document.content().len());
+            document.add_line(text.clone());
+            drop(document);
+            self.command_history.get().borrow_mut().add(self.clone());
         }
     }
 
     fn parse(&mut self, command: &str) -> Result<(), CommandParseError> {
-        let parts: Vec<&str> = command.splitn(2, ' ').collect();
-        if parts.len() == 2 {
-            self.text = Some(parts[1].to_string());
-            Ok(())
-        } else {
-            Err(CommandParseError {
-                message: "Invalid command format".to_string(),
-            })
-        }
+        let arguments = self.signature().parse(command)?;
+        self.text = arguments.text("text").map(str::to_string);
+        Ok(())
+    }
+
+    fn signature(&self) -> Signature {
+        Signature::new("AddText", "Adds a line of text at the end of the document")
+            .with(Parameter::required("text", ParameterKind::Text))
     }
 }
 
 impl ReversibleCommand for AddTextCommand {
     fn undo(&mut self) {
-        self.document
-            .borrow_mut()
-            .restore_snapshot(self.snapshot.take().unwrap());
+        if let Some(line) = self.line.take() {
+            let _ = self.document.get().borrow_mut().erase_line(line);
+        }
     }
 
     fn clone(&self) -> Box<dyn ReversibleCommand> {
@@ -236,7 +438,7 @@ impl ReversibleCommand for AddTextCommand {
             document: self.document.clone(),
             command_history: self.command_history.clone(),
             text: self.text.clone(),
-            snapshot: self.snapshot.clone(),
+            line: self.line,
         })
     }
 }
@@ -300,7 +502,7 @@ mod tests_add_text_command {
         assert_eq!(
             add_text_cmd.parse("AddText"),
             Err(crate::commands::CommandParseError {
-                message: "Invalid command format".to_string()
+                message: "Missing <text...>. Usage: AddText <text...>".to_string()
             })
         );
     }
@@ -325,25 +527,29 @@ mod tests_add_text_command {
 }
 
 pub struct ReplaceTextCommand {
-    document: Rc<RefCell<Document>>,
-    command_history: Rc<RefCell<CommandHistory>>,
+    document: Current<Document>,
+    command_history: Current<CommandHistory>,
     old_text: Option<String>,
     new_text: Option<String>,
+    /// The lines before the replacement, to undo only what changed.
+    replaced: Vec<(usize, String)>,
 }
 
 impl ReplaceTextCommand {
     pub fn new(
-        document: Rc<RefCell<Document>>,
-        command_history: Rc<RefCell<CommandHistory>>,
+        document: impl Into<Current<Document>>,
+        command_history: impl Into<Current<CommandHistory>>,
     ) -> ReplaceTextCommand {
         ReplaceTextCommand {
-            document,
-            command_history,
+            document: document.into(),
+            command_history: command_history.into(),
             old_text: None,
             new_text: None,
+            replaced: Vec::new(),
         }
     }
 
+    #[cfg(test)]
     pub(self) fn with_args(mut self, old_text: &str, new_text: &str) -> ReplaceTextCommand {
         self.old_text = Some(old_text.to_string());
         self.new_text = Some(new_text.to_string());
@@ -356,34 +562,34 @@ impl Command for ReplaceTextCommand {
         let old_text = self.old_text.as_ref().unwrap();
         let new_text = self.new_text.as_ref().unwrap();
 
-        self.command_history.borrow_mut().add(self.clone());
-
-        let mut doc = self.document.borrow_mut();
-        doc.replace_text(old_text, new_text);
+        self.replaced = self.document.get().borrow_mut().replace_text(old_text, new_text);
+        // nothing to undo, and recording it would drop what could be redone
+        if !self.replaced.is_empty() {
+            self.command_history.get().borrow_mut().add(self.clone());
+        }
     }
 
-    fn parse(&mut self, _command: &str) -> Result<(), CommandParseError> {
-        let parts: Vec<&str> = _command.splitn(3, ' ').collect();
-
-        if parts.len() == 3 {
-            self.old_text = Some(parts[1].to_string());
-            self.new_text = Some(parts[2].to_string());
-            return Ok(());
-        }
+    fn parse(&mut self, command: &str) -> Result<(), CommandParseError> {
+        let arguments = self.signature().parse(command)?;
+        self.old_text = arguments.text("old").map(str::to_string);
+        self.new_text = arguments.text("new").map(str::to_string);
+        Ok(())
+    }
 
-        Err(CommandParseError {
-            message: "Invalid command format".to_string(),
-        })
+    fn signature(&self) -> Signature {
+        Signature::new("ReplaceText", "Replaces every occurrence of a text fragment")
+            .with(Parameter::required("old", ParameterKind::Word))
+            .with(Parameter::required("new", ParameterKind::Word))
     }
 }
 
 impl ReversibleCommand for ReplaceTextCommand {
     fn undo(&mut self) {
-        let old_text = self.old_text.as_ref().unwrap();
-        let new_text = self.new_text.as_ref().unwrap();
-
-        let mut doc = self.document.borrow_mut();
-        doc.replace_text(new_text, old_text);
+        let document = self.document.get();
+        let mut document = document.borrow_mut();
+        for (index, line) in std::mem::take(&mut self.replaced) {
+            let _ = document.replace_line(index, line);
+        }
     }
 
     fn clone(&self) -> Box<dyn ReversibleCommand> {
@@ -392,6 +598,7 @@ impl ReversibleCommand for ReplaceTextCommand {
             command_history: self.command_history.clone(),
             old_text: self.old_text.clone(),
             new_text: self.new_text.clone(),
+            replaced: self.replaced.clone(),
         })
     }
 }
@@ -444,6 +651,26 @@ mod tests_replace_text_command {
         assert_eq!(command_history.as_ref().borrow().commands.len(), 1);
     }
 
+    #[rstest]
+    fn execute_without_matches_is_not_recorded(
+        document: Rc<RefCell<Document>>,
+        command_history: Rc<RefCell<CommandHistory>>,
+    ) {
+        let mut replace_text_cmd =
+            ReplaceTextCommand::new(document.clone(), command_history.clone())
+                .with_args("Missing", "Replaced");
+        document.as_ref().borrow_mut().mark_saved(std::path::Path::new("notes.txt"));
+
+        replace_text_cmd.execute();
+
+        assert_eq!(
+            document.as_ref().borrow().content(),
+            &vec!["Line1", "Line2", "Line3"]
+        );
+        assert!(!document.as_ref().borrow().is_modified());
+        assert_eq!(command_history.as_ref().borrow().commands.len(), 0);
+    }
+
     #[rstest]
     fn parsing_correct_arguments(mut replace_text_cmd: ReplaceTextCommand) {
         let _ = replace_text_cmd.parse("ReplaceText Line2 Replaced");
@@ -452,12 +679,20 @@ mod tests_replace_text_command {
         assert_eq!(replace_text_cmd.new_text, Some("Replaced".to_string()));
     }
 
+    #[rstest]
+    fn parsing_quoted_arguments(mut replace_text_cmd: ReplaceTextCommand) {
+        let _ = replace_text_cmd.parse("ReplaceText \"Line 2\" ''");
+
+        assert_eq!(replace_text_cmd.old_text, Some("Line 2".to_string()));
+        assert_eq!(replace_text_cmd.new_text, Some("".to_string()));
+    }
+
     #[rstest]
     fn parsing_incorrect_arguments(mut replace_text_cmd: ReplaceTextCommand) {
         assert_eq!(
             replace_text_cmd.parse("ReplaceText"),
             Err(crate::commands::CommandParseError {
-                message: "Invalid command format".to_string()
+                message: "Missing <old>. Usage: ReplaceText <old> <new>".to_string()
             })
         );
     }
@@ -484,54 +719,61 @@ mod tests_replace_text_command {
             &vec!["Line1", "Line2", "Line3"]
         );
     }
+
+    #[rstest]
+    fn undo_leaves_lines_that_already_had_the_new_text(
+        document: Rc<RefCell<Document>>,
+        command_history: Rc<RefCell<CommandHistory>>,
+    ) {
+        let mut replace_text_cmd =
+            ReplaceTextCommand::new(document.clone(), command_history.clone()).with_args("2", "3");
+
+        replace_text_cmd.execute();
+        replace_text_cmd.undo();
+
+        assert_eq!(
+            document.as_ref().borrow().content(),
+            &vec!["Line1", "Line2", "Line3"]
+        );
+    }
 }
 
 pub struct UndoCommand {
-    command_history: Rc<RefCell<CommandHistory>>,
+    command_history: Current<CommandHistory>,
 }
 
 impl UndoCommand {
-    pub fn new(command_history: Rc<RefCell<CommandHistory>>) -> UndoCommand {
-        UndoCommand { command_history }
+    pub fn new(command_history: impl Into<Current<CommandHistory>>) -> UndoCommand {
+        UndoCommand {
+            command_history: command_history.into(),
+        }
     }
 }
 
 impl Command for UndoCommand {
     fn execute(&mut self) {
-        self.command_history.borrow_mut().undo();
+        self.command_history.get().borrow_mut().undo();
     }
 
     fn parse(&mut self, command: &str) -> Result<(), CommandParseError> {
-        match command {
-            "Undo" => Ok(()),
-            _ => Err(CommandParseError {
-                message: format!("Unknown command: {}", command),
-            }),
-        }
+        self.signature().parse(command).map(|_| ())
+    }
+
+    fn signature(&self) -> Signature {
+        Signature::new("Undo", "Undoes the last change")
     }
 }
 
 #[cfg(test)]
 mod tests_undo_command {
-    use super::{test_commands_helpers::command_history, Command, ReversibleCommand, UndoCommand};
-    use mockall::mock;
+    use super::{
+        test_commands_helpers::{command_history, document, MockTestCommand},
+        AddTextCommand, Command, CommandHistory, UndoCommand,
+    };
+    use crate::document::Document;
     use rstest::rstest;
     use std::{cell::RefCell, rc::Rc};
 
-    mock! {
-        TestCommand {}
-
-        impl Command for TestCommand {
-            fn execute(&mut self);
-            fn parse(&mut self, command: &str) -> Result<(), crate::commands::CommandParseError>;
-        }
-
-        impl ReversibleCommand for TestCommand {
-            fn undo(&mut self);
-            fn clone(&self) -> Box<dyn ReversibleCommand>;
-        }
-    }
-
     #[rstest]
     fn execute_pops_command_from_history_and_executes_undo(
         command_history: Rc<RefCell<crate::commands::CommandHistory>>,
@@ -544,6 +786,28 @@ mod tests_undo_command {
         undo_cmd.execute();
 
         assert_eq!(command_history.borrow().commands.len(), 0);
+        assert_eq!(command_history.borrow().undone_commands.len(), 1);
+    }
+
+    #[rstest]
+    fn history_with_limit_forgets_the_oldest_commands(document: Rc<RefCell<Document>>) {
+        let command_history = Rc::new(RefCell::new(CommandHistory::with_limit(2)));
+        for text in ["Line4", "Line5", "Line6"] {
+            AddTextCommand::new(document.clone(), command_history.clone())
+                .with_text(text)
+                .execute();
+        }
+        assert_eq!(command_history.borrow().commands.len(), 2);
+
+        let mut undo_cmd = UndoCommand::new(command_history.clone());
+        for _ in 0..3 {
+            undo_cmd.execute();
+        }
+
+        assert_eq!(
+            document.borrow().content(),
+            &vec!["Line1", "Line2", "Line3", "Line4"]
+        );
     }
 
     #[rstest]
@@ -555,71 +819,1761 @@ mod tests_undo_command {
     }
 }
 
-/// A command to clear the document content.
-pub struct ClearCommand {
-    document: Rc<RefCell<Document>>,
-    command_history: Rc<RefCell<CommandHistory>>,
-    snapshot: Option<DocumentSnapshot>,
+pub struct RedoCommand {
+    command_history: Current<CommandHistory>,
 }
 
-impl ClearCommand {
-    pub fn new(document: Rc<RefCell<Document>>, command_history: Rc<RefCell<CommandHistory>>) -> ClearCommand {
-        ClearCommand {
-            document,
-            command_history,
-            snapshot: None,
+impl RedoCommand {
+    pub fn new(command_history: impl Into<Current<CommandHistory>>) -> RedoCommand {
+        RedoCommand {
+            command_history: command_history.into(),
         }
     }
 }
 
-impl Command for ClearCommand {
+impl Command for RedoCommand {
     fn execute(&mut self) {
-        self.snapshot = Some(self.document.borrow().create_snapshot());
-        self.command_history.borrow_mut().add(self.clone());
-        self.document.borrow_mut().clear();
+        CommandHistory::redo(&self.command_history.get());
     }
 
-    fn parse(&mut self, _command: &str) -> Result<(), CommandParseError> {
-        Ok(())
+    fn parse(&mut self, command: &str) -> Result<(), CommandParseError> {
+        self.signature().parse(command).map(|_| ())
+    }
+
+    fn signature(&self) -> Signature {
+        Signature::new("Redo", "Redoes the last undone change")
     }
 }
 
-impl ReversibleCommand for ClearCommand {
-    fn undo(&mut self) {
-        self.document.borrow_mut().restore_snapshot(self.snapshot.take().unwrap());
+#[cfg(test)]
+mod tests_redo_command {
+    use super::{
+        test_commands_helpers::{command_history, document, MockTestCommand},
+        AddTextCommand, Command, RedoCommand, UndoCommand,
+    };
+    use crate::document::Document;
+    use rstest::rstest;
+    use std::{cell::RefCell, rc::Rc};
+
+    #[rstest]
+    fn execute_pops_undone_command_and_executes_it_again(
+        command_history: Rc<RefCell<crate::commands::CommandHistory>>,
+    ) {
+        let mut cmd = Box::new(MockTestCommand::new());
+        cmd.expect_undo().times(1).returning(|| ());
+        cmd.expect_execute().times(1).returning(|| ());
+        command_history.borrow_mut().add(cmd);
+        command_history.borrow_mut().undo();
+
+        let mut redo_cmd = RedoCommand::new(command_history.clone());
+        redo_cmd.execute();
+
+        assert_eq!(command_history.borrow().undone_commands.len(), 0);
     }
 
-    fn clone(&self) -> Box<dyn ReversibleCommand> {
-        Box::new(ClearCommand {
-            document: self.document.clone(),
-            command_history: self.command_history.clone(),
-            snapshot: self.snapshot.clone(),
-        })
+    #[rstest]
+    fn when_nothing_was_undone_execute_does_nothing(
+        command_history: Rc<RefCell<crate::commands::CommandHistory>>,
+    ) {
+        let mut redo_cmd = RedoCommand::new(command_history.clone());
+        redo_cmd.execute();
     }
-}
 
+    #[rstest]
+    fn new_command_clears_undone_commands(
+        command_history: Rc<RefCell<crate::commands::CommandHistory>>,
+    ) {
+        let mut cmd = Box::new(MockTestCommand::new());
+        cmd.expect_undo().times(1).returning(|| ());
+        command_history.borrow_mut().add(cmd);
+        command_history.borrow_mut().undo();
+
+        command_history.borrow_mut().add(Box::new(MockTestCommand::new()));
+
+        assert_eq!(command_history.borrow().undone_commands.len(), 0);
+    }
+
+    #[rstest]
+    fn execute_restores_undone_changes_in_order(
+        document: Rc<RefCell<Document>>,
+        command_history: Rc<RefCell<crate::commands::CommandHistory>>,
+    ) {
+        AddTextCommand::new(document.clone(), command_history.clone())
+            .with_text("Line4")
+            .execute();
+        AddTextCommand::new(document.clone(), command_history.clone())
+            .with_text("Line5")
+            .execute();
+
+        let mut undo_cmd = UndoCommand::new(command_history.clone());
+        undo_cmd.execute();
+        undo_cmd.execute();
+
+        let mut redo_cmd = RedoCommand::new(command_history.clone());
+        redo_cmd.execute();
+        assert_eq!(
+            document.as_ref().borrow().content(),
+            &vec!["Line1", "Line2", "Line3", "Line4"]
+        );
+        assert_eq!(command_history.borrow().undone_commands.len(), 1);
+
+        redo_cmd.execute();
+        assert_eq!(
+            document.as_ref().borrow().content(),
+            &vec!["Line1", "Line2", "Line3", "Line4", "Line5"]
+        );
+
+        undo_cmd.execute();
+        assert_eq!(
+            document.as_ref().borrow().content(),
+            &vec!["Line1", "Line2", "Line3", "Line4"]
+        );
+    }
+}
 
 #[cfg(test)]
-mod tests_clear_command {
-    use std::{cell::RefCell, rc::Rc};
+mod tests_composite_command {
+    use super::{
+        test_commands_helpers::{command_history, document, MockTestCommand},
+        AddTextCommand, Command, CommandHistory, CompositeCommand, ReversibleCommand,
+    };
+    use crate::document::Document;
+    use mockall::Sequence;
     use rstest::rstest;
-    use crate::{commands::{ClearCommand, Command}, document::Document};
-    use super::test_commands_helpers::{command_history, document};
+    use std::{cell::RefCell, rc::Rc};
 
+    #[test]
+    fn undo_undoes_commands_in_reverse_order() {
+        let mut seq = Sequence::new();
+        let mut first = Box::new(MockTestCommand::new());
+        let mut second = Box::new(MockTestCommand::new());
+        second.expect_undo().times(1).in_sequence(&mut seq).returning(|| ());
+        first.expect_undo().times(1).in_sequence(&mut seq).returning(|| ());
+
+        let mut composite = CompositeCommand::new(vec![first, second]);
+        composite.undo();
+    }
 
     #[rstest]
-    fn execute_clears_document_content(document: Rc<RefCell<Document>>, command_history: Rc<RefCell<crate::commands::CommandHistory>>) {
-        let mut clear_cmd = ClearCommand::new(document.clone(), command_history.clone());
-        clear_cmd.execute();
+    fn group_is_added_to_history_as_one_command(
+        document: Rc<RefCell<Document>>,
+        command_history: Rc<RefCell<CommandHistory>>,
+    ) {
+        command_history.borrow_mut().begin_group();
+        for text in ["Line4", "Line5"] {
+            AddTextCommand::new(document.clone(), command_history.clone())
+                .with_text(text)
+                .execute();
+        }
+        assert_eq!(command_history.borrow().commands.len(), 0);
+        command_history.borrow_mut().end_group();
 
-        assert_eq!(document.borrow().content(), &Vec::<String>::new());
+        assert_eq!(command_history.borrow().commands.len(), 1);
+
+        command_history.borrow_mut().undo();
+        assert_eq!(
+            document.borrow().content(),
+            &vec!["Line1", "Line2", "Line3"]
+        );
+
+        CommandHistory::redo(&command_history);
+        assert_eq!(
+            document.borrow().content(),
+            &vec!["Line1", "Line2", "Line3", "Line4", "Line5"]
+        );
+        assert_eq!(command_history.borrow().commands.len(), 1);
+
+        command_history.borrow_mut().undo();
+        assert_eq!(
+            document.borrow().content(),
+            &vec!["Line1", "Line2", "Line3"]
+        );
     }
 
     #[rstest]
-    fn execute_registers_itself_in_command_history(document: Rc<RefCell<Document>>, command_history: Rc<RefCell<crate::commands::CommandHistory>>) {
-        let mut clear_cmd = ClearCommand::new(document.clone(), command_history.clone());
-        clear_cmd.execute();
+    fn group_of_one_command_is_added_as_it_is(command_history: Rc<RefCell<CommandHistory>>) {
+        let mut cmd = Box::new(MockTestCommand::new());
+        cmd.expect_undo().times(1).returning(|| ());
+
+        command_history.borrow_mut().begin_group();
+        command_history.borrow_mut().end_group();
+        command_history.borrow_mut().begin_group();
+        command_history.borrow_mut().add(cmd);
+        command_history.borrow_mut().end_group();
 
         assert_eq!(command_history.borrow().commands.len(), 1);
+        command_history.borrow_mut().undo();
     }
-}
\ No newline at end of file
+
+    #[rstest]
+    fn nested_groups_end_up_in_the_outer_group(
+        document: Rc<RefCell<Document>>,
+        command_history: Rc<RefCell<CommandHistory>>,
+    ) {
+        let mut history = command_history.borrow_mut();
+        history.begin_group();
+        history.add(AddTextCommand::new(document.clone(), command_history.clone()).clone());
+        history.begin_group();
+        history.add(AddTextCommand::new(document.clone(), command_history.clone()).clone());
+        history.add(AddTextCommand::new(document.clone(), command_history.clone()).clone());
+        history.end_group();
+        assert_eq!(history.commands.len(), 0);
+        history.end_group();
+
+        assert_eq!(history.commands.len(), 1);
+    }
+}
+
+/// A command to start a transaction: the changes until `Commit` are undone as one,
+/// and `Rollback` undoes them at once. Transactions can be nested.
+pub struct BeginCommand {
+    command_history: Current<CommandHistory>,
+    console: Rc<RefCell<dyn Console>>,
+}
+
+impl BeginCommand {
+    pub fn new(command_history: impl Into<Current<CommandHistory>>, console: Rc<RefCell<dyn Console>>) -> BeginCommand {
+        BeginCommand {
+            command_history: command_history.into(),
+            console,
+        }
+    }
+}
+
+impl Command for BeginCommand {
+    fn execute(&mut self) {
+        self.command_history.get().borrow_mut().begin_group();
+        self.console
+            .as_ref()
+            .borrow_mut()
+            .print_line("Transaction started, enter Commit or Rollback to finish");
+    }
+
+    fn parse(&mut self, command: &str) -> Result<(), CommandParseError> {
+        self.signature().parse(command).map(|_| ())
+    }
+
+    fn signature(&self) -> Signature {
+        Signature::new("Begin", "Starts a transaction of changes undone as one")
+    }
+}
+
+/// A command to finish the innermost transaction, keeping its changes as a single change.
+pub struct CommitCommand {
+    command_history: Current<CommandHistory>,
+    console: Rc<RefCell<dyn Console>>,
+}
+
+impl CommitCommand {
+    pub fn new(command_history: impl Into<Current<CommandHistory>>, console: Rc<RefCell<dyn Console>>) -> CommitCommand {
+        CommitCommand {
+            command_history: command_history.into(),
+            console,
+        }
+    }
+}
+
+impl Command for CommitCommand {
+    fn execute(&mut self) {
+        let result = {
+            let command_history = self.command_history.get();
+            let mut history = command_history.borrow_mut();
+            match history.is_grouping() {
+                true => {
+                    history.end_group();
+                    Ok("Transaction committed".to_string())
+                }
+                false => Err("No transaction to commit".to_string()),
+            }
+        };
+        print_result(&*self.console, result);
+    }
+
+    fn parse(&mut self, command: &str) -> Result<(), CommandParseError> {
+        self.signature().parse(command).map(|_| ())
+    }
+
+    fn signature(&self) -> Signature {
+        Signature::new("Commit", "Finishes the transaction, keeping its changes")
+    }
+}
+
+/// A command to finish the innermost transaction by undoing its changes.
+pub struct RollbackCommand {
+    command_history: Current<CommandHistory>,
+    console: Rc<RefCell<dyn Console>>,
+}
+
+impl RollbackCommand {
+    pub fn new(command_history: impl Into<Current<CommandHistory>>, console: Rc<RefCell<dyn Console>>) -> RollbackCommand {
+        RollbackCommand {
+            command_history: command_history.into(),
+            console,
+        }
+    }
+}
+
+impl Command for RollbackCommand {
+    fn execute(&mut self) {
+        let result = match self.command_history.get().borrow_mut().rollback_group() {
+            true => Ok("Transaction rolled back".to_string()),
+            false => Err("No transaction to roll back".to_string()),
+        };
+        print_result(&*self.console, result);
+    }
+
+    fn parse(&mut self, command: &str) -> Result<(), CommandParseError> {
+        self.signature().parse(command).map(|_| ())
+    }
+
+    fn signature(&self) -> Signature {
+        Signature::new("Rollback", "Finishes the transaction, undoing its changes")
+    }
+}
+
+#[cfg(test)]
+mod tests_transaction_commands {
+    use super::test_commands_helpers::{command_history, document, mock_console, parsed_command};
+    use crate::commands::CommandHistory;
+    use crate::console::MockConsole;
+    use crate::document::Document;
+    use mockall::predicate::eq;
+    use rstest::rstest;
+    use std::{cell::RefCell, rc::Rc};
+
+    /// Parses and executes each command line against the document.
+    fn run(
+        lines: &[&str],
+        document: &Rc<RefCell<Document>>,
+        command_history: &Rc<RefCell<CommandHistory>>,
+        console: &Rc<RefCell<MockConsole>>,
+    ) {
+        for line in lines {
+            parsed_command(line, document.clone(), command_history.clone(), console, None).execute();
+        }
+    }
+
+    fn quiet(mock_console: Rc<RefCell<MockConsole>>) -> Rc<RefCell<MockConsole>> {
+        mock_console.borrow_mut().expect_print_line().returning(|_| ());
+        mock_console
+    }
+
+    #[rstest]
+    fn rollback_restores_the_document_exactly(
+        document: Rc<RefCell<Document>>,
+        command_history: Rc<RefCell<CommandHistory>>,
+        mock_console: Rc<RefCell<MockConsole>>,
+    ) {
+        let console = quiet(mock_console);
+        run(&["Insert 1 Before"], &document, &command_history, &console);
+        let before = document.borrow().content().clone();
+
+        run(
+            &["Begin", "Delete 2", "Move 1 3", "Replace /Line/ L g", "Insert 1 x", "Rollback"],
+            &document,
+            &command_history,
+            &console,
+        );
+
+        assert_eq!(document.borrow().content(), &before);
+        assert_eq!(command_history.borrow().commands.len(), 1);
+        assert!(!command_history.borrow().is_grouping());
+    }
+
+    #[rstest]
+    fn committed_transaction_is_undone_as_one(
+        document: Rc<RefCell<Document>>,
+        command_history: Rc<RefCell<CommandHistory>>,
+        mock_console: Rc<RefCell<MockConsole>>,
+    ) {
+        let console = quiet(mock_console);
+
+        run(
+            &["Begin", "Delete 1", "Insert 3 Last", "Commit"],
+            &document,
+            &command_history,
+            &console,
+        );
+        assert_eq!(document.borrow().content(), &vec!["Line2", "Line3", "Last"]);
+        assert_eq!(command_history.borrow().commands.len(), 1);
+
+        run(&["Undo"], &document, &command_history, &console);
+        assert_eq!(document.borrow().content(), &vec!["Line1", "Line2", "Line3"]);
+    }
+
+    #[rstest]
+    fn undo_inside_transaction_undoes_its_last_change(
+        document: Rc<RefCell<Document>>,
+        command_history: Rc<RefCell<CommandHistory>>,
+        mock_console: Rc<RefCell<MockConsole>>,
+    ) {
+        let console = quiet(mock_console);
+
+        run(
+            &["Delete 3", "Begin", "Delete 1", "Delete 1", "Undo", "Commit"],
+            &document,
+            &command_history,
+            &console,
+        );
+
+        assert_eq!(document.borrow().content(), &vec!["Line2"]);
+        assert_eq!(command_history.borrow().commands.len(), 2);
+    }
+
+    #[rstest]
+    fn rollback_of_nested_transaction_keeps_the_outer_changes(
+        document: Rc<RefCell<Document>>,
+        command_history: Rc<RefCell<CommandHistory>>,
+        mock_console: Rc<RefCell<MockConsole>>,
+    ) {
+        let console = quiet(mock_console);
+
+        run(
+            &["Begin", "Delete 1", "Begin", "Delete 1", "Rollback", "Commit"],
+            &document,
+            &command_history,
+            &console,
+        );
+
+        assert_eq!(document.borrow().content(), &vec!["Line2", "Line3"]);
+        assert_eq!(command_history.borrow().commands.len(), 1);
+    }
+
+    #[rstest]
+    #[case("Commit", "No transaction to commit")]
+    #[case("Rollback", "No transaction to roll back")]
+    fn finishing_without_transaction_prints_message(
+        #[case] command: &str,
+        #[case] message: &'static str,
+        document: Rc<RefCell<Document>>,
+        command_history: Rc<RefCell<CommandHistory>>,
+        mock_console: Rc<RefCell<MockConsole>>,
+    ) {
+        mock_console
+            .borrow_mut()
+            .expect_print_error()
+            .with(eq(message))
+            .times(1)
+            .returning(|_| ());
+
+        run(&[command], &document, &command_history, &mock_console);
+
+        assert_eq!(document.borrow().content(), &vec!["Line1", "Line2", "Line3"]);
+    }
+}
+
+/// A command to clear the document content.
+pub struct ClearCommand {
+    document: Current<Document>,
+    command_history: Current<CommandHistory>,
+    /// The removed lines, restored on undo.
+    cleared: Vec<String>,
+}
+
+impl ClearCommand {
+    pub fn new(document: impl Into<Current<Document>>, command_history: impl Into<Current<CommandHistory>>) -> ClearCommand {
+        ClearCommand {
+            document: document.into(),
+            command_history: command_history.into(),
+            cleared: Vec::new(),
+        }
+    }
+}
+
+impl Command for ClearCommand {
+    fn execute(&mut self) {
+        self.cleared = self.document.get().borrow_mut().clear();
+        if !self.cleared.is_empty() {
+            self.command_history.get().borrow_mut().add(self.clone());
+        }
+    }
+
+    fn parse(&mut self, command: &str) -> Result<(), CommandParseError> {
+        self.signature().parse(command).map(|_| ())
+    }
+
+    fn signature(&self) -> Signature {
+        Signature::new("Clear", "Removes all lines")
+    }
+}
+
+impl ReversibleCommand for ClearCommand {
+    fn undo(&mut self) {
+        let _ = self.document.get().borrow_mut().insert_lines(0, std::mem::take(&mut self.cleared));
+    }
+
+    fn clone(&self) -> Box<dyn ReversibleCommand> {
+        Box::new(ClearCommand {
+            document: self.document.clone(),
+            command_history: self.command_history.clone(),
+            cleared: self.cleared.clone(),
+        })
+    }
+}
+
+
+#[cfg(test)]
+mod tests_clear_command {
+    use std::{cell::RefCell, rc::Rc};
+    use rstest::rstest;
+    use crate::{commands::{ClearCommand, Command}, document::Document};
+    use super::test_commands_helpers::{command_history, document};
+
+
+    #[rstest]
+    fn execute_clears_document_content(document: Rc<RefCell<Document>>, command_history: Rc<RefCell<crate::commands::CommandHistory>>) {
+        let mut clear_cmd = ClearCommand::new(document.clone(), command_history.clone());
+        clear_cmd.execute();
+
+        assert_eq!(document.borrow().content(), &Vec::<String>::new());
+    }
+
+    #[rstest]
+    fn execute_registers_itself_in_command_history(document: Rc<RefCell<Document>>, command_history: Rc<RefCell<crate::commands::CommandHistory>>) {
+        let mut clear_cmd = ClearCommand::new(document.clone(), command_history.clone());
+        clear_cmd.execute();
+
+        assert_eq!(command_history.borrow().commands.len(), 1);
+    }
+
+    #[rstest]
+    fn undo_restores_removed_lines(document: Rc<RefCell<Document>>, command_history: Rc<RefCell<crate::commands::CommandHistory>>) {
+        ClearCommand::new(document.clone(), command_history.clone()).execute();
+
+        command_history.borrow_mut().undo();
+
+        assert_eq!(document.borrow().content(), &vec!["Line1", "Line2", "Line3"]);
+    }
+}
+
+/// Adds an executed command to the history, or reports on the console why it failed.
+fn record(
+    command: &dyn ReversibleCommand,
+    command_history: &RefCell<CommandHistory>,
+    console: &RefCell<dyn Console>,
+    result: Result<(), DocumentError>,
+) {
+    match result {
+        Ok(()) => command_history.borrow_mut().add(command.clone()),
+        Err(error) => console.borrow_mut().print_error(&error.message),
+    }
+}
+
+/// Inserts a line of text before a line; one past the last line appends.
+#[derive(Clone)]
+pub struct InsertCommand {
+    document: Current<Document>,
+    command_history: Current<CommandHistory>,
+    console: Rc<RefCell<dyn Console>>,
+    line: Option<usize>,
+    text: Option<String>,
+}
+
+impl InsertCommand {
+    pub fn new(
+        document: impl Into<Current<Document>>,
+        command_history: impl Into<Current<CommandHistory>>,
+        console: Rc<RefCell<dyn Console>>,
+    ) -> InsertCommand {
+        InsertCommand {
+            document: document.into(),
+            command_history: command_history.into(),
+            console,
+            line: None,
+            text: None,
+        }
+    }
+}
+
+impl Command for InsertCommand {
+    fn execute(&mut self) {
+        if let (Some(line), Some(text)) = (self.line, &self.text) {
+            let result = self.document.get().borrow_mut().insert_line(line, text.clone());
+            record(self, &self.command_history.get(), &*self.console, result);
+        }
+    }
+
+    fn parse(&mut self, command: &str) -> Result<(), CommandParseError> {
+        let arguments = self.signature().parse(command)?;
+        self.line = arguments.line("line");
+        self.text = arguments.text("text").map(str::to_string);
+        Ok(())
+    }
+
+    fn signature(&self) -> Signature {
+        Signature::new("Insert", "Inserts a line of text before a line")
+            .with(Parameter::required("line", ParameterKind::Line))
+            .with(Parameter::required("text", ParameterKind::Text))
+    }
+}
+
+impl ReversibleCommand for InsertCommand {
+    fn undo(&mut self) {
+        if let Some(line) = self.line {
+            let _ = self.document.get().borrow_mut().erase_line(line);
+        }
+    }
+
+    fn clone(&self) -> Box<dyn ReversibleCommand> {
+        Box::new(Clone::clone(self))
+    }
+}
+
+/// Deletes a line or a range of lines.
+#[derive(Clone)]
+pub struct DeleteCommand {
+    document: Current<Document>,
+    command_history: Current<CommandHistory>,
+    console: Rc<RefCell<dyn Console>>,
+    lines: Option<Range<usize>>,
+    deleted: Vec<String>,
+}
+
+impl DeleteCommand {
+    pub fn new(
+        document: impl Into<Current<Document>>,
+        command_history: impl Into<Current<CommandHistory>>,
+        console: Rc<RefCell<dyn Console>>,
+    ) -> DeleteCommand {
+        DeleteCommand {
+            document: document.into(),
+            command_history: command_history.into(),
+            console,
+            lines: None,
+            deleted: Vec::new(),
+        }
+    }
+}
+
+impl Command for DeleteCommand {
+    fn execute(&mut self) {
+        if let Some(lines) = self.lines.clone() {
+            let result = self.document.get().borrow_mut().erase_lines(lines);
+            let result = result.map(|deleted| self.deleted = deleted);
+            record(self, &self.command_history.get(), &*self.console, result);
+        }
+    }
+
+    fn parse(&mut self, command: &str) -> Result<(), CommandParseError> {
+        let arguments = self.signature().parse(command)?;
+        self.lines = arguments.range("lines");
+        Ok(())
+    }
+
+    fn signature(&self) -> Signature {
+        Signature::new("Delete", "Deletes a line or a range of lines such as 3..7")
+            .with(Parameter::required("lines", ParameterKind::Range))
+    }
+}
+
+impl ReversibleCommand for DeleteCommand {
+    fn undo(&mut self) {
+        if let Some(lines) = &self.lines {
+            let deleted = std::mem::take(&mut self.deleted);
+            let _ = self.document.get().borrow_mut().insert_lines(lines.start, deleted);
+        }
+    }
+
+    fn clone(&self) -> Box<dyn ReversibleCommand> {
+        Box::new(Clone::clone(self))
+    }
+}
+
+/// Moves a line so that it becomes the given line.
+#[derive(Clone)]
+pub struct MoveCommand {
+    document: Current<Document>,
+    command_history: Current<CommandHistory>,
+    console: Rc<RefCell<dyn Console>>,
+    from: Option<usize>,
+    to: Option<usize>,
+}
+
+impl MoveCommand {
+    pub fn new(
+        document: impl Into<Current<Document>>,
+        command_history: impl Into<Current<CommandHistory>>,
+        console: Rc<RefCell<dyn Console>>,
+    ) -> MoveCommand {
+        MoveCommand {
+            document: document.into(),
+            command_history: command_history.into(),
+            console,
+            from: None,
+            to: None,
+        }
+    }
+}
+
+/// Moves the line at `from` to `to`, leaving the document untouched if either does not exist.
+fn move_line(document: &mut Document, from: usize, to: usize) -> Result<(), DocumentError> {
+    document.lines(from.max(to)..from.max(to) + 1)?;
+    let line = document.erase_line(from)?;
+    document.insert_line(to, line)
+}
+
+impl Command for MoveCommand {
+    fn execute(&mut self) {
+        if let (Some(from), Some(to)) = (self.from, self.to) {
+            let result = move_line(&mut self.document.get().borrow_mut(), from, to);
+            record(self, &self.command_history.get(), &*self.console, result);
+        }
+    }
+
+    fn parse(&mut self, command: &str) -> Result<(), CommandParseError> {
+        let arguments = self.signature().parse(command)?;
+        self.from = arguments.line("from");
+        self.to = arguments.line("to");
+        Ok(())
+    }
+
+    fn signature(&self) -> Signature {
+        Signature::new("Move", "Moves a line so that it becomes line <to>")
+            .with(Parameter::required("from", ParameterKind::Line))
+            .with(Parameter::required("to", ParameterKind::Line))
+    }
+}
+
+impl ReversibleCommand for MoveCommand {
+    fn undo(&mut self) {
+        if let (Some(from), Some(to)) = (self.from, self.to) {
+            let _ = move_line(&mut self.document.get().borrow_mut(), to, from);
+        }
+    }
+
+    fn clone(&self) -> Box<dyn ReversibleCommand> {
+        Box::new(Clone::clone(self))
+    }
+}
+
+/// Copies a line or a range of lines before another line; one past the last line appends.
+#[derive(Clone)]
+pub struct CopyCommand {
+    document: Current<Document>,
+    command_history: Current<CommandHistory>,
+    console: Rc<RefCell<dyn Console>>,
+    lines: Option<Range<usize>>,
+    to: Option<usize>,
+}
+
+impl CopyCommand {
+    pub fn new(
+        document: impl Into<Current<Document>>,
+        command_history: impl Into<Current<CommandHistory>>,
+        console: Rc<RefCell<dyn Console>>,
+    ) -> CopyCommand {
+        CopyCommand {
+            document: document.into(),
+            command_history: command_history.into(),
+            console,
+            lines: None,
+            to: None,
+        }
+    }
+}
+
+impl Command for CopyCommand {
+    fn execute(&mut self) {
+        if let (Some(lines), Some(to)) = (self.lines.clone(), self.to) {
+            let document = self.document.get();
+            let mut document = document.borrow_mut();
+            let result = document
+                .lines(lines)
+                .map(|copied| copied.to_vec())
+                .and_then(|copied| document.insert_lines(to, copied));
+            drop(document);
+            record(self, &self.command_history.get(), &*self.console, result);
+        }
+    }
+
+    fn parse(&mut self, command: &str) -> Result<(), CommandParseError> {
+        let arguments = self.signature().parse(command)?;
+        self.lines = arguments.range("lines");
+        self.to = arguments.line("to");
+        Ok(())
+    }
+
+    fn signature(&self) -> Signature {
+        Signature::new("Copy", "Copies a line or a range of lines before line <to>")
+            .with(Parameter::required("lines", ParameterKind::Range))
+            .with(Parameter::required("to", ParameterKind::Line))
+    }
+}
+
+impl ReversibleCommand for CopyCommand {
+    fn undo(&mut self) {
+        if let (Some(lines), Some(to)) = (&self.lines, self.to) {
+            let _ = self.document.get().borrow_mut().erase_lines(to..to + lines.len());
+        }
+    }
+
+    fn clone(&self) -> Box<dyn ReversibleCommand> {
+        Box::new(Clone::clone(self))
+    }
+}
+
+/// Joins a range of lines into one, separated by spaces. A single line is joined with the next.
+#[derive(Clone)]
+pub struct JoinCommand {
+    document: Current<Document>,
+    command_history: Current<CommandHistory>,
+    console: Rc<RefCell<dyn Console>>,
+    lines: Option<Range<usize>>,
+    joined: Vec<String>,
+}
+
+impl JoinCommand {
+    pub fn new(
+        document: impl Into<Current<Document>>,
+        command_history: impl Into<Current<CommandHistory>>,
+        console: Rc<RefCell<dyn Console>>,
+    ) -> JoinCommand {
+        JoinCommand {
+            document: document.into(),
+            command_history: command_history.into(),
+            console,
+            lines: None,
+            joined: Vec::new(),
+        }
+    }
+}
+
+impl Command for JoinCommand {
+    fn execute(&mut self) {
+        if let Some(mut lines) = self.lines.clone() {
+            if lines.len() == 1 {
+                lines.end += 1;
+            }
+            let document = self.document.get();
+            let mut document = document.borrow_mut();
+            let result = document.erase_lines(lines.clone()).and_then(|joined| {
+                document.insert_line(lines.start, joined.join(" "))?;
+                self.joined = joined;
+                Ok(())
+            });
+            drop(document);
+            record(self, &self.command_history.get(), &*self.console, result);
+        }
+    }
+
+    fn parse(&mut self, command: &str) -> Result<(), CommandParseError> {
+        let arguments = self.signature().parse(command)?;
+        self.lines = arguments.range("lines");
+        Ok(())
+    }
+
+    fn signature(&self) -> Signature {
+        Signature::new("Join", "Joins a range of lines, or a line with the next one")
+            .with(Parameter::required("lines", ParameterKind::Range))
+    }
+}
+
+impl ReversibleCommand for JoinCommand {
+    fn undo(&mut self) {
+        if let Some(lines) = &self.lines {
+            let document = self.document.get();
+            let mut document = document.borrow_mut();
+            let _ = document.erase_line(lines.start);
+            let _ = document.insert_lines(lines.start, std::mem::take(&mut self.joined));
+        }
+    }
+
+    fn clone(&self) -> Box<dyn ReversibleCommand> {
+        Box::new(Clone::clone(self))
+    }
+}
+
+/// Splits a line in two before the given column.
+#[derive(Clone)]
+pub struct SplitCommand {
+    document: Current<Document>,
+    command_history: Current<CommandHistory>,
+    console: Rc<RefCell<dyn Console>>,
+    line: Option<usize>,
+    column: Option<usize>,
+}
+
+impl SplitCommand {
+    pub fn new(
+        document: impl Into<Current<Document>>,
+        command_history: impl Into<Current<CommandHistory>>,
+        console: Rc<RefCell<dyn Console>>,
+    ) -> SplitCommand {
+        SplitCommand {
+            document: document.into(),
+            command_history: command_history.into(),
+            console,
+            line: None,
+            column: None,
+        }
+    }
+}
+
+fn split_line(document: &mut Document, line: usize, column: usize) -> Result<(), DocumentError> {
+    let text = &document.lines(line..line + 1)?[0];
+    let index = match text.char_indices().map(|(index, _)| index).chain([text.len()]).nth(column) {
+        Some(index) => index,
+        None => {
+            return Err(DocumentError {
+                message: format!("Column {} is beyond the end of line {}", column + 1, line + 1),
+            })
+        }
+    };
+
+    let (first, second) = text.split_at(index);
+    let split = vec![first.to_string(), second.to_string()];
+    document.erase_line(line)?;
+    document.insert_lines(line, split)
+}
+
+impl Command for SplitCommand {
+    fn execute(&mut self) {
+        if let (Some(line), Some(column)) = (self.line, self.column) {
+            let result = split_line(&mut self.document.get().borrow_mut(), line, column);
+            record(self, &self.command_history.get(), &*self.console, result);
+        }
+    }
+
+    fn parse(&mut self, command: &str) -> Result<(), CommandParseError> {
+        let arguments = self.signature().parse(command)?;
+        self.line = arguments.line("line");
+        self.column = arguments.column("column");
+        Ok(())
+    }
+
+    fn signature(&self) -> Signature {
+        Signature::new("Split", "Splits a line in two before a column")
+            .with(Parameter::required("line", ParameterKind::Line))
+            .with(Parameter::required("column", ParameterKind::Column))
+    }
+}
+
+impl ReversibleCommand for SplitCommand {
+    fn undo(&mut self) {
+        if let Some(line) = self.line {
+            let document = self.document.get();
+            let mut document = document.borrow_mut();
+            if let Ok(split) = document.erase_lines(line..line + 2) {
+                let _ = document.insert_line(line, split.concat());
+            }
+        }
+    }
+
+    fn clone(&self) -> Box<dyn ReversibleCommand> {
+        Box::new(Clone::clone(self))
+    }
+}
+
+#[cfg(test)]
+mod tests_line_commands {
+    use super::test_commands_helpers::{command_history, document, mock_console, parsed_command};
+    use crate::commands::CommandHistory;
+    use crate::console::MockConsole;
+    use crate::document::Document;
+    use mockall::predicate::eq;
+    use rstest::rstest;
+    use std::{cell::RefCell, rc::Rc};
+
+    #[rstest]
+    #[case("Insert 2 New line", vec!["Line1", "New line", "Line2", "Line3"])]
+    #[case("Insert 4 Last", vec!["Line1", "Line2", "Line3", "Last"])]
+    #[case("Delete 2", vec!["Line1", "Line3"])]
+    #[case("Delete 1..2", vec!["Line3"])]
+    #[case("Move 1 3", vec!["Line2", "Line3", "Line1"])]
+    #[case("Move 3 1", vec!["Line3", "Line1", "Line2"])]
+    #[case("Copy 1..2 4", vec!["Line1", "Line2", "Line3", "Line1", "Line2"])]
+    #[case("Copy 3 1", vec!["Line3", "Line1", "Line2", "Line3"])]
+    #[case("Join 1..3", vec!["Line1 Line2 Line3"])]
+    #[case("Join 2", vec!["Line1", "Line2 Line3"])]
+    #[case("Split 2 5", vec!["Line1", "Line", "2", "Line3"])]
+    #[case("Split 1 6", vec!["Line1", "", "Line2", "Line3"])]
+    fn execute_edits_lines_and_undo_restores_them(
+        #[case] command: &str,
+        #[case] expected: Vec<&str>,
+        document: Rc<RefCell<Document>>,
+        command_history: Rc<RefCell<CommandHistory>>,
+        mock_console: Rc<RefCell<MockConsole>>,
+    ) {
+        let mut cmd = parsed_command(command, document.clone(), command_history.clone(), &mock_console, None);
+
+        cmd.execute();
+        assert_eq!(document.borrow().content(), &expected);
+        assert_eq!(command_history.borrow().commands.len(), 1);
+
+        command_history.borrow_mut().undo();
+        assert_eq!(document.borrow().content(), &vec!["Line1", "Line2", "Line3"]);
+
+        CommandHistory::redo(&command_history);
+        assert_eq!(document.borrow().content(), &expected);
+    }
+
+    #[rstest]
+    #[case("Insert 5 Text", "Cannot insert at line 5, the document has 3 lines")]
+    #[case("Delete 4", "Line 4 does not exist, the document has 3 lines")]
+    #[case("Delete 2..9", "Line 4 does not exist, the document has 3 lines")]
+    #[case("Move 4 1", "Line 4 does not exist, the document has 3 lines")]
+    #[case("Move 1 4", "Line 4 does not exist, the document has 3 lines")]
+    #[case("Copy 3..4 1", "Line 4 does not exist, the document has 3 lines")]
+    #[case("Copy 1 5", "Cannot insert at line 5, the document has 3 lines")]
+    #[case("Join 3", "Line 4 does not exist, the document has 3 lines")]
+    #[case("Split 4 1", "Line 4 does not exist, the document has 3 lines")]
+    #[case("Split 1 7", "Column 7 is beyond the end of line 1")]
+    fn out_of_range_lines_are_reported_on_console(
+        #[case] command: &str,
+        #[case] message: &str,
+        document: Rc<RefCell<Document>>,
+        command_history: Rc<RefCell<CommandHistory>>,
+        mock_console: Rc<RefCell<MockConsole>>,
+    ) {
+        mock_console
+            .borrow_mut()
+            .expect_print_error()
+            .with(eq(message.to_string()))
+            .times(1)
+            .returning(|_| ());
+        let mut cmd = parsed_command(command, document.clone(), command_history.clone(), &mock_console, None);
+
+        cmd.execute();
+
+        assert_eq!(document.borrow().content(), &vec!["Line1", "Line2", "Line3"]);
+        assert_eq!(command_history.borrow().commands.len(), 0);
+    }
+
+    #[rstest]
+    fn split_counts_columns_in_characters(
+        command_history: Rc<RefCell<CommandHistory>>,
+        mock_console: Rc<RefCell<MockConsole>>,
+    ) {
+        let document = Rc::new(RefCell::new(Document::new()));
+        document.borrow_mut().add_line("Grüße".to_string());
+
+        parsed_command("Split 1 4", document.clone(), command_history, &mock_console, None).execute();
+
+        assert_eq!(document.borrow().content(), &vec!["Grü", "ße"]);
+    }
+}
+
+fn compile_pattern(pattern: &str, case_insensitive: bool) -> Result<Regex, CommandParseError> {
+    RegexBuilder::new(pattern)
+        .case_insensitive(case_insensitive)
+        .build()
+        .map_err(|error| CommandParseError {
+            message: format!("Invalid pattern /{}/: {}", pattern, error),
+        })
+}
+
+/// Lists the matches of a regular expression with their line and column.
+pub struct FindCommand {
+    document: Current<Document>,
+    console: Rc<RefCell<dyn Console>>,
+    regex: Option<Regex>,
+    lines: Option<Range<usize>>,
+}
+
+impl FindCommand {
+    pub fn new(document: impl Into<Current<Document>>, console: Rc<RefCell<dyn Console>>) -> FindCommand {
+        FindCommand {
+            document: document.into(),
+            console,
+            regex: None,
+            lines: None,
+        }
+    }
+
+    fn matches(&self, regex: &Regex) -> Result<Vec<String>, DocumentError> {
+        let document = self.document.get();
+        let document = document.borrow();
+        let lines = self.lines.clone().unwrap_or(0..document.content().len());
+        let start = lines.start;
+
+        let mut matches = Vec::new();
+        for (index, line) in document.lines(lines)?.iter().enumerate() {
+            for found in regex.find_iter(line) {
+                let column = line[..found.start()].chars().count() + 1;
+                matches.push(format!("{}:{}: {}", start + index + 1, column, line));
+            }
+        }
+        Ok(matches)
+    }
+}
+
+impl Command for FindCommand {
+    fn execute(&mut self) {
+        if let Some(regex) = &self.regex {
+            let output = match self.matches(regex) {
+                Ok(matches) if matches.is_empty() => vec!["No matches".to_string()],
+                Ok(matches) => matches,
+                Err(error) => return self.console.as_ref().borrow_mut().print_error(&error.message),
+            };
+
+            let mut console = self.console.as_ref().borrow_mut();
+            for line in output {
+                console.print_line(&line);
+            }
+        }
+    }
+
+    fn parse(&mut self, command: &str) -> Result<(), CommandParseError> {
+        let arguments = self.signature().parse(command)?;
+        self.regex = Some(compile_pattern(arguments.text("regex").unwrap_or_default(), false)?);
+        self.lines = arguments.range("lines");
+        Ok(())
+    }
+
+    fn signature(&self) -> Signature {
+        Signature::new("Find", "Lists the matches of a regular expression as line:column")
+            .with(Parameter::required("regex", ParameterKind::Pattern))
+            .with(Parameter::optional("lines", ParameterKind::Range))
+    }
+}
+
+/// Replaces the matches of a regular expression, by default the first one on each line.
+/// The replacement can refer to capture groups as `$1` or `${name}`.
+#[derive(Clone)]
+pub struct ReplaceCommand {
+    document: Current<Document>,
+    command_history: Current<CommandHistory>,
+    console: Rc<RefCell<dyn Console>>,
+    regex: Option<Regex>,
+    replacement: String,
+    lines: Option<Range<usize>>,
+    global: bool,
+    /// The lines before the replacement, to undo only what changed.
+    replaced: Vec<(usize, String)>,
+}
+
+impl ReplaceCommand {
+    pub fn new(
+        document: impl Into<Current<Document>>,
+        command_history: impl Into<Current<CommandHistory>>,
+        console: Rc<RefCell<dyn Console>>,
+    ) -> ReplaceCommand {
+        ReplaceCommand {
+            document: document.into(),
+            command_history: command_history.into(),
+            console,
+            regex: None,
+            replacement: String::new(),
+            lines: None,
+            global: false,
+            replaced: Vec::new(),
+        }
+    }
+
+    /// Replaces on every line in range, returning the number of replaced matches.
+    fn replace(&mut self, regex: &Regex) -> Result<usize, DocumentError> {
+        let document = self.document.get();
+        let mut document = document.borrow_mut();
+        let lines = self.lines.clone().unwrap_or(0..document.content().len());
+        let limit = if self.global { 0 } else { 1 };
+
+        let mut count = 0;
+        let mut changed = Vec::new();
+        for (index, line) in document.lines(lines.clone())?.iter().enumerate() {
+            let matches = regex.find_iter(line).count();
+            if matches > 0 {
+                count += if self.global { matches } else { 1 };
+                let line = regex.replacen(line, limit, self.replacement.as_str());
+                changed.push((lines.start + index, line.into_owned()));
+            }
+        }
+
+        self.replaced = changed
+            .into_iter()
+            .map(|(index, line)| document.replace_line(index, line).map(|old| (index, old)))
+            .collect::<Result<_, _>>()?;
+        Ok(count)
+    }
+}
+
+impl Command for ReplaceCommand {
+    fn execute(&mut self) {
+        let Some(regex) = self.regex.clone() else {
+            return;
+        };
+
+        let result = match self.replace(&regex) {
+            Ok(0) => {
+                self.console.as_ref().borrow_mut().print_line("No matches");
+                return;
+            }
+            Ok(count) => {
+                let message = format!("Replaced {} matches on {} lines", count, self.replaced.len());
+                self.console.as_ref().borrow_mut().print_line(&message);
+                Ok(())
+            }
+            Err(error) => Err(error),
+        };
+        record(self, &self.command_history.get(), &*self.console, result);
+    }
+
+    fn parse(&mut self, command: &str) -> Result<(), CommandParseError> {
+        let arguments = self.signature().parse(command)?;
+        let flags = arguments.text("flags").unwrap_or_default();
+        self.regex = Some(compile_pattern(
+            arguments.text("regex").unwrap_or_default(),
+            flags.contains('i'),
+        )?);
+        self.replacement = arguments.text("replacement").unwrap_or_default().to_string();
+        self.lines = arguments.range("lines");
+        self.global = flags.contains('g');
+        Ok(())
+    }
+
+    fn signature(&self) -> Signature {
+        Signature::new(
+            "Replace",
+            "Replaces regular expression matches, g: all on each line, i: ignoring case",
+        )
+        .with(Parameter::required("regex", ParameterKind::Pattern))
+        .with(Parameter::required("replacement", ParameterKind::Word))
+        .with(Parameter::optional("lines", ParameterKind::Range))
+        .with(Parameter::optional("flags", ParameterKind::Flags("gi")))
+    }
+}
+
+impl ReversibleCommand for ReplaceCommand {
+    fn undo(&mut self) {
+        let document = self.document.get();
+        let mut document = document.borrow_mut();
+        for (index, line) in std::mem::take(&mut self.replaced) {
+            let _ = document.replace_line(index, line);
+        }
+    }
+
+    fn clone(&self) -> Box<dyn ReversibleCommand> {
+        Box::new(Clone::clone(self))
+    }
+}
+
+#[cfg(test)]
+mod tests_regex_commands {
+    use super::test_commands_helpers::{command_history, mock_console, parsed_command};
+    use crate::commands::{
+        Command, CommandHistory, CommandParseError, FindCommand, ReplaceCommand,
+    };
+    use crate::console::MockConsole;
+    use crate::document::Document;
+    use mockall::{predicate::eq, Sequence};
+    use rstest::{fixture, rstest};
+    use std::{cell::RefCell, rc::Rc};
+
+    #[fixture]
+    fn document() -> Rc<RefCell<Document>> {
+        let mut doc = Document::new();
+        doc.add_line("cat 12 dog 7".to_string());
+        doc.add_line("no numbers".to_string());
+        doc.add_line("Cat 3 cat".to_string());
+        Rc::new(RefCell::new(doc))
+    }
+
+    fn expect_lines(console: &Rc<RefCell<MockConsole>>, lines: &[&str]) {
+        let mut seq = Sequence::new();
+        for line in lines {
+            console
+                .borrow_mut()
+                .expect_print_line()
+                .with(eq(line.to_string()))
+                .times(1)
+                .in_sequence(&mut seq)
+                .returning(|_| ());
+        }
+    }
+
+    #[rstest]
+    #[case("Find /\\d+/", &["1:5: cat 12 dog 7", "1:12: cat 12 dog 7", "3:5: Cat 3 cat"])]
+    #[case("Find /cat/ 2..3", &["3:7: Cat 3 cat"])]
+    #[case("Find /(?i)cat/ 3", &["3:1: Cat 3 cat", "3:7: Cat 3 cat"])]
+    #[case("Find /bird/", &["No matches"])]
+    fn find_lists_matches_with_line_and_column(
+        #[case] command: &str,
+        #[case] output: &[&str],
+        document: Rc<RefCell<Document>>,
+        mock_console: Rc<RefCell<MockConsole>>,
+    ) {
+        expect_lines(&mock_console, output);
+        let mut find_cmd = FindCommand::new(document, mock_console.clone());
+
+        find_cmd.parse(command).unwrap();
+        find_cmd.execute();
+    }
+
+    #[rstest]
+    fn find_reports_column_in_characters(mock_console: Rc<RefCell<MockConsole>>) {
+        let document = Rc::new(RefCell::new(Document::new()));
+        document.borrow_mut().add_line("Größe 42".to_string());
+        expect_lines(&mock_console, &["1:7: Größe 42"]);
+        let mut find_cmd = FindCommand::new(document, mock_console.clone());
+
+        find_cmd.parse("Find /42/").unwrap();
+        find_cmd.execute();
+    }
+
+    #[rstest]
+    fn parsing_invalid_pattern(
+        document: Rc<RefCell<Document>>,
+        mock_console: Rc<RefCell<MockConsole>>,
+    ) {
+        let mut find_cmd = FindCommand::new(document, mock_console);
+
+        let error: CommandParseError = find_cmd.parse("Find /(unclosed/").unwrap_err();
+
+        assert!(error.message.starts_with("Invalid pattern /(unclosed/: "));
+    }
+
+    #[rstest]
+    #[case("Replace /cat/ cow", "Replaced 2 matches on 2 lines", vec!["cow 12 dog 7", "no numbers", "Cat 3 cow"])]
+    #[case("Replace /cat/ cow g", "Replaced 2 matches on 2 lines", vec!["cow 12 dog 7", "no numbers", "Cat 3 cow"])]
+    #[case("Replace /cat/ cow gi", "Replaced 3 matches on 2 lines", vec!["cow 12 dog 7", "no numbers", "cow 3 cow"])]
+    #[case("Replace /cat/ cow 3 i", "Replaced 1 matches on 1 lines", vec!["cat 12 dog 7", "no numbers", "cow 3 cat"])]
+    #[case("Replace /(\\w+) (\\d+)/ \"$2 ${1}s\" g", "Replaced 3 matches on 2 lines", vec!["12 cats 7 dogs", "no numbers", "3 Cats cat"])]
+    fn replace_substitutes_matches_and_undo_restores_them(
+        #[case] command: &str,
+        #[case] message: &str,
+        #[case] expected: Vec<&str>,
+        document: Rc<RefCell<Document>>,
+        command_history: Rc<RefCell<CommandHistory>>,
+        mock_console: Rc<RefCell<MockConsole>>,
+    ) {
+        expect_lines(&mock_console, &[message]);
+        let mut replace_cmd = ReplaceCommand::new(document.clone(), command_history.clone(), mock_console.clone());
+
+        replace_cmd.parse(command).unwrap();
+        replace_cmd.execute();
+        assert_eq!(document.borrow().content(), &expected);
+
+        command_history.borrow_mut().undo();
+        assert_eq!(
+            document.borrow().content(),
+            &vec!["cat 12 dog 7", "no numbers", "Cat 3 cat"]
+        );
+    }
+
+    #[rstest]
+    fn replace_keeps_only_the_changed_lines_for_undo(
+        document: Rc<RefCell<Document>>,
+        command_history: Rc<RefCell<CommandHistory>>,
+        mock_console: Rc<RefCell<MockConsole>>,
+    ) {
+        mock_console.borrow_mut().expect_print_line().returning(|_| ());
+        let mut replace_cmd = ReplaceCommand::new(document.clone(), command_history.clone(), mock_console.clone());
+
+        replace_cmd.parse("Replace /dog/ wolf").unwrap();
+        replace_cmd.execute();
+
+        assert_eq!(replace_cmd.replaced, vec![(0, "cat 12 dog 7".to_string())]);
+    }
+
+    #[rstest]
+    #[case("Find /cat/ 4")]
+    #[case("Replace /cat/ cow 2..5")]
+    fn lines_out_of_range_are_reported_as_errors(
+        #[case] command: &str,
+        document: Rc<RefCell<Document>>,
+        command_history: Rc<RefCell<CommandHistory>>,
+        mock_console: Rc<RefCell<MockConsole>>,
+    ) {
+        mock_console
+            .borrow_mut()
+            .expect_print_error()
+            .with(eq("Line 4 does not exist, the document has 3 lines"))
+            .times(1)
+            .returning(|_| ());
+        parsed_command(command, document.clone(), command_history.clone(), &mock_console, None).execute();
+
+        assert_eq!(command_history.borrow().commands.len(), 0);
+        assert_eq!(
+            document.borrow().content(),
+            &vec!["cat 12 dog 7", "no numbers", "Cat 3 cat"]
+        );
+    }
+
+    #[rstest]
+    fn replace_without_matches_is_not_recorded(
+        document: Rc<RefCell<Document>>,
+        command_history: Rc<RefCell<CommandHistory>>,
+        mock_console: Rc<RefCell<MockConsole>>,
+    ) {
+        let command = "Replace /bird/ fish";
+        expect_lines(&mock_console, &["No matches"]);
+        let mut replace_cmd = ReplaceCommand::new(document.clone(), command_history.clone(), mock_console.clone());
+
+        replace_cmd.parse(command).unwrap();
+        replace_cmd.execute();
+
+        assert_eq!(command_history.borrow().commands.len(), 0);
+        assert_eq!(
+            document.borrow().content(),
+            &vec!["cat 12 dog 7", "no numbers", "Cat 3 cat"]
+        );
+    }
+}
+
+/// A command to replace the document with the content of a file.
+/// The history is cleared, since its commands refer to the replaced content.
+pub struct OpenCommand {
+    document: Current<Document>,
+    command_history: Current<CommandHistory>,
+    console: Rc<RefCell<dyn Console>>,
+    path: Option<PathBuf>,
+}
+
+impl OpenCommand {
+    pub fn new(
+        document: impl Into<Current<Document>>,
+        command_history: impl Into<Current<CommandHistory>>,
+        console: Rc<RefCell<dyn Console>>,
+    ) -> OpenCommand {
+        OpenCommand {
+            document: document.into(),
+            command_history: command_history.into(),
+            console,
+            path: None,
+        }
+    }
+}
+
+impl Command for OpenCommand {
+    fn execute(&mut self) {
+        let Some(path) = &self.path else {
+            return;
+        };
+
+        let format = FileFormat::detect(path);
+        let mut console = self.console.as_ref().borrow_mut();
+        match format.read(path) {
+            Ok(lines) => {
+                let count = lines.len();
+                self.document.get().borrow_mut().load(path, lines);
+                self.command_history.get().borrow_mut().clear();
+                console.print_line(&format!("Opened {} ({}, {} lines)", path.display(), format, count));
+            }
+            Err(error) => console.print_error(&format!("Cannot open {}: {}", path.display(), error)),
+        }
+    }
+
+    fn parse(&mut self, command: &str) -> Result<(), CommandParseError> {
+        let arguments = self.signature().parse(command)?;
+        self.path = arguments.text("path").map(PathBuf::from);
+        Ok(())
+    }
+
+    fn signature(&self) -> Signature {
+        Signature::new("Open", "Replaces the document with the content of a file")
+            .with(Parameter::required("path", ParameterKind::Word))
+    }
+}
+
+/// A command to write the document to the file it came from, or to a given path.
+pub struct SaveCommand {
+    document: Current<Document>,
+    console: Rc<RefCell<dyn Console>>,
+    path: Option<PathBuf>,
+}
+
+impl SaveCommand {
+    pub fn new(document: impl Into<Current<Document>>, console: Rc<RefCell<dyn Console>>) -> SaveCommand {
+        SaveCommand {
+            document: document.into(),
+            console,
+            path: None,
+        }
+    }
+}
+
+impl Command for SaveCommand {
+    fn execute(&mut self) {
+        let path = self
+            .path
+            .clone()
+            .or_else(|| self.document.get().borrow().path().map(Path::to_path_buf));
+
+        let result = match path {
+            Some(path) => save_document(&mut self.document.get().borrow_mut(), &path),
+            None => Err("The document has no file yet, use SaveAs <path>".to_string()),
+        };
+        print_result(&*self.console, result);
+    }
+
+    fn parse(&mut self, command: &str) -> Result<(), CommandParseError> {
+        let arguments = self.signature().parse(command)?;
+        self.path = arguments.text("path").map(PathBuf::from);
+        Ok(())
+    }
+
+    fn signature(&self) -> Signature {
+        Signature::new("Save", "Saves the document to its file, or to the given path")
+            .with(Parameter::optional("path", ParameterKind::Word))
+    }
+}
+
+/// A command to write the document to a new file, which becomes the document's file.
+pub struct SaveAsCommand {
+    document: Current<Document>,
+    console: Rc<RefCell<dyn Console>>,
+    path: Option<PathBuf>,
+}
+
+impl SaveAsCommand {
+    pub fn new(document: impl Into<Current<Document>>, console: Rc<RefCell<dyn Console>>) -> SaveAsCommand {
+        SaveAsCommand {
+            document: document.into(),
+            console,
+            path: None,
+        }
+    }
+}
+
+impl Command for SaveAsCommand {
+    fn execute(&mut self) {
+        if let Some(path) = &self.path {
+            let result = save_document(&mut self.document.get().borrow_mut(), path);
+            print_result(&*self.console, result);
+        }
+    }
+
+    fn parse(&mut self, command: &str) -> Result<(), CommandParseError> {
+        let arguments = self.signature().parse(command)?;
+        self.path = arguments.text("path").map(PathBuf::from);
+        Ok(())
+    }
+
+    fn signature(&self) -> Signature {
+        Signature::new("SaveAs", "Saves the document to a new file")
+            .with(Parameter::required("path", ParameterKind::Word))
+    }
+}
+
+fn save_document(document: &mut Document, path: &Path) -> Result<String, String> {
+    let format = FileFormat::detect(path);
+    match format.write(path, document.content()) {
+        Ok(()) => {
+            document.mark_saved(path);
+            Ok(format!(
+                "Saved {} ({}, {} lines)",
+                path.display(),
+                format,
+                document.content().len()
+            ))
+        }
+        Err(error) => Err(format!("Cannot save {}: {}", path.display(), error)),
+    }
+}
+
+/// Prints the message of a successful command, or the error of a failed one.
+fn print_result(console: &RefCell<dyn Console>, result: Result<String, String>) {
+    match result {
+        Ok(message) => console.borrow_mut().print_line(&message),
+        Err(message) => console.borrow_mut().print_error(&message),
+    }
+}
+
+#[cfg(test)]
+mod tests_file_commands {
+    use super::test_commands_helpers::{command_history, document, mock_console};
+    use crate::commands::{
+        AddTextCommand, Command, CommandHistory, CommandParseError, OpenCommand, SaveAsCommand,
+        SaveCommand,
+    };
+    use crate::console::MockConsole;
+    use crate::document::Document;
+    use crate::storage::tests_storage::TempFile;
+    use mockall::predicate::{eq, str::starts_with};
+    use rstest::rstest;
+    use std::path::Path;
+    use std::{cell::RefCell, rc::Rc};
+
+    #[rstest]
+    #[case("notes.txt", "Saved {} (text, 3 lines)")]
+    #[case("notes.md", "Saved {} (Markdown, 3 lines)")]
+    #[case("notes.json", "Saved {} (JSON, 3 lines)")]
+    fn save_as_writes_file_and_open_reads_it_back(
+        #[case] name: &str,
+        #[case] message: &str,
+        document: Rc<RefCell<Document>>,
+        command_history: Rc<RefCell<CommandHistory>>,
+        mock_console: Rc<RefCell<MockConsole>>,
+    ) {
+        let file = TempFile::new(name);
+        let saved = message.replace("{}", &file.0.display().to_string());
+        let opened = saved.replace("Saved", "Opened");
+        mock_console
+            .borrow_mut()
+            .expect_print_line()
+            .with(eq(saved))
+            .times(1)
+            .returning(|_| ());
+        mock_console
+            .borrow_mut()
+            .expect_print_line()
+            .with(eq(opened))
+            .times(1)
+            .returning(|_| ());
+
+        let mut save_as_cmd = SaveAsCommand::new(document.clone(), mock_console.clone());
+        save_as_cmd
+            .parse(&format!("SaveAs {}", file.0.display()))
+            .unwrap();
+        save_as_cmd.execute();
+
+        let opened_document = Rc::new(RefCell::new(Document::new()));
+        let mut open_cmd = OpenCommand::new(
+            opened_document.clone(),
+            command_history.clone(),
+            mock_console.clone(),
+        );
+        open_cmd.parse(&format!("Open {}", file.0.display())).unwrap();
+        open_cmd.execute();
+
+        assert_eq!(
+            opened_document.borrow().content(),
+            &vec!["Line1", "Line2", "Line3"]
+        );
+        assert_eq!(opened_document.borrow().path(), Some(file.0.as_path()));
+    }
+
+    #[rstest]
+    fn save_writes_to_the_document_file(
+        document: Rc<RefCell<Document>>,
+        command_history: Rc<RefCell<CommandHistory>>,
+        mock_console: Rc<RefCell<MockConsole>>,
+    ) {
+        let file = TempFile::new("save.txt");
+        document.borrow_mut().mark_saved(&file.0);
+        AddTextCommand::new(document.clone(), command_history.clone())
+            .with_text("Line4")
+            .execute();
+        assert!(document.borrow().is_modified());
+        mock_console
+            .borrow_mut()
+            .expect_print_line()
+            .with(starts_with("Saved"))
+            .times(1)
+            .returning(|_| ());
+
+        let mut save_cmd = SaveCommand::new(document.clone(), mock_console.clone());
+        save_cmd.parse("Save").unwrap();
+        save_cmd.execute();
+
+        assert!(!document.borrow().is_modified());
+        assert_eq!(
+            std::fs::read_to_string(&file.0).unwrap(),
+            "Line1\nLine2\nLine3\nLine4\n"
+        );
+    }
+
+    #[rstest]
+    fn save_without_file_asks_for_a_path(
+        document: Rc<RefCell<Document>>,
+        mock_console: Rc<RefCell<MockConsole>>,
+    ) {
+        mock_console
+            .borrow_mut()
+            .expect_print_error()
+            .with(eq("The document has no file yet, use SaveAs <path>"))
+            .times(1)
+            .returning(|_| ());
+
+        let mut save_cmd = SaveCommand::new(document.clone(), mock_console.clone());
+        save_cmd.parse("Save").unwrap();
+        save_cmd.execute();
+    }
+
+    #[rstest]
+    fn save_reports_io_errors_on_console(
+        document: Rc<RefCell<Document>>,
+        mock_console: Rc<RefCell<MockConsole>>,
+    ) {
+        mock_console
+            .borrow_mut()
+            .expect_print_error()
+            .with(starts_with("Cannot save /nonexistent/dir/notes.txt: "))
+            .times(1)
+            .returning(|_| ());
+
+        let mut save_cmd = SaveCommand::new(document.clone(), mock_console.clone());
+        save_cmd.parse("Save /nonexistent/dir/notes.txt").unwrap();
+        save_cmd.execute();
+
+        assert!(document.borrow().path().is_none());
+    }
+
+    #[rstest]
+    fn open_reports_io_errors_on_console_and_keeps_document(
+        document: Rc<RefCell<Document>>,
+        command_history: Rc<RefCell<CommandHistory>>,
+        mock_console: Rc<RefCell<MockConsole>>,
+    ) {
+        mock_console
+            .borrow_mut()
+            .expect_print_error()
+            .with(starts_with("Cannot open missing.txt: "))
+            .times(1)
+            .returning(|_| ());
+
+        let mut open_cmd = OpenCommand::new(document.clone(), command_history, mock_console.clone());
+        open_cmd.parse("Open missing.txt").unwrap();
+        open_cmd.execute();
+
+        assert_eq!(
+            document.borrow().content(),
+            &vec!["Line1", "Line2", "Line3"]
+        );
+    }
+
+    #[rstest]
+    fn open_clears_command_history(
+        document: Rc<RefCell<Document>>,
+        command_history: Rc<RefCell<CommandHistory>>,
+        mock_console: Rc<RefCell<MockConsole>>,
+    ) {
+        let file = TempFile::new("history.txt");
+        std::fs::write(&file.0, "Opened\n").unwrap();
+        mock_console.borrow_mut().expect_print_line().returning(|_| ());
+        AddTextCommand::new(document.clone(), command_history.clone())
+            .with_text("Line4")
+            .execute();
+
+        let mut open_cmd = OpenCommand::new(document.clone(), command_history.clone(), mock_console.clone());
+        open_cmd.parse(&format!("Open {}", file.0.display())).unwrap();
+        open_cmd.execute();
+
+        assert_eq!(command_history.borrow().commands.len(), 0);
+        assert_eq!(document.borrow().content(), &vec!["Opened"]);
+    }
+
+    #[rstest]
+    fn parsing_missing_path(
+        document: Rc<RefCell<Document>>,
+        command_history: Rc<RefCell<CommandHistory>>,
+        mock_console: Rc<RefCell<MockConsole>>,
+    ) {
+        let mut open_cmd = OpenCommand::new(document.clone(), command_history, mock_console.clone());
+        let mut save_as_cmd = SaveAsCommand::new(document.clone(), mock_console.clone());
+
+        assert_eq!(
+            open_cmd.parse("Open"),
+            Err(CommandParseError {
+                message: "Missing <path>. Usage: Open <path>".to_string(),
+            })
+        );
+        assert_eq!(
+            save_as_cmd.parse("SaveAs  "),
+            Err(CommandParseError {
+                message: "Missing <path>. Usage: SaveAs <path>".to_string(),
+            })
+        );
+    }
+
+    #[rstest]
+    fn parsing_quoted_path_with_spaces(
+        document: Rc<RefCell<Document>>,
+        mock_console: Rc<RefCell<MockConsole>>,
+    ) {
+        let mut save_cmd = SaveCommand::new(document.clone(), mock_console.clone());
+
+        save_cmd.parse("Save \"my notes.md\"").unwrap();
+
+        assert_eq!(save_cmd.path.as_deref(), Some(Path::new("my notes.md")));
+    }
+}
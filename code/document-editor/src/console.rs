@@ -1,15 +1,97 @@
+use std::io::{BufRead, Write};
+
 use mockall::{automock};
 
 #[automock]
 pub trait Console {
     fn read_line(&mut self) -> String;
+    /// Asks for the next line; consoles that are not interactive leave it out.
+    fn prompt(&mut self, text: &str);
     fn print_line(&mut self, line: &str);
+    /// Reports a command that could not be carried out.
+    fn print_error(&mut self, message: &str);
+}
+
+/// A console that reads command lines from a script and writes the output to a stream,
+/// for running the editor without interaction.
+///
+/// Blank lines and lines starting with `#` are skipped. Once a command fails, a line cannot
+/// be read or the output cannot be written, or the script ends, every line read is `Exit`.
+/// Errors are not written to the output but kept, to be reported once with where they happened.
+pub struct ScriptConsole<R, W> {
+    name: String,
+    lines: std::io::Lines<R>,
+    output: W,
+    output_failed: bool,
+    line_number: usize,
+    errors: Vec<String>,
+}
+
+impl<R: BufRead, W: Write> ScriptConsole<R, W> {
+    pub fn new(name: &str, input: R, output: W) -> ScriptConsole<R, W> {
+        ScriptConsole {
+            name: name.to_string(),
+            lines: input.lines(),
+            output,
+            output_failed: false,
+            line_number: 0,
+            errors: Vec::new(),
+        }
+    }
+
+    /// The errors, each prefixed with the script name and the number of the failed line,
+    /// if the script had started.
+    pub fn errors(&self) -> &[String] {
+        &self.errors
+    }
+}
+
+impl<R: BufRead, W: Write> Console for ScriptConsole<R, W> {
+    fn read_line(&mut self) -> String {
+        while self.errors.is_empty() {
+            let Some(line) = self.lines.next() else {
+                break;
+            };
+            self.line_number += 1;
+            let line = match line {
+                Ok(line) => line,
+                Err(error) => {
+                    self.print_error(&format!("Cannot read the line: {}", error));
+                    break;
+                }
+            };
+
+            let line = line.trim();
+            if !line.is_empty() && !line.starts_with('#') {
+                return line.to_string();
+            }
+        }
+        "Exit".to_string()
+    }
+
+    fn prompt(&mut self, _text: &str) {}
+
+    fn print_line(&mut self, line: &str) {
+        // the first failure is enough, the output is lost from there on
+        if let Err(error) = writeln!(self.output, "{}", line) {
+            if !std::mem::replace(&mut self.output_failed, true) {
+                self.print_error(&format!("Cannot write the output: {}", error));
+            }
+        }
+    }
+
+    fn print_error(&mut self, message: &str) {
+        self.errors.push(match self.line_number {
+            0 => format!("{}: {}", self.name, message),
+            line_number => format!("{}:{}: {}", self.name, line_number, message),
+        });
+    }
 }
 
 #[cfg(test)]
 pub(crate) mod tests_console {
     use std::{cell::RefCell, rc::Rc};
-    use super::{MockConsole};
+    use super::{Console, MockConsole, ScriptConsole};
 
     use mockall::predicate::str::contains;
     use rstest::{fixture};
@@ -19,10 +101,98 @@ pub(crate) mod tests_console {
         let mock = Rc::new(RefCell::new(MockConsole::new()));
         mock.as_ref()
             .borrow_mut()
-            .expect_print_line()
+            .expect_prompt()
             .with(contains("Enter a command: "))
             .times(1..)
             .returning(|_| ());
         mock
     }
+
+    #[test]
+    fn script_console_reads_commands_and_then_exit() {
+        let script = "AddText one\n\n  # a comment\n  Print  \n";
+        let mut console = ScriptConsole::new("edit.cmds", script.as_bytes(), Vec::new());
+
+        assert_eq!(console.read_line(), "AddText one");
+        assert_eq!(console.read_line(), "Print");
+        assert_eq!(console.read_line(), "Exit");
+        assert_eq!(console.read_line(), "Exit");
+    }
+
+    #[test]
+    fn script_console_writes_output_without_prompts_or_errors() {
+        let mut output = Vec::new();
+        {
+            let mut console = ScriptConsole::new("edit.cmds", "".as_bytes(), &mut output);
+            console.prompt("Enter a command: ");
+            console.print_line("Line1");
+            console.print_error("Line 9 does not exist");
+        }
+
+        assert_eq!(String::from_utf8(output).unwrap(), "Line1\n");
+    }
+
+    #[test]
+    fn script_console_keeps_errors_and_stops_reading() {
+        let script = "Delete 9\nDelete 8\n\nPrint\n";
+        let mut console = ScriptConsole::new("edit.cmds", script.as_bytes(), Vec::new());
+        assert!(console.errors().is_empty());
+
+        console.read_line();
+        console.print_error("Line 9 does not exist");
+        console.print_error("Line 8 does not exist");
+
+        assert_eq!(console.read_line(), "Exit");
+        assert_eq!(
+            console.errors(),
+            ["edit.cmds:1: Line 9 does not exist", "edit.cmds:1: Line 8 does not exist"]
+        );
+    }
+
+    #[test]
+    fn script_console_reports_unreadable_line_and_stops_reading() {
+        let script: &[u8] = b"Print\n\xff\nPrint\n";
+        let mut console = ScriptConsole::new("edit.cmds", script, Vec::new());
+
+        assert_eq!(console.read_line(), "Print");
+        assert_eq!(console.read_line(), "Exit");
+        assert_eq!(
+            console.errors(),
+            ["edit.cmds:2: Cannot read the line: stream did not contain valid UTF-8"]
+        );
+    }
+
+    struct FullDisk;
+
+    impl std::io::Write for FullDisk {
+        fn write(&mut self, _buf: &[u8]) -> std::io::Result<usize> {
+            Err(std::io::Error::other("no space left"))
+        }
+
+        fn flush(&mut self) -> std::io::Result<()> {
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn script_console_reports_first_write_failure_and_stops_reading() {
+        let mut console = ScriptConsole::new("edit.cmds", "Print\nPrint\n".as_bytes(), FullDisk);
+
+        assert_eq!(console.read_line(), "Print");
+        console.print_line("Line1");
+        console.print_line("Line2");
+
+        assert_eq!(console.read_line(), "Exit");
+        assert_eq!(console.errors(), ["edit.cmds:1: Cannot write the output: no space left"]);
+    }
+
+    #[test]
+    fn script_console_reports_failure_before_the_first_line_without_number() {
+        let mut console = ScriptConsole::new("edit.cmds", "Print\n".as_bytes(), Vec::new());
+
+        console.print_error("Cannot load plugin");
+
+        assert_eq!(console.read_line(), "Exit");
+        assert_eq!(console.errors(), ["edit.cmds: Cannot load plugin"]);
+    }
 }
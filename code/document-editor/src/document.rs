@@ -1,53 +1,151 @@
+use std::ops::Range;
+use std::path::{Path, PathBuf};
+
 pub struct Document {
     content: Vec<String>,
+    path: Option<PathBuf>,
+    modified: bool,
 }
 
 impl Document {
     pub fn new() -> Document {
-        Document { content: vec![] }
+        Document {
+            content: vec![],
+            path: None,
+            modified: false,
+        }
     }
 
     pub fn content(&self) -> &Vec<String> {
         &self.content
     }
 
+    /// The file the document was last opened from or saved to.
+    pub fn path(&self) -> Option<&Path> {
+        self.path.as_deref()
+    }
+
+    /// Whether the content changed since the document was last opened or saved.
+    pub fn is_modified(&self) -> bool {
+        self.modified
+    }
+
+    /// Replaces the content with lines read from `path`.
+    pub fn load(&mut self, path: &Path, content: Vec<String>) {
+        self.content = content;
+        self.mark_saved(path);
+    }
+
+    pub fn mark_saved(&mut self, path: &Path) {
+        self.path = Some(path.to_path_buf());
+        self.modified = false;
+    }
+
     pub fn add_line(&mut self, line: String) {
         self.content.push(line);
+        self.modified = true;
     }
 
-    pub fn insert_line(&mut self, index: usize, line: String) {
-        self.content.insert(index, line);
+    /// The lines in `range`, an error if the range goes beyond the last line.
+    pub fn lines(&self, range: Range<usize>) -> Result<&[String], DocumentError> {
+        self.check_range(&range)?;
+        Ok(&self.content[range])
     }
 
-    pub fn clear(&mut self) {
-        self.content.clear();
+    /// Inserts a line before `index`; an index one past the last line appends.
+    pub fn insert_line(&mut self, index: usize, line: String) -> Result<(), DocumentError> {
+        self.insert_lines(index, vec![line])
     }
 
-    pub fn erase_line(&mut self, index: usize) {
-        self.content.remove(index);
+    pub fn insert_lines(&mut self, index: usize, lines: Vec<String>) -> Result<(), DocumentError> {
+        if index > self.content.len() {
+            return Err(DocumentError {
+                message: format!(
+                    "Cannot insert at line {}, the document has {} lines",
+                    index.saturating_add(1),
+                    self.content.len()
+                ),
+            });
+        }
+        self.content.splice(index..index, lines);
+        self.modified = true;
+        Ok(())
     }
 
-    pub fn replace_text(&mut self, old_text: &str, new_text: &str) {
-        for line in self.content.iter_mut() {
-            *line = line.replace(old_text, new_text);
-        }
+    /// Removes every line and returns them.
+    pub fn clear(&mut self) -> Vec<String> {
+        self.modified |= !self.content.is_empty();
+        std::mem::take(&mut self.content)
+    }
+
+    /// Removes the line at `index` and returns it.
+    pub fn erase_line(&mut self, index: usize) -> Result<String, DocumentError> {
+        let mut lines = self.erase_lines(index..index.saturating_add(1))?;
+        Ok(lines.remove(0))
+    }
+
+    /// Replaces the line at `index` and returns the previous one.
+    pub fn replace_line(&mut self, index: usize, line: String) -> Result<String, DocumentError> {
+        self.check_range(&(index..index.saturating_add(1)))?;
+        self.modified = true;
+        Ok(std::mem::replace(&mut self.content[index], line))
+    }
+
+    /// Removes the lines in `range` and returns them.
+    pub fn erase_lines(&mut self, range: Range<usize>) -> Result<Vec<String>, DocumentError> {
+        self.check_range(&range)?;
+        self.modified = true;
+        Ok(self.content.drain(range).collect())
     }
 
-    pub fn create_snapshot(&self) -> DocumentSnapshot {
-        bincode::serialize(&self.content).unwrap()
+    /// Replaces every occurrence of `old_text` and returns the changed lines as they were,
+    /// with their indexes.
+    pub fn replace_text(&mut self, old_text: &str, new_text: &str) -> Vec<(usize, String)> {
+        let mut replaced = Vec::new();
+        for (index, line) in self.content.iter_mut().enumerate() {
+            let new_line = line.replace(old_text, new_text);
+            if new_line != *line {
+                replaced.push((index, std::mem::replace(line, new_line)));
+            }
+        }
+        self.modified |= !replaced.is_empty();
+        replaced
     }
 
-    pub fn restore_snapshot(&mut self, snapshot: DocumentSnapshot) {
-        self.content = bincode::deserialize(&snapshot).unwrap();
+    fn check_range(&self, range: &Range<usize>) -> Result<(), DocumentError> {
+        if range.start > range.end {
+            return Err(DocumentError {
+                message: format!(
+                    "Line {} comes after line {}",
+                    range.start.saturating_add(1),
+                    range.end
+                ),
+            });
+        }
+        if range.end > self.content.len() {
+            return Err(DocumentError {
+                message: format!(
+                    "Line {} does not exist, the document has {} lines",
+                    self.content.len().max(range.start).saturating_add(1),
+                    self.content.len()
+                ),
+            });
+        }
+        Ok(())
     }
 }
 
-pub type DocumentSnapshot = Vec<u8>;
+#[derive(Debug, PartialEq, Clone)]
+pub struct DocumentError {
+    pub message: String,
+}
 
 #[cfg(test)]
 pub(crate) mod tests_document {
     use rstest::{fixture, rstest};
-    use crate::document::Document;
+    use crate::document::{Document, DocumentError};
+    use std::ops::Range;
+    use std::path::Path;
 
     #[test]
     fn document_is_empty_on_start() {
@@ -82,7 +180,7 @@ pub(crate) mod tests_document {
 
     #[rstest]
     fn document_inserting_line(mut document: Document) {
-        document.insert_line(1, "Inserted line".to_string());
+        document.insert_line(1, "Inserted line".to_string()).unwrap();
         assert_eq!(
             document.content(),
             &vec!["Line1", "Inserted line", "Line2", "Line3"]
@@ -91,25 +189,118 @@ pub(crate) mod tests_document {
 
     #[rstest]
     fn document_erase_a_line(mut document: Document) {
-        document.erase_line(1);
+        assert_eq!(document.erase_line(1), Ok("Line2".to_string()));
         assert_eq!(document.content(), &vec!["Line1", "Line3"]);
     }
 
+    #[rstest]
+    fn document_inserting_lines_after_the_last_one(mut document: Document) {
+        document
+            .insert_lines(3, vec!["Line4".to_string(), "Line5".to_string()])
+            .unwrap();
+        assert_eq!(
+            document.content(),
+            &vec!["Line1", "Line2", "Line3", "Line4", "Line5"]
+        );
+    }
+
+    #[rstest]
+    fn document_erase_lines(mut document: Document) {
+        assert_eq!(
+            document.erase_lines(0..2),
+            Ok(vec!["Line1".to_string(), "Line2".to_string()])
+        );
+        assert_eq!(document.content(), &vec!["Line3"]);
+    }
+
+    #[rstest]
+    fn document_replace_line(mut document: Document) {
+        assert_eq!(
+            document.replace_line(2, "Replaced".to_string()),
+            Ok("Line3".to_string())
+        );
+        assert_eq!(document.content(), &vec!["Line1", "Line2", "Replaced"]);
+        assert!(document.replace_line(3, "Line4".to_string()).is_err());
+    }
+
+    #[rstest]
+    fn document_out_of_range_lines_are_errors(mut document: Document) {
+        let missing = "Line 4 does not exist, the document has 3 lines";
+
+        assert_eq!(document.erase_line(3).unwrap_err().message, missing);
+        assert_eq!(document.erase_lines(1..5).unwrap_err().message, missing);
+        assert_eq!(document.lines(2..4).unwrap_err().message, missing);
+        assert!(document.erase_line(usize::MAX).is_err());
+        assert!(document.replace_line(usize::MAX, "Line4".to_string()).is_err());
+        assert!(document.insert_line(usize::MAX, "Line4".to_string()).is_err());
+        let reversed = Range { start: 2, end: 1 };
+        assert_eq!(
+            document.lines(reversed.clone()).unwrap_err().message,
+            "Line 3 comes after line 1"
+        );
+        assert!(document.erase_lines(reversed).is_err());
+        assert_eq!(
+            document.insert_line(4, "Line5".to_string()),
+            Err(DocumentError {
+                message: "Cannot insert at line 5, the document has 3 lines".to_string()
+            })
+        );
+        assert_eq!(document.content(), &vec!["Line1", "Line2", "Line3"]);
+    }
+
     #[rstest]
     fn document_replace_text(mut document: Document) {
-        document.replace_text("Line2", "Replaced line");
+        let replaced = document.replace_text("Line2", "Replaced line");
         assert_eq!(document.content(), &vec!["Line1", "Replaced line", "Line3"]);
+        assert_eq!(replaced, vec![(1, "Line2".to_string())]);
     }
 
     #[rstest]
-    fn document_create_snaphot_that_restores_the_state(mut document: Document) {
-        let snapshot = document.create_snapshot();
+    fn document_clear_returns_removed_lines(mut document: Document) {
+        let removed = document.clear();
 
-        document.clear();
         assert!(document.content().is_empty());
+        assert_eq!(removed, vec!["Line1", "Line2", "Line3"]);
+    }
+
+    #[test]
+    fn document_is_unmodified_on_start() {
+        let document = Document::new();
+        assert!(!document.is_modified());
+        assert_eq!(document.path(), None);
+    }
 
-        document.restore_snapshot(snapshot);
+    #[rstest]
+    fn document_editing_marks_it_modified(mut document: Document) {
+        document.mark_saved(Path::new("notes.txt"));
+        assert!(!document.is_modified());
 
-        assert_eq!(document.content(), &vec!["Line1", "Line2", "Line3"]);
+        document.replace_text("Line2", "Replaced line");
+
+        assert!(document.is_modified());
+        assert_eq!(document.path(), Some(Path::new("notes.txt")));
+    }
+
+    #[rstest]
+    fn document_edits_that_change_nothing_keep_it_unmodified(mut document: Document) {
+        document.mark_saved(Path::new("notes.txt"));
+
+        assert!(document.replace_text("Missing", "Replaced").is_empty());
+        assert!(document.replace_text("Line2", "Line2").is_empty());
+        assert!(!document.is_modified());
+
+        document.clear();
+        document.mark_saved(Path::new("notes.txt"));
+        assert!(document.clear().is_empty());
+        assert!(!document.is_modified());
+    }
+
+    #[rstest]
+    fn document_load_replaces_content(mut document: Document) {
+        document.load(Path::new("notes.md"), vec!["# Notes".to_string()]);
+
+        assert_eq!(document.content(), &vec!["# Notes"]);
+        assert_eq!(document.path(), Some(Path::new("notes.md")));
+        assert!(!document.is_modified());
     }
 }
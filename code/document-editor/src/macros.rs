@@ -0,0 +1,155 @@
+use std::collections::BTreeMap;
+use std::fs;
+use std::io;
+use std::path::PathBuf;
+
+use crate::arguments::{Parameter, ParameterKind, Signature};
+
+/// Named sequences of command lines, recorded from the console and kept in a JSON file
+/// that maps each name to its lines.
+#[derive(Default)]
+pub struct Macros {
+    macros: BTreeMap<String, Vec<String>>,
+    recording: Option<(String, Vec<String>)>,
+    path: Option<PathBuf>,
+}
+
+impl Macros {
+    /// Loads the macros saved at `path`, where they are saved again after each recording.
+    /// A missing file means no macros were recorded yet.
+    pub fn load(path: PathBuf) -> io::Result<Macros> {
+        let macros = match fs::read_to_string(&path) {
+            Ok(content) => serde_json::from_str(&content)
+                .map_err(|error| io::Error::new(io::ErrorKind::InvalidData, error))?,
+            Err(error) if error.kind() == io::ErrorKind::NotFound => BTreeMap::new(),
+            Err(error) => return Err(error),
+        };
+
+        Ok(Macros {
+            macros,
+            recording: None,
+            path: Some(path),
+        })
+    }
+
+    pub fn signatures() -> Vec<Signature> {
+        vec![
+            Signature::new("Record", "Records the following commands as a macro")
+                .with(Parameter::required("name", ParameterKind::Word)),
+            Signature::new("Stop", "Stops recording and saves the macro"),
+            Signature::new("Play", "Plays a macro, undone as a single change")
+                .with(Parameter::required("name", ParameterKind::Word))
+                .with(Parameter::optional("times", ParameterKind::Count)),
+        ]
+    }
+
+    pub fn record(&mut self, name: &str) -> Result<(), String> {
+        if let Some((recording, _)) = &self.recording {
+            return Err(format!("Already recording macro '{}'", recording));
+        }
+        self.recording = Some((name.to_string(), Vec::new()));
+        Ok(())
+    }
+
+    /// Adds a command line to the macro being recorded, if any.
+    pub fn add_line(&mut self, line: &str) {
+        if let Some((_, lines)) = &mut self.recording {
+            lines.push(line.to_string());
+        }
+    }
+
+    /// Finishes the recording and returns the macro's name and lines.
+    pub fn stop(&mut self) -> Result<(String, &[String]), String> {
+        let (name, lines) = self.recording.take().ok_or("Not recording a macro")?;
+        self.macros.insert(name.clone(), lines);
+        let lines = &self.macros[&name];
+        Ok((name, lines))
+    }
+
+    pub fn get(&self, name: &str) -> Option<&[String]> {
+        self.macros.get(name).map(Vec::as_slice)
+    }
+
+    /// Writes the macros to the file they were loaded from; in-memory macros are not saved.
+    pub fn save(&self) -> io::Result<()> {
+        match &self.path {
+            Some(path) => fs::write(path, serde_json::to_string_pretty(&self.macros)? + "\n"),
+            None => Ok(()),
+        }
+    }
+
+    pub fn path(&self) -> Option<&PathBuf> {
+        self.path.as_ref()
+    }
+}
+
+#[cfg(test)]
+mod tests_macros {
+    use super::Macros;
+    use crate::storage::tests_storage::TempFile;
+
+    #[test]
+    fn recording_collects_lines_until_stop() {
+        let mut macros = Macros::default();
+        macros.add_line("AddText ignored");
+
+        macros.record("greet").unwrap();
+        macros.add_line("AddText Hello");
+        macros.add_line("AddText World");
+        let (name, lines) = macros.stop().unwrap();
+
+        assert_eq!(name, "greet");
+        assert_eq!(lines, ["AddText Hello", "AddText World"]);
+        assert_eq!(macros.get("greet").unwrap().len(), 2);
+        assert_eq!(macros.get("other"), None);
+    }
+
+    #[test]
+    fn recording_again_replaces_the_macro() {
+        let mut macros = Macros::default();
+        macros.record("m").unwrap();
+        macros.add_line("Print");
+        macros.stop().unwrap();
+
+        macros.record("m").unwrap();
+        macros.stop().unwrap();
+
+        assert_eq!(macros.get("m"), Some(&[][..]));
+    }
+
+    #[test]
+    fn record_and_stop_report_misuse() {
+        let mut macros = Macros::default();
+        assert_eq!(macros.stop().unwrap_err(), "Not recording a macro");
+
+        macros.record("first").unwrap();
+        assert_eq!(
+            macros.record("second"),
+            Err("Already recording macro 'first'".to_string())
+        );
+    }
+
+    #[test]
+    fn saved_macros_are_loaded_again() {
+        let file = TempFile::new("macros.json");
+        let mut macros = Macros::load(file.0.clone()).unwrap();
+        macros.record("greet").unwrap();
+        macros.add_line("AddText \"Hello, world\"");
+        macros.stop().unwrap();
+
+        macros.save().unwrap();
+
+        let loaded = Macros::load(file.0.clone()).unwrap();
+        assert_eq!(loaded.get("greet").unwrap(), ["AddText \"Hello, world\""]);
+    }
+
+    #[test]
+    fn loading_invalid_file_fails() {
+        let file = TempFile::new("invalid-macros.json");
+        std::fs::write(&file.0, "[1, 2]").unwrap();
+
+        let error = Macros::load(file.0.clone()).err().unwrap();
+
+        assert_eq!(error.kind(), std::io::ErrorKind::InvalidData);
+    }
+}
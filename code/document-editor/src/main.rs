@@ -1,38 +1,115 @@
 mod application;
+mod arguments;
+mod buffers;
 mod commands;
 mod console;
 mod document;
+mod macros;
+mod options;
+mod plugins;
+mod storage;
 
-use std::{cell::RefCell, io::BufRead, rc::Rc};
+use std::fs::File;
+use std::io::{BufRead, BufReader, Write};
+use std::path::Path;
+use std::{cell::RefCell, rc::Rc};
 
 use application::ApplicationBuilder;
-use commands::CommandHistory;
-use document::Document;
+use buffers::Buffers;
+use console::{Console, ScriptConsole};
+use macros::Macros;
+use options::Options;
+
+const MACROS_FILE: &str = ".document_editor_macros.json";
 
 struct Terminal {}
 
 impl console::Console for Terminal {
     fn read_line(&mut self) -> String {
         let mut line = String::new();
-        let _ = std::io::stdin().lock().read_line(&mut line);
-        line.trim().to_string()
+        match std::io::stdin().lock().read_line(&mut line) {
+            Ok(0) | Err(_) => "Exit".to_string(),
+            Ok(_) => line.trim().to_string(),
+        }
+    }
+
+    fn prompt(&mut self, text: &str) {
+        println!("{}", text);
     }
 
     fn print_line(&mut self, line: &str) {
         println!("{}", line);
     }
+
+    fn print_error(&mut self, message: &str) {
+        println!("{}", message);
+    }
 }
 
 fn main() {
-    let terminal = Rc::new(RefCell::new(Terminal {}));
-    let doc = Rc::new(RefCell::new(Document::new()));
-    let command_history = Rc::new(RefCell::new(CommandHistory::new()));
+    let options = Options::parse(std::env::args().skip(1)).unwrap_or_else(|message| {
+        eprintln!("{}", message);
+        std::process::exit(2);
+    });
+
+    let status = match &options.script {
+        Some(script) => run_script(script, &options),
+        None => {
+            run(Rc::new(RefCell::new(Terminal {})), &options);
+            0
+        }
+    };
+    std::process::exit(status);
+}
+
+/// Runs the commands of a script, returning the exit status: 1 if a command failed,
+/// 2 if the script could not be run at all.
+fn run_script(script: &Path, options: &Options) -> i32 {
+    let input = match File::open(script) {
+        Ok(input) => BufReader::new(input),
+        Err(error) => {
+            eprintln!("Cannot open {}: {}", script.display(), error);
+            return 2;
+        }
+    };
+    let output: Box<dyn Write> = match &options.output {
+        Some(path) => match File::create(path) {
+            Ok(file) => Box::new(file),
+            Err(error) => {
+                eprintln!("Cannot create {}: {}", path.display(), error);
+                return 2;
+            }
+        },
+        None => Box::new(std::io::stdout()),
+    };
+
+    let console = Rc::new(RefCell::new(ScriptConsole::new(
+        &script.display().to_string(),
+        input,
+        output,
+    )));
+    run(console.clone(), options);
+
+    let errors = console.borrow().errors().to_vec();
+    for error in &errors {
+        eprintln!("{}", error);
+    }
+    if errors.is_empty() { 0 } else { 1 }
+}
+
+fn run(console: Rc<RefCell<dyn Console>>, options: &Options) {
+    let buffers = Rc::new(RefCell::new(Buffers::new(options.history_size)));
+    let doc = Buffers::current_document(&buffers);
+    let command_history = Buffers::current_history(&buffers);
     {
         let mut app = ApplicationBuilder::new()
-            .with_console(terminal.clone())
+            .with_console(console.clone())
+            .with_buffers(buffers.clone())
+            .with_exit_confirmation(options.script.is_none())
+            .with_macros(load_macros(&mut *console.borrow_mut(), options))
             .build();
 
-        let print_command = commands::PrintCommand::new(doc.clone(), terminal.clone());
+        let print_command = commands::PrintCommand::new(doc.clone(), console.clone());
         app.add_command("Print".to_string(), Rc::new(RefCell::new(print_command)));
 
         let add_text_command = commands::AddTextCommand::new(doc.clone(), command_history.clone());
@@ -51,6 +128,80 @@ fn main() {
 
         app.add_command("Undo".to_string(), Rc::new(RefCell::new(commands::UndoCommand::new(command_history.clone()))));
 
+        app.add_command("Redo".to_string(), Rc::new(RefCell::new(commands::RedoCommand::new(command_history.clone()))));
+
+        app.add_command("Begin".to_string(), Rc::new(RefCell::new(commands::BeginCommand::new(command_history.clone(), console.clone()))));
+
+        app.add_command("Commit".to_string(), Rc::new(RefCell::new(commands::CommitCommand::new(command_history.clone(), console.clone()))));
+
+        app.add_command("Rollback".to_string(), Rc::new(RefCell::new(commands::RollbackCommand::new(command_history.clone(), console.clone()))));
+
+        app.add_command("Insert".to_string(), Rc::new(RefCell::new(commands::InsertCommand::new(doc.clone(), command_history.clone(), console.clone()))));
+
+        app.add_command("Delete".to_string(), Rc::new(RefCell::new(commands::DeleteCommand::new(doc.clone(), command_history.clone(), console.clone()))));
+
+        app.add_command("Move".to_string(), Rc::new(RefCell::new(commands::MoveCommand::new(doc.clone(), command_history.clone(), console.clone()))));
+
+        app.add_command("Copy".to_string(), Rc::new(RefCell::new(commands::CopyCommand::new(doc.clone(), command_history.clone(), console.clone()))));
+
+        app.add_command("Join".to_string(), Rc::new(RefCell::new(commands::JoinCommand::new(doc.clone(), command_history.clone(), console.clone()))));
+
+        app.add_command("Split".to_string(), Rc::new(RefCell::new(commands::SplitCommand::new(doc.clone(), command_history.clone(), console.clone()))));
+
+        app.add_command("Find".to_string(), Rc::new(RefCell::new(commands::FindCommand::new(doc.clone(), console.clone()))));
+
+        app.add_command("Replace".to_string(), Rc::new(RefCell::new(commands::ReplaceCommand::new(doc.clone(), command_history.clone(), console.clone()))));
+
+        app.add_command("Open".to_string(), Rc::new(RefCell::new(commands::OpenCommand::new(doc.clone(), command_history.clone(), console.clone()))));
+
+        app.add_command("Save".to_string(), Rc::new(RefCell::new(commands::SaveCommand::new(doc.clone(), console.clone()))));
+
+        app.add_command("SaveAs".to_string(), Rc::new(RefCell::new(commands::SaveAsCommand::new(doc.clone(), console.clone()))));
+
+        app.add_command("New".to_string(), Rc::new(RefCell::new(buffers::NewCommand::new(buffers.clone(), console.clone()))));
+
+        app.add_command("Buffers".to_string(), Rc::new(RefCell::new(buffers::BuffersCommand::new(buffers.clone(), console.clone()))));
+
+        app.add_command("Switch".to_string(), Rc::new(RefCell::new(buffers::SwitchCommand::new(buffers.clone(), console.clone()))));
+
+        app.add_command("Close".to_string(), Rc::new(RefCell::new(buffers::CloseCommand::new(buffers.clone(), console.clone()))));
+
+        for path in &options.plugins {
+            let specs = match plugins::load(path) {
+                Ok(specs) => specs,
+                Err(error) => {
+                    console.borrow_mut().print_error(&format!("Cannot load plugin {}: {}", path.display(), error));
+                    continue;
+                }
+            };
+            for spec in specs {
+                if app.has_command(spec.name()) {
+                    console.borrow_mut().print_error(&format!("Plugin {} redefines the {} command", path.display(), spec.name()));
+                    continue;
+                }
+                let command = plugins::PluginCommand::new(spec.clone(), doc.clone(), command_history.clone(), console.clone());
+                app.add_command(spec.name().to_string(), Rc::new(RefCell::new(command)));
+            }
+        }
+
         app.run();
     }
 }
+
+/// Loads the macros from `--macros`, or from the home directory unless running a script,
+/// so that scripts neither depend on nor change the macros recorded interactively.
+fn load_macros(console: &mut dyn Console, options: &Options) -> Macros {
+    let path = match (&options.macros, &options.script) {
+        (Some(path), _) => Some(path.clone()),
+        (None, Some(_)) => None,
+        (None, None) => std::env::home_dir().map(|home| home.join(MACROS_FILE)),
+    };
+    let Some(path) = path else {
+        return Macros::default();
+    };
+
+    Macros::load(path.clone()).unwrap_or_else(|error| {
+        console.print_error(&format!("Cannot load macros from {}: {}", path.display(), error));
+        Macros::default()
+    })
+}
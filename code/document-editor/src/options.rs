@@ -0,0 +1,130 @@
+use std::path::PathBuf;
+
+/// The command line options of the editor.
+#[derive(Debug, PartialEq)]
+pub struct Options {
+    /// The most changes kept for undo.
+    pub history_size: usize,
+    /// A file of command lines to run instead of reading them from the terminal.
+    pub script: Option<PathBuf>,
+    /// Where the output of a script goes, standard output if not given.
+    pub output: Option<PathBuf>,
+    /// Dynamic libraries with more commands.
+    pub plugins: Vec<PathBuf>,
+    /// Where macros are kept. Without it, the editor keeps them in the home directory
+    /// and a script only in memory.
+    pub macros: Option<PathBuf>,
+}
+
+pub const USAGE: &str =
+    "Usage: document-editor [--history-size <n>] [--script <file> [--output <file>]] [--plugin <file>]... [--macros <file>]";
+
+const DEFAULT_HISTORY_SIZE: usize = 1000;
+
+impl Options {
+    /// Parses the arguments that follow the program name.
+    pub fn parse(arguments: impl IntoIterator<Item = String>) -> Result<Options, String> {
+        let mut options = Options {
+            history_size: DEFAULT_HISTORY_SIZE,
+            script: None,
+            output: None,
+            plugins: Vec::new(),
+            macros: None,
+        };
+
+        let mut arguments = arguments.into_iter();
+        while let Some(argument) = arguments.next() {
+            match argument.as_str() {
+                "--history-size" => {
+                    let value = arguments.next().unwrap_or_default();
+                    options.history_size = value
+                        .parse()
+                        .map_err(|_| format!("'{}' is not a history size. {}", value, USAGE))?;
+                }
+                "--script" | "--output" | "--plugin" | "--macros" => {
+                    let path = arguments
+                        .next()
+                        .ok_or_else(|| format!("Missing <file> after {}. {}", argument, USAGE))?;
+                    match argument.as_str() {
+                        "--script" => options.script = Some(PathBuf::from(path)),
+                        "--output" => options.output = Some(PathBuf::from(path)),
+                        "--macros" => options.macros = Some(PathBuf::from(path)),
+                        _ => options.plugins.push(PathBuf::from(path)),
+                    }
+                }
+                _ => return Err(format!("Unknown option '{}'. {}", argument, USAGE)),
+            }
+        }
+
+        if options.output.is_some() && options.script.is_none() {
+            return Err(format!("--output needs --script. {}", USAGE));
+        }
+        Ok(options)
+    }
+}
+
+#[cfg(test)]
+mod tests_options {
+    use super::{Options, DEFAULT_HISTORY_SIZE, USAGE};
+    use rstest::rstest;
+    use std::path::PathBuf;
+
+    fn parse(arguments: &[&str]) -> Result<Options, String> {
+        Options::parse(arguments.iter().map(|argument| argument.to_string()))
+    }
+
+    #[test]
+    fn defaults_without_arguments() {
+        assert_eq!(
+            parse(&[]),
+            Ok(Options {
+                history_size: DEFAULT_HISTORY_SIZE,
+                script: None,
+                output: None,
+                plugins: Vec::new(),
+                macros: None,
+            })
+        );
+    }
+
+    #[test]
+    fn parses_script_and_output() {
+        let options = parse(&["--output", "out.txt", "--script", "edit.cmds"]).unwrap();
+
+        assert_eq!(options.script, Some(PathBuf::from("edit.cmds")));
+        assert_eq!(options.output, Some(PathBuf::from("out.txt")));
+    }
+
+    #[test]
+    fn collects_plugins() {
+        let options = parse(&["--plugin", "libwc.so", "--plugin", "libsort.so"]).unwrap();
+
+        assert_eq!(
+            options.plugins,
+            vec![PathBuf::from("libwc.so"), PathBuf::from("libsort.so")]
+        );
+    }
+
+    #[test]
+    fn parses_macros_file() {
+        let options = parse(&["--script", "edit.cmds", "--macros", "macros.json"]).unwrap();
+
+        assert_eq!(options.macros, Some(PathBuf::from("macros.json")));
+    }
+
+    #[test]
+    fn parses_history_size() {
+        assert_eq!(parse(&["--history-size", "20"]).unwrap().history_size, 20);
+    }
+
+    #[rstest]
+    #[case(&["--history-size"], "'' is not a history size")]
+    #[case(&["--history-size", "many"], "'many' is not a history size")]
+    #[case(&["--verbose"], "Unknown option '--verbose'")]
+    #[case(&["--script"], "Missing <file> after --script")]
+    #[case(&["--macros"], "Missing <file> after --macros")]
+    #[case(&["--output", "out.txt"], "--output needs --script")]
+    fn reports_invalid_arguments(#[case] arguments: &[&str], #[case] problem: &str) {
+        assert_eq!(parse(arguments), Err(format!("{}. {}", problem, USAGE)));
+    }
+}
@@ -0,0 +1,592 @@
+//! Commands loaded at runtime from dynamic libraries through a C interface.
+//!
+//! A plugin exports `document_editor_plugin`, which returns a description of its commands:
+//!
+//! ```c
+//! typedef struct {
+//!     void *context;
+//!     size_t (*line_count)(void *context);
+//!     /* NULL if there is no such line; valid until the next call */
+//!     const char *(*line)(void *context, size_t index);
+//!     bool (*replace_line)(void *context, size_t index, const char *text);
+//!     bool (*insert_line)(void *context, size_t index, const char *text);
+//!     bool (*erase_line)(void *context, size_t index);
+//!     void (*print_line)(void *context, const char *text);
+//!     void (*print_error)(void *context, const char *text);
+//! } Host;
+//!
+//! typedef struct {
+//!     const char *name;
+//!     const char *summary;
+//!     /* returns false if the command failed, its edits are then reverted */
+//!     bool (*execute)(const Host *host, const char *arguments);
+//! } PluginCommand;
+//!
+//! typedef struct {
+//!     uint32_t abi_version; /* 1 */
+//!     const PluginCommand *commands;
+//!     size_t command_count;
+//! } Plugin;
+//!
+//! const Plugin *document_editor_plugin(void);
+//! ```
+//!
+//! Line indexes start at 0. A plugin only reaches the document and the console through
+//! the host functions, which check every index, and its edits are undone as one change.
+
+use std::cell::RefCell;
+use std::ffi::{c_char, c_void, CStr, CString};
+use std::path::Path;
+use std::rc::Rc;
+
+use libloading::Library;
+
+use crate::arguments::{Parameter, ParameterKind, Signature};
+use crate::buffers::Current;
+use crate::commands::{Command, CommandHistory, CommandParseError, ReversibleCommand};
+use crate::console::Console;
+use crate::document::Document;
+
+/// The version of the interface in `ffi`, raised with every incompatible change.
+pub const ABI_VERSION: u32 = 1;
+
+/// The C types shared with plugins.
+pub mod ffi {
+    use std::ffi::{c_char, c_void};
+
+    pub const ENTRY_POINT: &str = "document_editor_plugin";
+
+    pub type EntryPoint = unsafe extern "C" fn() -> *const Plugin;
+
+    pub type Execute = unsafe extern "C" fn(host: *const Host, arguments: *const c_char) -> bool;
+
+    #[repr(C)]
+    pub struct Host {
+        pub context: *mut c_void,
+        pub line_count: extern "C" fn(context: *mut c_void) -> usize,
+        pub line: extern "C" fn(context: *mut c_void, index: usize) -> *const c_char,
+        pub replace_line: extern "C" fn(context: *mut c_void, index: usize, text: *const c_char) -> bool,
+        pub insert_line: extern "C" fn(context: *mut c_void, index: usize, text: *const c_char) -> bool,
+        pub erase_line: extern "C" fn(context: *mut c_void, index: usize) -> bool,
+        pub print_line: extern "C" fn(context: *mut c_void, text: *const c_char),
+        pub print_error: extern "C" fn(context: *mut c_void, text: *const c_char),
+    }
+
+    #[repr(C)]
+    pub struct PluginCommand {
+        pub name: *const c_char,
+        pub summary: *const c_char,
+        pub execute: Execute,
+    }
+
+    #[repr(C)]
+    pub struct Plugin {
+        pub abi_version: u32,
+        pub commands: *const PluginCommand,
+        pub command_count: usize,
+    }
+}
+
+/// A command found in a plugin, which keeps the plugin loaded.
+#[derive(Clone)]
+pub struct PluginSpec {
+    name: &'static str,
+    summary: &'static str,
+    execute: ffi::Execute,
+    _library: Option<Rc<Library>>,
+}
+
+impl PluginSpec {
+    pub fn name(&self) -> &'static str {
+        self.name
+    }
+}
+
+/// Loads a plugin and returns its commands.
+pub fn load(path: &Path) -> Result<Vec<PluginSpec>, String> {
+    // SAFETY: loading runs the library's initialisers, the library is trusted like the editor.
+    let library = unsafe { Library::new(path) }.map_err(|error| error.to_string())?;
+    // SAFETY: the entry point has the type documented for plugins.
+    let entry_point = unsafe { library.get::<ffi::EntryPoint>(ffi::ENTRY_POINT.as_bytes()) }
+        .map(|symbol| *symbol)
+        .map_err(|_| format!("It does not export {}", ffi::ENTRY_POINT))?;
+
+    // SAFETY: the plugin returns a description that lives as long as the library.
+    let plugin = unsafe { entry_point().as_ref() }.ok_or("It returned no description")?;
+    // SAFETY: the description follows the plugin interface it names, checked first.
+    unsafe { specs(plugin, Some(Rc::new(library))) }
+}
+
+/// The commands of a plugin description, checked for the interface version and names.
+///
+/// # Safety
+/// When `plugin` has the editor's interface version, `commands` must point to
+/// `command_count` commands whose names and summaries are C strings or NULL.
+unsafe fn specs(plugin: &ffi::Plugin, library: Option<Rc<Library>>) -> Result<Vec<PluginSpec>, String> {
+    if plugin.abi_version != ABI_VERSION {
+        return Err(format!(
+            "It was built for plugin interface {}, the editor has {}",
+            plugin.abi_version, ABI_VERSION
+        ));
+    }
+
+    let commands = match plugin.command_count {
+        0 => &[][..],
+        count => std::slice::from_raw_parts(plugin.commands, count),
+    };
+    commands
+        .iter()
+        .map(|command| {
+            let name = text(command.name).unwrap_or_default();
+            if name.is_empty() || name.contains(char::is_whitespace) {
+                return Err(format!("'{}' is not a command name", name));
+            }
+            let summary = text(command.summary).unwrap_or_default();
+
+            Ok(PluginSpec {
+                // the signatures of commands are static, and plugins are never unloaded
+                name: Box::leak(name.into_boxed_str()),
+                summary: Box::leak(summary.into_boxed_str()),
+                execute: command.execute,
+                _library: library.clone(),
+            })
+        })
+        .collect()
+}
+
+/// A line changed by a plugin, with what is needed to change it back.
+#[derive(Debug, PartialEq, Clone)]
+enum Edit {
+    Inserted(usize),
+    Erased(usize, String),
+    Replaced(usize, String),
+}
+
+fn revert(edits: Vec<Edit>, document: &mut Document) {
+    for edit in edits.into_iter().rev() {
+        let _ = match edit {
+            Edit::Inserted(index) => document.erase_line(index).map(|_| ()),
+            Edit::Erased(index, line) => document.insert_line(index, line),
+            Edit::Replaced(index, line) => document.replace_line(index, line).map(|_| ()),
+        };
+    }
+}
+
+/// Reads a C string from a plugin.
+///
+/// # Safety
+/// `text` must be NULL or point to a NUL-terminated string.
+unsafe fn text(text: *const c_char) -> Option<String> {
+    match text.is_null() {
+        true => None,
+        false => Some(CStr::from_ptr(text).to_string_lossy().into_owned()),
+    }
+}
+
+/// What a plugin command reaches while it executes, behind the `context` of `ffi::Host`.
+struct Session<'a> {
+    document: &'a mut Document,
+    console: &'a RefCell<dyn Console>,
+    edits: Vec<Edit>,
+    /// The line last handed to the plugin.
+    line: CString,
+    reported_error: bool,
+}
+
+impl Session<'_> {
+    fn host(&mut self) -> ffi::Host {
+        ffi::Host {
+            context: self as *mut Session as *mut c_void,
+            line_count: host_line_count,
+            line: host_line,
+            replace_line: host_replace_line,
+            insert_line: host_insert_line,
+            erase_line: host_erase_line,
+            print_line: host_print_line,
+            print_error: host_print_error,
+        }
+    }
+
+    /// # Safety
+    /// `context` must be the one of a host made by `Session::host` that is still executing.
+    unsafe fn from_context<'a>(context: *mut c_void) -> &'a mut Session<'a> {
+        &mut *(context as *mut Session)
+    }
+}
+
+extern "C" fn host_line_count(context: *mut c_void) -> usize {
+    let session = unsafe { Session::from_context(context) };
+    session.document.content().len()
+}
+
+extern "C" fn host_line(context: *mut c_void, index: usize) -> *const c_char {
+    let session = unsafe { Session::from_context(context) };
+    let Some(end) = index.checked_add(1) else {
+        return std::ptr::null();
+    };
+    match session.document.lines(index..end) {
+        Ok(lines) => {
+            session.line = CString::new(lines[0].replace('\0', "")).unwrap_or_default();
+            session.line.as_ptr()
+        }
+        Err(_) => std::ptr::null(),
+    }
+}
+
+extern "C" fn host_replace_line(context: *mut c_void, index: usize, text: *const c_char) -> bool {
+    let session = unsafe { Session::from_context(context) };
+    let Some(text) = (unsafe { self::text(text) }) else {
+        return false;
+    };
+    session
+        .document
+        .replace_line(index, text)
+        .map(|old| session.edits.push(Edit::Replaced(index, old)))
+        .is_ok()
+}
+
+extern "C" fn host_insert_line(context: *mut c_void, index: usize, text: *const c_char) -> bool {
+    let session = unsafe { Session::from_context(context) };
+    let Some(text) = (unsafe { self::text(text) }) else {
+        return false;
+    };
+    session
+        .document
+        .insert_line(index, text)
+        .map(|()| session.edits.push(Edit::Inserted(index)))
+        .is_ok()
+}
+
+extern "C" fn host_erase_line(context: *mut c_void, index: usize) -> bool {
+    let session = unsafe { Session::from_context(context) };
+    session
+        .document
+        .erase_line(index)
+        .map(|old| session.edits.push(Edit::Erased(index, old)))
+        .is_ok()
+}
+
+extern "C" fn host_print_line(context: *mut c_void, text: *const c_char) {
+    let session = unsafe { Session::from_context(context) };
+    if let Some(text) = unsafe { self::text(text) } {
+        session.console.borrow_mut().print_line(&text);
+    }
+}
+
+extern "C" fn host_print_error(context: *mut c_void, text: *const c_char) {
+    let session = unsafe { Session::from_context(context) };
+    if let Some(text) = unsafe { self::text(text) } {
+        session.console.borrow_mut().print_error(&text);
+        session.reported_error = true;
+    }
+}
+
+/// A command provided by a plugin, given the rest of the command line as its arguments.
+#[derive(Clone)]
+pub struct PluginCommand {
+    document: Current<Document>,
+    command_history: Current<CommandHistory>,
+    console: Rc<RefCell<dyn Console>>,
+    spec: PluginSpec,
+    arguments: Option<String>,
+    /// The lines the plugin changed, to undo only what changed.
+    edits: Vec<Edit>,
+}
+
+impl PluginCommand {
+    pub fn new(
+        spec: PluginSpec,
+        document: impl Into<Current<Document>>,
+        command_history: impl Into<Current<CommandHistory>>,
+        console: Rc<RefCell<dyn Console>>,
+    ) -> PluginCommand {
+        PluginCommand {
+            document: document.into(),
+            command_history: command_history.into(),
+            console,
+            spec,
+            arguments: None,
+            edits: Vec::new(),
+        }
+    }
+}
+
+impl Command for PluginCommand {
+    fn execute(&mut self) {
+        let Some(arguments) = &self.arguments else {
+            return;
+        };
+        let arguments = CString::new(arguments.replace('\0', "")).unwrap_or_default();
+
+        let document = self.document.get();
+        let mut document = document.borrow_mut();
+        let mut session = Session {
+            document: &mut document,
+            console: &*self.console,
+            edits: Vec::new(),
+            line: CString::default(),
+            reported_error: false,
+        };
+        let host = session.host();
+        // SAFETY: the plugin follows the documented interface, and `host` outlives the call.
+        let succeeded = unsafe { (self.spec.execute)(&host, arguments.as_ptr()) };
+        let (edits, reported_error) = (session.edits, session.reported_error);
+
+        if !succeeded {
+            revert(edits, &mut document);
+            if !reported_error {
+                let message = format!("{} failed", self.spec.name);
+                self.console.borrow_mut().print_error(&message);
+            }
+            return;
+        }
+
+        drop(document);
+        self.edits = edits;
+        if !self.edits.is_empty() {
+            self.command_history.get().borrow_mut().add(Box::new(Clone::clone(self)));
+        }
+    }
+
+    fn parse(&mut self, command: &str) -> Result<(), CommandParseError> {
+        let arguments = self.signature().parse(command)?;
+        self.arguments = Some(arguments.text("arguments").unwrap_or_default().to_string());
+        Ok(())
+    }
+
+    fn signature(&self) -> Signature {
+        Signature::new(self.spec.name, self.spec.summary)
+            .with(Parameter::optional("arguments", ParameterKind::Text))
+    }
+}
+
+impl ReversibleCommand for PluginCommand {
+    fn undo(&mut self) {
+        revert(std::mem::take(&mut self.edits), &mut self.document.get().borrow_mut());
+    }
+
+    fn clone(&self) -> Box<dyn ReversibleCommand> {
+        Box::new(Clone::clone(self))
+    }
+}
+
+#[cfg(test)]
+mod tests_plugins {
+    use super::{ffi, ffi::Host, load, specs, PluginCommand, PluginSpec, ABI_VERSION};
+    use crate::commands::test_commands_helpers::{command_history, document};
+    use crate::commands::{Command, CommandHistory};
+    use crate::console::MockConsole;
+    use crate::document::Document;
+    use mockall::predicate::eq;
+    use rstest::rstest;
+    use std::ffi::{c_char, CStr};
+    use std::path::Path;
+    use std::{cell::RefCell, rc::Rc};
+
+    /// Upper-cases every line.
+    unsafe extern "C" fn shout(host: *const Host, _arguments: *const c_char) -> bool {
+        let host = &*host;
+        for index in 0..(host.line_count)(host.context) {
+            let line = CStr::from_ptr((host.line)(host.context, index)).to_string_lossy();
+            let line = format!("{}\0", line.to_uppercase());
+            (host.replace_line)(host.context, index, line.as_ptr().cast());
+        }
+        true
+    }
+
+    /// Prints its arguments and checks that indexes out of range are refused.
+    unsafe extern "C" fn echo(host: *const Host, arguments: *const c_char) -> bool {
+        let host = &*host;
+        let refused = (host.line)(host.context, 3).is_null()
+            && (host.line)(host.context, usize::MAX).is_null()
+            && !(host.erase_line)(host.context, 3)
+            && !(host.erase_line)(host.context, usize::MAX)
+            && !(host.replace_line)(host.context, usize::MAX, c"x".as_ptr())
+            && !(host.insert_line)(host.context, 5, c"x".as_ptr())
+            && !(host.insert_line)(host.context, usize::MAX, c"x".as_ptr());
+        (host.print_line)(host.context, arguments);
+        refused
+    }
+
+    /// Fails after an edit, reporting why.
+    unsafe extern "C" fn reject(host: *const Host, _arguments: *const c_char) -> bool {
+        let host = &*host;
+        (host.erase_line)(host.context, 0);
+        (host.print_error)(host.context, c"Nothing to reject".as_ptr());
+        false
+    }
+
+    /// Fails after an edit, without a word.
+    unsafe extern "C" fn crash(host: *const Host, _arguments: *const c_char) -> bool {
+        let host = &*host;
+        (host.insert_line)(host.context, 0, c"partial".as_ptr());
+        false
+    }
+
+    fn plugin_command(
+        name: &'static str,
+        execute: super::ffi::Execute,
+        document: &Rc<RefCell<Document>>,
+        command_history: &Rc<RefCell<CommandHistory>>,
+        console: MockConsole,
+    ) -> PluginCommand {
+        let spec = PluginSpec {
+            name,
+            summary: "A test plugin",
+            execute,
+            _library: None,
+        };
+        PluginCommand::new(
+            spec,
+            document.clone(),
+            command_history.clone(),
+            Rc::new(RefCell::new(console)),
+        )
+    }
+
+    #[rstest]
+    fn execute_edits_through_the_host_and_undo_reverts(
+        document: Rc<RefCell<Document>>,
+        command_history: Rc<RefCell<CommandHistory>>,
+    ) {
+        let mut cmd = plugin_command("Shout", shout, &document, &command_history, MockConsole::new());
+
+        cmd.parse("Shout").unwrap();
+        cmd.execute();
+        assert_eq!(document.borrow().content(), &vec!["LINE1", "LINE2", "LINE3"]);
+        assert_eq!(command_history.borrow().commands.len(), 1);
+
+        command_history.borrow_mut().undo();
+        assert_eq!(document.borrow().content(), &vec!["Line1", "Line2", "Line3"]);
+    }
+
+    #[rstest]
+    fn execute_passes_arguments_and_refuses_lines_out_of_range(
+        document: Rc<RefCell<Document>>,
+        command_history: Rc<RefCell<CommandHistory>>,
+    ) {
+        let mut console = MockConsole::new();
+        console
+            .expect_print_line()
+            .with(eq("two  words"))
+            .times(1)
+            .returning(|_| ());
+        let mut cmd = plugin_command("Echo", echo, &document, &command_history, console);
+
+        cmd.parse("Echo \"two  words\"").unwrap();
+        cmd.execute();
+
+        assert_eq!(document.borrow().content(), &vec!["Line1", "Line2", "Line3"]);
+        assert_eq!(command_history.borrow().commands.len(), 0);
+    }
+
+    #[rstest]
+    #[case("Reject", reject, "Nothing to reject")]
+    #[case("Crash", crash, "Crash failed")]
+    fn failed_execution_reverts_edits_and_reports_error(
+        #[case] name: &'static str,
+        #[case] execute: super::ffi::Execute,
+        #[case] message: &'static str,
+        document: Rc<RefCell<Document>>,
+        command_history: Rc<RefCell<CommandHistory>>,
+    ) {
+        let mut console = MockConsole::new();
+        console
+            .expect_print_error()
+            .with(eq(message))
+            .times(1)
+            .returning(|_| ());
+        let mut cmd = plugin_command(name, execute, &document, &command_history, console);
+
+        cmd.parse(name).unwrap();
+        cmd.execute();
+
+        assert_eq!(document.borrow().content(), &vec!["Line1", "Line2", "Line3"]);
+        assert_eq!(command_history.borrow().commands.len(), 0);
+    }
+
+    #[rstest]
+    fn signature_uses_the_plugin_name_and_summary(
+        document: Rc<RefCell<Document>>,
+        command_history: Rc<RefCell<CommandHistory>>,
+    ) {
+        let cmd = plugin_command("Shout", shout, &document, &command_history, MockConsole::new());
+
+        let signature = cmd.signature();
+
+        assert_eq!(signature.usage(), "Shout [arguments...]");
+        assert_eq!(signature.summary, "A test plugin");
+    }
+
+    #[test]
+    fn loading_missing_library_fails() {
+        assert!(load(Path::new("/nonexistent/libplugin.so")).is_err());
+    }
+
+    fn plugin_command_description(name: *const c_char) -> ffi::PluginCommand {
+        ffi::PluginCommand {
+            name,
+            summary: c"Upper-cases every line".as_ptr(),
+            execute: shout,
+        }
+    }
+
+    #[test]
+    fn specs_describe_the_commands_of_a_plugin() {
+        let commands = [
+            plugin_command_description(c"Shout".as_ptr()),
+            ffi::PluginCommand {
+                summary: std::ptr::null(),
+                ..plugin_command_description(c"Whisper".as_ptr())
+            },
+        ];
+        let plugin = ffi::Plugin {
+            abi_version: ABI_VERSION,
+            commands: commands.as_ptr(),
+            command_count: commands.len(),
+        };
+
+        let specs = unsafe { specs(&plugin, None) }.unwrap();
+
+        let described: Vec<_> = specs.iter().map(|spec| (spec.name, spec.summary)).collect();
+        assert_eq!(described, vec![("Shout", "Upper-cases every line"), ("Whisper", "")]);
+    }
+
+    #[test]
+    fn specs_refuse_another_plugin_interface() {
+        let plugin = ffi::Plugin {
+            abi_version: ABI_VERSION + 1,
+            commands: std::ptr::null(),
+            command_count: 0,
+        };
+
+        let error = unsafe { specs(&plugin, None) }.err();
+
+        assert_eq!(
+            error,
+            Some(format!(
+                "It was built for plugin interface {}, the editor has {}",
+                ABI_VERSION + 1,
+                ABI_VERSION
+            ))
+        );
+    }
+
+    #[rstest]
+    #[case(c"".as_ptr(), "'' is not a command name")]
+    #[case(c"Two words".as_ptr(), "'Two words' is not a command name")]
+    #[case(c"Tab\tName".as_ptr(), "'Tab\tName' is not a command name")]
+    #[case(std::ptr::null(), "'' is not a command name")]
+    fn specs_refuse_empty_or_spaced_command_names(#[case] name: *const c_char, #[case] expected: &str) {
+        let commands = [plugin_command_description(c"Shout".as_ptr()), plugin_command_description(name)];
+        let plugin = ffi::Plugin {
+            abi_version: ABI_VERSION,
+            commands: commands.as_ptr(),
+            command_count: commands.len(),
+        };
+
+        let error = unsafe { specs(&plugin, None) }.err();
+
+        assert_eq!(error.as_deref(), Some(expected));
+    }
+}
@@ -0,0 +1,141 @@
+use std::fmt;
+use std::fs;
+use std::io;
+use std::path::Path;
+
+/// The file formats a document can be read from and written to, detected from the file extension.
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub enum FileFormat {
+    Text,
+    /// Read and written like plain text.
+    Markdown,
+    /// A JSON array with one string per line.
+    Json,
+}
+
+impl FileFormat {
+    pub fn detect(path: &Path) -> FileFormat {
+        let extension = path
+            .extension()
+            .and_then(|extension| extension.to_str())
+            .map(|extension| extension.to_ascii_lowercase());
+
+        match extension.as_deref() {
+            Some("md" | "markdown") => FileFormat::Markdown,
+            Some("json") => FileFormat::Json,
+            _ => FileFormat::Text,
+        }
+    }
+
+    pub fn read(&self, path: &Path) -> io::Result<Vec<String>> {
+        let content = fs::read_to_string(path)?;
+        match self {
+            FileFormat::Text | FileFormat::Markdown => {
+                Ok(content.lines().map(|line| line.to_string()).collect())
+            }
+            FileFormat::Json => serde_json::from_str(&content)
+                .map_err(|error| io::Error::new(io::ErrorKind::InvalidData, error)),
+        }
+    }
+
+    pub fn write(&self, path: &Path, lines: &[String]) -> io::Result<()> {
+        let content = match self {
+            FileFormat::Text | FileFormat::Markdown => {
+                lines.iter().map(|line| format!("{}\n", line)).collect()
+            }
+            FileFormat::Json => serde_json::to_string_pretty(lines)? + "\n",
+        };
+        fs::write(path, content)
+    }
+}
+
+impl fmt::Display for FileFormat {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            FileFormat::Text => write!(f, "text"),
+            FileFormat::Markdown => write!(f, "Markdown"),
+            FileFormat::Json => write!(f, "JSON"),
+        }
+    }
+}
+
+#[cfg(test)]
+pub(crate) mod tests_storage {
+    use super::FileFormat;
+    use rstest::rstest;
+    use std::path::{Path, PathBuf};
+
+    /// A path in the temporary directory that is removed again when dropped.
+    pub(crate) struct TempFile(pub PathBuf);
+
+    impl TempFile {
+        pub(crate) fn new(name: &str) -> TempFile {
+            let name = format!("document-editor-{}-{}", std::process::id(), name);
+            TempFile(std::env::temp_dir().join(name))
+        }
+    }
+
+    impl Drop for TempFile {
+        fn drop(&mut self) {
+            let _ = std::fs::remove_file(&self.0);
+        }
+    }
+
+    fn lines() -> Vec<String> {
+        vec!["# Title".to_string(), "".to_string(), "Say \"hi\"".to_string()]
+    }
+
+    #[rstest]
+    #[case("notes.txt", FileFormat::Text)]
+    #[case("notes", FileFormat::Text)]
+    #[case("README.md", FileFormat::Markdown)]
+    #[case("guide.Markdown", FileFormat::Markdown)]
+    #[case("lines.json", FileFormat::Json)]
+    fn detects_format_from_extension(#[case] path: &str, #[case] expected: FileFormat) {
+        assert_eq!(FileFormat::detect(Path::new(path)), expected);
+    }
+
+    #[rstest]
+    #[case("roundtrip.txt")]
+    #[case("roundtrip.md")]
+    #[case("roundtrip.json")]
+    fn written_lines_are_read_back(#[case] name: &str) {
+        let file = TempFile::new(name);
+        let format = FileFormat::detect(&file.0);
+
+        format.write(&file.0, &lines()).unwrap();
+
+        assert_eq!(format.read(&file.0).unwrap(), lines());
+    }
+
+    #[test]
+    fn json_is_written_as_array_of_lines() {
+        let file = TempFile::new("array.json");
+
+        FileFormat::Json.write(&file.0, &["a".to_string(), "b".to_string()]).unwrap();
+
+        assert_eq!(
+            std::fs::read_to_string(&file.0).unwrap(),
+            "[\n  \"a\",\n  \"b\"\n]\n"
+        );
+    }
+
+    #[test]
+    fn reading_json_that_is_not_an_array_of_strings_fails() {
+        let file = TempFile::new("invalid.json");
+        std::fs::write(&file.0, "{\"line\": 1}").unwrap();
+
+        let error = FileFormat::Json.read(&file.0).unwrap_err();
+
+        assert_eq!(error.kind(), std::io::ErrorKind::InvalidData);
+    }
+
+    #[test]
+    fn reading_missing_file_fails() {
+        let file = TempFile::new("missing.txt");
+
+        let error = FileFormat::Text.read(&file.0).unwrap_err();
+
+        assert_eq!(error.kind(), std::io::ErrorKind::NotFound);
+    }
+}